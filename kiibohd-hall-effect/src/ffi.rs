@@ -0,0 +1,162 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// C accessor layer
+// Sensors<S, H> is generic so it can't cross FFI, the firmware registers its instance once
+// (see register) and C code goes through the he_sensor_* functions.
+// Header: include/kiibohd_hall_effect.h (cbindgen, see cbindgen.toml)
+
+// ----- Crates -----
+
+use super::*;
+use core::ptr::addr_of_mut;
+
+// ----- Status Codes -----
+
+/// Reading accumulated, no new analysis yet
+pub const HE_PENDING: i32 = 0;
+/// Reading completed a sample window, a new analysis is ready
+pub const HE_READY: i32 = 1;
+/// Reading completed a sample window, but the sensor is not calibrated (see he_sensor_status)
+pub const HE_NOT_CALIBRATED: i32 = 2;
+
+/// SensorError::InvalidFrameLength
+pub const HE_ERROR_INVALID_FRAME_LENGTH: i32 = -2;
+/// SensorError::InvalidLookupTable
+pub const HE_ERROR_INVALID_LOOKUP_TABLE: i32 = -3;
+/// SensorError::InvalidPair
+pub const HE_ERROR_INVALID_PAIR: i32 = -4;
+/// SensorError::InvalidRemap
+pub const HE_ERROR_INVALID_REMAP: i32 = -5;
+/// SensorError::InvalidSampleCount
+pub const HE_ERROR_INVALID_SAMPLE_COUNT: i32 = -6;
+/// SensorError::InvalidSensor
+pub const HE_ERROR_INVALID_SENSOR: i32 = -7;
+/// No Sensors instance has been registered (see register)
+pub const HE_ERROR_NOT_REGISTERED: i32 = -100;
+
+// ----- Calibration Status -----
+
+/// CalibrationStatus::NotReady
+pub const HE_STATUS_NOT_READY: i32 = CalibrationStatus::NotReady as i32;
+/// CalibrationStatus::SensorMissing
+pub const HE_STATUS_SENSOR_MISSING: i32 = CalibrationStatus::SensorMissing as i32;
+/// CalibrationStatus::SensorBroken
+pub const HE_STATUS_SENSOR_BROKEN: i32 = CalibrationStatus::SensorBroken as i32;
+/// CalibrationStatus::MagnetDetected
+pub const HE_STATUS_MAGNET_DETECTED: i32 = CalibrationStatus::MagnetDetected as i32;
+/// CalibrationStatus::MagnetWrongPoleOrMissing
+pub const HE_STATUS_MAGNET_WRONG_POLE_OR_MISSING: i32 =
+    CalibrationStatus::MagnetWrongPoleOrMissing as i32;
+/// CalibrationStatus::InvalidIndex
+pub const HE_STATUS_INVALID_INDEX: i32 = CalibrationStatus::InvalidIndex as i32;
+
+// ----- Registration -----
+
+/// Type erased Sensors (the const generics are fixed by the firmware)
+trait Registered {
+    fn add(&mut self, index: usize, reading: u16) -> Result<Measurement<'_>, SensorError>;
+    /// Calibration status and latest analysis of a sensor
+    fn state(&self, index: usize) -> Result<(&CalibrationStatus, &SenseAnalysis), SensorError>;
+}
+
+impl<const S: usize, const H: usize, const CAL_CONFIRM: usize> Registered
+    for Sensors<S, H, CAL_CONFIRM>
+{
+    fn add(&mut self, index: usize, reading: u16) -> Result<Measurement<'_>, SensorError> {
+        self.add_runtime(index, reading)
+    }
+
+    fn state(&self, index: usize) -> Result<(&CalibrationStatus, &SenseAnalysis), SensorError> {
+        self.sensors
+            .get(index)
+            .map(|data| (&data.cal, &data.analysis))
+            .ok_or(SensorError::InvalidSensor(index))
+    }
+}
+
+static mut REGISTERED: Option<&'static mut dyn Registered> = None;
+
+/// Register the Sensors instance used by the he_sensor_* functions
+/// Replaces any previously registered instance.
+///
+/// # Safety
+/// Must not be called while a he_sensor_* function is running (e.g. from an interrupt).
+pub unsafe fn register<const S: usize, const H: usize, const CAL_CONFIRM: usize>(
+    sensors: &'static mut Sensors<S, H, CAL_CONFIRM>,
+) {
+    *addr_of_mut!(REGISTERED) = Some(sensors);
+}
+
+/// Registered Sensors instance
+///
+/// # Safety
+/// No other reference to the instance may be alive (the he_sensor_* functions are not
+/// reentrant).
+unsafe fn registered() -> Result<&'static mut dyn Registered, i32> {
+    match &mut *addr_of_mut!(REGISTERED) {
+        Some(sensors) => Ok(&mut **sensors),
+        None => Err(HE_ERROR_NOT_REGISTERED),
+    }
+}
+
+// ----- External C Interface -----
+
+/// Add a raw ADC reading for the given ADC channel (see Sensors::add_runtime)
+/// Returns HE_READY once a new analysis is ready, HE_PENDING while accumulating,
+/// HE_NOT_CALIBRATED if the sensor is not calibrated or a negative error code
+/// (SensorError::code or HE_ERROR_NOT_REGISTERED).
+///
+/// # Safety
+/// Not reentrant, must not be called concurrently with any other he_sensor_* function.
+#[no_mangle]
+pub unsafe extern "C" fn he_sensor_add(index: u16, reading: u16) -> i32 {
+    let sensors = match registered() {
+        Ok(sensors) => sensors,
+        Err(code) => return code,
+    };
+    match sensors.add(index as usize, reading) {
+        Ok(measurement) => measurement.code(),
+        Err(err) => err.code(),
+    }
+}
+
+/// Distance of the latest analysis of the given sensor
+/// 0 if the sensor is not calibrated (MagnetDetected), invalid or nothing is registered.
+/// Use he_sensor_status to tell these apart.
+///
+/// # Safety
+/// Not reentrant, must not be called concurrently with any other he_sensor_* function.
+#[no_mangle]
+pub unsafe extern "C" fn he_sensor_distance(index: u16) -> i16 {
+    let sensors = match registered() {
+        Ok(sensors) => sensors,
+        Err(_) => return 0,
+    };
+    match sensors.state(index as usize) {
+        Ok((CalibrationStatus::MagnetDetected, analysis)) => analysis.distance(),
+        _ => 0,
+    }
+}
+
+/// Calibration status of the given sensor
+/// Returns the (non-negative) CalibrationStatus value (HE_STATUS_*) or a negative error code
+/// (SensorError::code or HE_ERROR_NOT_REGISTERED).
+///
+/// # Safety
+/// Not reentrant, must not be called concurrently with any other he_sensor_* function.
+#[no_mangle]
+pub unsafe extern "C" fn he_sensor_status(index: u16) -> i32 {
+    let sensors = match registered() {
+        Ok(sensors) => sensors,
+        Err(code) => return code,
+    };
+    match sensors.state(index as usize) {
+        Ok((cal, _)) => cal.clone() as i32,
+        Err(err) => err.code(),
+    }
+}
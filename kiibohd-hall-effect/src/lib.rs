@@ -9,7 +9,11 @@
 
 #![no_std]
 
+#[cfg(feature = "ffi")]
+pub mod ffi;
 mod rawlookup;
+#[cfg(feature = "sim")]
+pub mod sim;
 mod test;
 
 // ----- Crates -----
@@ -23,8 +27,23 @@ mod test;
     feature = "defmt-error"
 ))]
 use defmt::*;
-use heapless::Vec;
+#[cfg(all(
+    feature = "log",
+    not(any(
+        feature = "defmt-default",
+        feature = "defmt-trace",
+        feature = "defmt-debug",
+        feature = "defmt-info",
+        feature = "defmt-warn",
+        feature = "defmt-error"
+    ))
+))]
+use log::*;
+
+// No logging backend, tracing is compiled out
+// Arguments are still type checked (but never evaluated) so they don't become unused.
 #[cfg(not(any(
+    feature = "log",
     feature = "defmt-default",
     feature = "defmt-trace",
     feature = "defmt-debug",
@@ -32,13 +51,168 @@ use heapless::Vec;
     feature = "defmt-warn",
     feature = "defmt-error"
 )))]
-use log::*;
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, Ordering};
 
 // TODO Use features to determine which lookup table to use
 use rawlookup::MODEL;
 
+// ----- Lookup Table -----
+
+/// Describes a raw ADC reading to distance lookup table
+/// The physical scale is part of the table description so alternate tables carry their own
+/// calibration. One distance unit is um_num / um_den micrometers of travel.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct LookupTable {
+    /// Linearized distance, indexed by raw ADC reading
+    pub table: &'static [i16],
+    /// Micrometers per distance unit (numerator)
+    pub um_num: u32,
+    /// Micrometers per distance unit (denominator), must be non-zero
+    pub um_den: u32,
+}
+
+impl LookupTable {
+    /// Convert distance units (lookup table units) to micrometers
+    /// Integer math only, rounded to the nearest micrometer (ties away from zero).
+    pub const fn to_um(&self, distance: i32) -> i32 {
+        let scaled = distance as i64 * self.um_num as i64;
+        let half = self.um_den as i64 / 2;
+        let um = if scaled < 0 {
+            (scaled - half) / self.um_den as i64
+        } else {
+            (scaled + half) / self.um_den as i64
+        };
+        um as i32
+    }
+}
+
+/// Default lookup table (MODEL)
+/// Scale is approximately 4 um per unit (roughly 1000 units over 4 mm of travel, measured on a
+/// Keystone v1.00 TKL pcb).
+pub const DEFAULT_LOOKUP_TABLE: LookupTable = LookupTable {
+    table: &MODEL,
+    um_num: 4,
+    um_den: 1,
+};
+
+/// Maximum number of lookup tables that can be registered with a Sensors instance
+/// Table 0 is DEFAULT_LOOKUP_TABLE unless replaced.
+pub const MAX_LOOKUP_TABLES: usize = 4;
+
+/// Linearize a raw ADC reading using the default lookup table (MODEL)
+/// Readings past the end of the table clamp to the last entry.
+/// The result is the absolute table value (not relative to a calibrated rest position).
+pub fn linearize(raw: u16) -> i16 {
+    MODEL[(raw as usize).min(MODEL.len() - 1)]
+}
+
+/// Linearize a raw ADC reading using the default lookup table (MODEL)
+/// Returns None if the reading is past the end of the table.
+pub fn linearize_checked(raw: u16) -> Option<i16> {
+    MODEL.get(raw as usize).copied()
+}
+
 // ----- Sense Data -----
 
+/// Maximum ADC reading (12-bit ADC, size of the lookup table)
+/// Inverted sensors are mirrored around the ADC midpoint (ADC_MAX - reading).
+pub const ADC_MAX: u16 = MODEL.len() as u16 - 1;
+
+/// Native resolution of the lookup tables (ADC_MAX + 1 entries)
+/// Readings from other resolutions are scaled into this domain (see SensorsConfig::adc_bits),
+/// so all thresholds (MNOK, MXOK, NS) and raw values are in 12-bit counts.
+pub const TABLE_BITS: u8 = 12;
+
+/// Scale a reading from the source ADC resolution into the lookup table domain
+/// Higher resolutions are shifted down, lower resolutions shifted up.
+/// Readings beyond the range of the source resolution saturate at ADC_MAX.
+#[inline]
+fn scale_reading(reading: u16, bits: u8) -> u16 {
+    let reading = reading as u32;
+    let scaled = if bits >= TABLE_BITS {
+        reading >> (bits - TABLE_BITS).min(16)
+    } else {
+        reading << (TABLE_BITS - bits)
+    };
+    scaled.min(ADC_MAX as u32) as u16
+}
+
+/// Compile-time calibration threshold (MNOK, MXOK, NS) as a table domain reading
+/// Saturates instead of wrapping for thresholds that don't fit in a u16.
+const fn threshold(val: usize) -> u16 {
+    if val > u16::MAX as usize {
+        u16::MAX
+    } else {
+        val as u16
+    }
+}
+
+/// Maximum absolute velocity still considered stationary for idle detection
+pub const IDLE_VELOCITY: u16 = 1;
+
+/// Min/max spread (ADC counts) considered a healthy, fully confident calibration
+/// (see SenseData::confidence)
+pub const CONFIDENCE_SPREAD: u16 = 512;
+
+/// Analysis samples after which a narrow min/max spread is no longer given the benefit of the
+/// doubt (see SenseData::confidence)
+pub const CONFIDENCE_SAMPLES: u32 = 1024;
+
+/// Distance below which a key is considered fully released (resets the peak velocity)
+pub const RELEASE_DISTANCE: i16 = 8;
+
+/// Histogram of raw readings
+/// The ADC range is split into BINS bins of equal width (bin_width). Counts saturate.
+#[derive(Clone, Debug, PartialEq, defmt::Format)]
+pub struct Histogram<const BINS: usize> {
+    bins: [u16; BINS],
+}
+
+impl<const BINS: usize> Histogram<BINS> {
+    pub fn new() -> Histogram<BINS> {
+        Histogram { bins: [0; BINS] }
+    }
+
+    /// Width of each bin in ADC counts
+    pub const fn bin_width() -> u16 {
+        ((ADC_MAX as usize + BINS) / BINS) as u16
+    }
+
+    /// Count a raw reading
+    pub fn add(&mut self, reading: u16) {
+        let bin = (reading.min(ADC_MAX) / Self::bin_width()) as usize;
+        self.bins[bin] = self.bins[bin].saturating_add(1);
+    }
+
+    /// Bin counts, bin n covers readings n * bin_width..(n + 1) * bin_width
+    pub fn bins(&self) -> &[u16; BINS] {
+        &self.bins
+    }
+
+    /// Clear all bins
+    pub fn clear(&mut self) {
+        self.bins = [0; BINS];
+    }
+}
+
+impl<const BINS: usize> Default for Histogram<BINS> {
+    fn default() -> Self {
+        Histogram::new()
+    }
+}
+
+/// Number of bins in each SenseData histogram
+#[cfg(feature = "histogram")]
+pub const HISTOGRAM_BINS: usize = 64;
+
 /// Calibration status indicates if a sensor position is ready to send
 /// analysis for a particular key.
 #[repr(C)]
@@ -52,36 +226,180 @@ pub enum CalibrationStatus {
     InvalidIndex = 5, // Invalid index
 }
 
+/// Calibration status change of a sensor
+#[derive(Clone, Debug, PartialEq, defmt::Format)]
+pub struct CalibrationTransition {
+    pub from: CalibrationStatus,
+    pub to: CalibrationStatus,
+}
+
+/// Hot-swap state change of a sensor (see SensorsConfig::hot_swap)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum HotSwapEvent {
+    /// A calibrated sensor lost its magnet (e.g. the switch was pulled), the grace period starts
+    Removed,
+    /// A magnet reappeared within the grace period, seeded if the previous rest position was
+    /// restored (the reading was within HotSwap::tolerance of it)
+    Reinserted { seeded: bool },
+    /// The grace period elapsed, the previous rest position is forgotten
+    Expired,
+}
+
+/// Number of CalibrationStatus variants (size of Sensors::status_summary)
+pub const CALIBRATION_STATUS_COUNT: usize = 6;
+
+impl CalibrationStatus {
+    /// Sensor is in a fault state (missing or broken sensor, or wrong/missing magnet)
+    /// NotReady is not a fault, the sensor just hasn't been calibrated yet.
+    pub fn is_fault(&self) -> bool {
+        matches!(
+            self,
+            CalibrationStatus::SensorMissing
+                | CalibrationStatus::SensorBroken
+                | CalibrationStatus::MagnetWrongPoleOrMissing
+        )
+    }
+}
+
 #[derive(Clone, Debug, defmt::Format)]
 pub enum SensorError {
-    CalibrationError(SenseData),
-    FailedToResize(usize),
+    /// Sensor is not calibrated, with its calibration status
+    /// See Sensors::get_data_mut for the sensor data of an uncalibrated sensor.
+    CalibrationError(CalibrationStatus),
+    InvalidFrameLength(usize),
+    InvalidLookupTable(u8),
+    /// Sensors can't be paired (same sensor, or one of them is already paired)
+    InvalidPair(usize, usize),
+    /// Invalid channel remap table entry (position), out of range or a duplicate
+    InvalidRemap(usize),
+    InvalidSampleCount(u8),
     InvalidSensor(usize),
 }
 
+impl SensorError {
+    /// Stable (negative) numeric error code, e.g. for C firmware
+    /// These values must not change; new variants get a new code.
+    pub const fn code(&self) -> i32 {
+        match self {
+            SensorError::CalibrationError(_) => -1,
+            SensorError::InvalidFrameLength(_) => -2,
+            SensorError::InvalidLookupTable(_) => -3,
+            SensorError::InvalidPair(_, _) => -4,
+            SensorError::InvalidRemap(_) => -5,
+            SensorError::InvalidSampleCount(_) => -6,
+            SensorError::InvalidSensor(_) => -7,
+        }
+    }
+}
+
+/// Result of adding a reading to a sensor (see Sensors::add)
+/// Calibration states are routine (e.g. no magnet detected yet during boot) so they're reported
+/// here rather than as a SensorError.
+#[derive(Clone, Debug, defmt::Format)]
+pub enum Measurement<'a> {
+    /// Still accumulating samples, no new analysis
+    Pending,
+    /// Analysis of the completed sample window
+    Ready(&'a SenseAnalysis),
+    /// Sensor is not calibrated (test mode only), NotReady or a fault
+    NotCalibrated(CalibrationStatus),
+}
+
+impl<'a> Measurement<'a> {
+    /// Still accumulating samples
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Measurement::Pending)
+    }
+
+    /// New analysis, if one was computed
+    pub fn analysis(&self) -> Option<&'a SenseAnalysis> {
+        match self {
+            Measurement::Ready(analysis) => Some(analysis),
+            _ => None,
+        }
+    }
+
+    /// Calibration status if the sensor is not calibrated
+    pub fn not_calibrated(&self) -> Option<CalibrationStatus> {
+        match self {
+            Measurement::NotCalibrated(cal) => Some(cal.clone()),
+            _ => None,
+        }
+    }
+
+    /// Stable (non-negative) numeric code, e.g. for C firmware (see SensorError::code)
+    /// These values must not change; new variants get a new code.
+    pub const fn code(&self) -> i32 {
+        match self {
+            Measurement::Pending => 0,
+            Measurement::Ready(_) => 1,
+            Measurement::NotCalibrated(_) => 2,
+        }
+    }
+}
+
+/// Saturate a widened calculation back to i16
+#[inline]
+fn saturate(val: i32) -> i16 {
+    val.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Round an f32 calculation to the nearest count and saturate to i16
+/// (float to int casts saturate)
+#[cfg(feature = "f32")]
+fn saturate_f32(val: f32) -> i16 {
+    (if val < 0.0 { val - 0.5 } else { val + 0.5 }) as i16
+}
+
 /// Calculations:
 ///  d = linearized(adc sample) --> distance
 ///  v = (d - d_prev) / 1       --> velocity
 ///  a = (v - v_prev) / 2       --> acceleration
 ///  j = (a - a_prev) / 3       --> jerk
+///  sv += (v - sv) >> shift     --> smoothed velocity (optional)
 ///
 /// These calculations assume constant time delta of 1
 #[repr(C)]
 #[derive(Clone, Debug, defmt::Format)]
 pub struct SenseAnalysis {
-    raw: u16,          // Raw ADC reading
-    distance: i16,     // Distance value (lookup + min/max alignment)
-    velocity: i16,     // Velocity calculation (*)
-    acceleration: i16, // Acceleration calculation (*)
-    jerk: i16,         // Jerk calculation (*)
+    raw: u16,               // Raw ADC reading
+    distance: i16,          // Distance value (lookup + min/max alignment)
+    velocity: i16,          // Velocity calculation (*)
+    acceleration: i16,      // Acceleration calculation (*)
+    jerk: i16,              // Jerk calculation (*)
+    smoothed_velocity: i16, // Exponentially smoothed velocity (see SensorsConfig::velocity_smoothing)
 }
 
 impl SenseAnalysis {
     /// Using the raw value do calculations
-    /// Requires the previous analysis
-    pub fn new(raw: u16, data: &SenseData) -> SenseAnalysis {
-        // Do raw lookup (we've already checked the bounds)
-        let initial_distance = MODEL[raw as usize];
+    /// Requires the previous analysis, and the lookup table selected by data.table_index
+    /// (see Sensors::lookup_table)
+    /// Calculated in f32 with the f32 feature (see new_f32), integer math otherwise.
+    pub fn new<const H: usize>(
+        raw: u16,
+        data: &SenseData<H>,
+        table: &LookupTable,
+    ) -> SenseAnalysis {
+        #[cfg(feature = "f32")]
+        {
+            SenseAnalysis::new_f32(raw, data, table)
+        }
+        #[cfg(not(feature = "f32"))]
+        {
+            SenseAnalysis::new_int(raw, data, table)
+        }
+    }
+
+    /// Integer analysis (default, for targets without an FPU)
+    #[cfg_attr(feature = "f32", allow(dead_code))]
+    fn new_int<const H: usize>(
+        raw: u16,
+        data: &SenseData<H>,
+        table: &LookupTable,
+    ) -> SenseAnalysis {
+        // Do raw lookup (lookup_distance clamps to the end of the table)
+        // In passthrough mode the raw reading is used as-is
+        let initial_distance = data.lookup_distance(table, raw);
 
         /*
         // Min/max adjustment
@@ -98,19 +416,189 @@ impl SenseAnalysis {
             }
         };
         */
-        let distance_offset = MODEL[data.stats.min as usize];
-        let distance = initial_distance - distance_offset;
-        let velocity = distance - data.analysis.distance; // / 1
-        let acceleration = (velocity - data.analysis.velocity) / 2;
+        let distance_offset = data.lookup_distance(table, data.stats.min);
+        // Calculations are widened to i32 and saturated back to i16 so that large steps
+        // (e.g. fast magnet swap or recalibration glitch) clamp instead of wrapping.
+        // Temperature drift correction is also removed here (see Sensors::set_temp_compensation)
+        let distance = saturate(initial_distance - distance_offset - data.temp_offset as i32);
+        let velocity = saturate(distance as i32 - data.analysis.distance as i32); // / 1
+        let acceleration = saturate((velocity as i32 - data.analysis.velocity as i32) / 2);
         // NOTE: To use jerk, the compile-time thresholds will need to be
-        //       multiplied by 3 (to account for the missing / 3)
-        let jerk = acceleration - data.analysis.acceleration;
+        //       multiplied by 3 (to account for the missing / 3), see jerk_exceeds
+        let jerk = saturate(acceleration as i32 - data.analysis.acceleration as i32);
         SenseAnalysis {
             raw,
             distance,
             velocity,
             acceleration,
             jerk,
+            smoothed_velocity: velocity,
+        }
+    }
+
+    /// f32 analysis (for targets with an FPU)
+    /// Oversampled readings are interpolated without truncation and the derivative chain is
+    /// kept in f32 (acceleration is not truncated by the / 2), results are rounded to the
+    /// nearest count and saturated only when stored. Agrees with the integer analysis to within
+    /// a count or two.
+    #[cfg(feature = "f32")]
+    fn new_f32<const H: usize>(
+        raw: u16,
+        data: &SenseData<H>,
+        table: &LookupTable,
+    ) -> SenseAnalysis {
+        let distance = data.lookup_distance_f32(table, raw)
+            - data.lookup_distance_f32(table, data.stats.min)
+            - data.temp_offset as f32;
+        let velocity = distance - data.analysis.distance as f32;
+        let acceleration = (velocity - data.analysis.velocity as f32) / 2.0;
+        let jerk = acceleration - data.analysis.acceleration as f32;
+        let velocity = saturate_f32(velocity);
+        SenseAnalysis {
+            raw,
+            distance: saturate_f32(distance),
+            velocity,
+            acceleration: saturate_f32(acceleration),
+            jerk: saturate_f32(jerk),
+            smoothed_velocity: velocity,
+        }
+    }
+
+    /// Replace distance and velocity with the alpha-beta estimate (see SensorsConfig::estimator)
+    /// Acceleration and jerk are derived from the estimated velocity.
+    fn estimate(&mut self, prev: &SenseAnalysis, state: &mut AlphaBetaState, gains: AlphaBeta) {
+        let (distance, velocity) = state.update(self.distance, gains);
+        self.distance = distance;
+        self.velocity = velocity;
+        self.smoothed_velocity = velocity;
+        self.acceleration = saturate((velocity as i32 - prev.velocity as i32) / 2);
+        self.jerk = saturate(self.acceleration as i32 - prev.acceleration as i32);
+    }
+
+    /// Apply velocity smoothing (see SensorsConfig::velocity_smoothing)
+    /// Shift-based EMA, sv += (v - sv) >> shift. Without smoothing the smoothed velocity is the
+    /// instantaneous velocity.
+    fn smooth(&mut self, prev: &SenseAnalysis, smoothing: VelocitySmoothing) {
+        let prev_velocity = prev.smoothed_velocity as i32;
+        let shift = smoothing.shift.min(15);
+        self.smoothed_velocity =
+            saturate(prev_velocity + ((self.velocity as i32 - prev_velocity) >> shift));
+        if smoothing.derivatives {
+            self.acceleration = saturate((self.smoothed_velocity as i32 - prev_velocity) / 2);
+            self.jerk = saturate(self.acceleration as i32 - prev.acceleration as i32);
+        }
+    }
+
+    /// Raw ADC reading (after inversion and filtering)
+    pub fn raw(&self) -> u16 {
+        self.raw
+    }
+
+    /// Distance from the calibrated rest position (lookup table units)
+    /// Positive is towards bottom-out.
+    pub fn distance(&self) -> i16 {
+        self.distance
+    }
+
+    /// Change in distance since the previous analysis
+    pub fn velocity(&self) -> i16 {
+        self.velocity
+    }
+
+    /// Exponentially smoothed velocity, same as velocity unless smoothing is enabled
+    /// See SensorsConfig::velocity_smoothing
+    pub fn smoothed_velocity(&self) -> i16 {
+        self.smoothed_velocity
+    }
+
+    /// Change in velocity since the previous analysis (/ 2, see struct docs)
+    pub fn acceleration(&self) -> i16 {
+        self.acceleration
+    }
+
+    /// Change in acceleration since the previous analysis (without the / 3, see jerk_exceeds)
+    pub fn jerk(&self) -> i16 {
+        self.jerk
+    }
+
+    /// Key is travelling towards bottom-out (positive velocity)
+    pub fn is_moving_down(&self) -> bool {
+        self.velocity > 0
+    }
+
+    /// Key is travelling back towards rest (negative velocity)
+    pub fn is_moving_up(&self) -> bool {
+        self.velocity < 0
+    }
+
+    /// Distance in micrometers (using the default lookup table scale)
+    /// See LookupTable::to_um for rounding.
+    pub fn distance_um(&self) -> i32 {
+        DEFAULT_LOOKUP_TABLE.to_um(self.distance as i32)
+    }
+
+    /// Distance in fixed-point millimeters, distance * scale_num / scale_den
+    /// The scale is chosen for the sensor geometry and the fixed-point precision, e.g. with the
+    /// default lookup table (~4 um per unit) 2 / 5 gives hundredths of a millimeter.
+    /// Rounded to the nearest value (ties away from zero) and saturated, 0 if scale_den is 0.
+    pub fn distance_mm(&self, scale_num: u16, scale_den: u16) -> i16 {
+        if scale_den == 0 {
+            return 0;
+        }
+        let scaled = self.distance as i32 * scale_num as i32;
+        let half = scale_den as i32 / 2;
+        let mm = if scaled < 0 {
+            (scaled - half) / scale_den as i32
+        } else {
+            (scaled + half) / scale_den as i32
+        };
+        saturate(mm)
+    }
+
+    /// Velocity in micrometers per analysis sample (using the default lookup table scale)
+    /// See LookupTable::to_um for rounding.
+    pub fn velocity_um_per_sample(&self) -> i32 {
+        DEFAULT_LOOKUP_TABLE.to_um(self.velocity as i32)
+    }
+
+    /// Jerk magnitude exceeds JT (e.g. impact or bottom-out rather than slow drift)
+    /// jerk is calculated without the / 3, so the threshold is multiplied by 3 instead
+    /// (at compile-time, keeping the check division-free).
+    pub fn jerk_exceeds<const JT: usize>(&self) -> bool {
+        self.jerk.unsigned_abs() as usize > const { JT * 3 }
+    }
+
+    /// Distance has reached the actuation point AP
+    pub fn actuated<const AP: usize>(&self) -> bool {
+        self.distance as i32 >= AP as i32
+    }
+
+    /// Determine which zone (band) of travel the distance is in
+    /// boundaries is the starting distance of each band and must be monotonically increasing.
+    /// Distances before the first boundary clamp to the first band (0) and distances past the
+    /// last boundary stay in the last band (N - 1).
+    pub fn zone<const N: usize>(&self, boundaries: &[i16; N]) -> usize {
+        debug_assert!(
+            boundaries.windows(2).all(|w| w[0] <= w[1]),
+            "Zone boundaries must be monotonic"
+        );
+        boundaries
+            .iter()
+            .take_while(|&&boundary| self.distance >= boundary)
+            .count()
+            .saturating_sub(1)
+    }
+
+    /// Average of two analyses (e.g. two sensors measuring the same key)
+    pub fn average(&self, other: &SenseAnalysis) -> SenseAnalysis {
+        let avg = |a: i16, b: i16| ((a as i32 + b as i32) / 2) as i16;
+        SenseAnalysis {
+            raw: ((self.raw as u32 + other.raw as u32) / 2) as u16,
+            distance: avg(self.distance, other.distance),
+            velocity: avg(self.velocity, other.velocity),
+            acceleration: avg(self.acceleration, other.acceleration),
+            jerk: avg(self.jerk, other.jerk),
+            smoothed_velocity: avg(self.smoothed_velocity, other.smoothed_velocity),
         }
     }
 
@@ -122,15 +610,131 @@ impl SenseAnalysis {
             velocity: 0,
             acceleration: 0,
             jerk: 0,
+            smoothed_velocity: 0,
+        }
+    }
+}
+
+/// Velocity strike event
+/// Generated when the downward velocity of a key exceeds a threshold
+/// (e.g. soft vs. hard press detection, MIDI velocity).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct VelocityEvent {
+    /// Downward velocity of the strike (always positive)
+    pub velocity: i16,
+}
+
+/// Actuation state change (see SenseData::poll_actuation)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum ActuationEvent {
+    Press = 0,   // Distance reached the actuation point
+    Release = 1, // Distance dropped below the release point, or the sensor lost calibration
+}
+
+/// Filter applied to incoming raw samples before analysis
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub enum Filter {
+    /// Boxcar average of SC samples (also averaged with the previous SC samples)
+    /// Nothing is produced until SC samples have been accumulated.
+    #[default]
+    Average,
+    /// Single-pole IIR low-pass filter, y += (x - y) >> shift
+    /// The filter is updated every sample, SC is used as the analysis divider
+    /// (a filtered value is returned every SC samples so higher-order calculations still
+    /// happen at a fixed rate).
+    /// shift should be between 1 and 16 (larger is smoother, but slower to respond).
+    Iir { shift: u8 },
+    /// Boxcar average of SC samples, decimated by fewer bits than a full average
+    /// The result keeps bits extra bits of resolution (TABLE_BITS + bits), e.g. 16 samples are
+    /// enough for 2 extra bits (4^bits samples per extra bit pair). Averaged with the previous
+    /// SC samples like Average.
+    /// bits is limited to MAX_OVERSAMPLE_BITS (see SenseData::lookup_distance for table use).
+    Oversample { bits: u8 },
+    /// Largest of SC samples (max-hold), e.g. to catch the deepest point of a press at low
+    /// analysis rates. Not averaged with the previous SC samples.
+    Max,
+    /// Smallest of SC samples (min-hold), e.g. for fault detection
+    /// Not averaged with the previous SC samples.
+    Min,
+}
+
+/// Maximum extra bits of Filter::Oversample (readings are limited to 16 bits)
+pub const MAX_OVERSAMPLE_BITS: u8 = 16 - TABLE_BITS;
+
+impl Filter {
+    /// Extra bits of resolution produced by the filter
+    #[inline]
+    pub fn oversample_bits(&self) -> u8 {
+        match self {
+            Filter::Oversample { bits } => (*bits).min(MAX_OVERSAMPLE_BITS),
+            _ => 0,
+        }
+    }
+}
+
+/// Handling of readings below the calibrated rest position (stats.min)
+/// e.g. a key floating above its rest position after the plate flexes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub enum RestBoundary {
+    /// Lower readings become the new rest position, distance is never negative
+    /// Returning to the original rest position then reports a partial press.
+    #[default]
+    Track,
+    /// Rest position is kept once calibrated, lower readings report a negative distance
+    /// Velocity stays continuous across the rest position. Use extrema_decay (or
+    /// SenseData::decay_extrema) to follow a baseline that drifts down.
+    Signed,
+}
+
+/// Median pre-filter applied to each raw sample before accumulation
+/// Used to reject single sample spikes (e.g. ADC crosstalk from neighbouring channels) that
+/// would otherwise be smeared into the average.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub enum Median {
+    /// No pre-filtering
+    #[default]
+    Off,
+    /// Median of the last 3 samples (rejects 1 sample spikes, 1 sample of latency)
+    Of3,
+    /// Median of the last 5 samples (rejects 2 sample spikes, 2 samples of latency)
+    Of5,
+}
+
+impl Median {
+    /// Number of samples used to compute the median
+    fn len(&self) -> usize {
+        match self {
+            Median::Off => 1,
+            Median::Of3 => 3,
+            Median::Of5 => 5,
         }
     }
 }
 
+/// Divide by the number of samples
+/// Uses a shift if the number of samples is a power of two (the common case).
+#[inline]
+fn divide_samples(val: u32, samples: u32) -> u32 {
+    if samples.is_power_of_two() {
+        val >> samples.trailing_zeros()
+    } else {
+        val / samples
+    }
+}
+
 /// Stores incoming raw samples
 #[repr(C)]
 #[derive(Clone, Debug, defmt::Format)]
 pub struct RawData {
     scratch_samples: u8,
+    history_len: u8,
+    /// Previous raw samples (newest first), used by the median pre-filter
+    history: [u16; 4],
     scratch: u32,
     prev_scratch: u32,
 }
@@ -139,43 +743,165 @@ impl RawData {
     fn new() -> RawData {
         RawData {
             scratch_samples: 0,
+            history_len: 0,
+            history: [0; 4],
             scratch: 0,
             prev_scratch: 0,
         }
     }
 
+    /// Median pre-filter
+    /// Until enough samples have been recorded the reading is passed through unmodified.
+    /// Only uses integer comparisons (insertion sort of at most 5 elements).
+    #[inline]
+    fn median(&mut self, reading: u16, median: Median) -> u16 {
+        let len = median.len();
+        if len == 1 {
+            return reading;
+        }
+
+        // Build the window from the current reading and the history
+        let mut window = [0u16; 5];
+        window[0] = reading;
+        window[1..len].copy_from_slice(&self.history[..len - 1]);
+        let ready = self.history_len as usize >= len - 1;
+
+        // Shift history
+        self.history.copy_within(0..3, 1);
+        self.history[0] = reading;
+        if !ready {
+            self.history_len += 1;
+            return reading;
+        }
+
+        // Sort window
+        let window = &mut window[..len];
+        for i in 1..len {
+            let mut j = i;
+            while j > 0 && window[j - 1] > window[j] {
+                window.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        window[len / 2]
+    }
+
     /// Adds to the internal scratch location
     /// Designed to accumulate until a set number of readings added
-    /// SC: specifies the number of scratch samples until ready to average
-    ///     Should be a power of two (1, 2, 4, 8, 16...) for the compiler to
-    ///     optimize.
-    fn add<const SC: usize>(&mut self, reading: u16) -> Option<u16> {
+    /// samples: specifies the number of scratch samples until ready to average
+    ///     Should be a power of two (1, 2, 4, 8, 16...) so the average can be computed using
+    ///     shifts instead of division.
+    #[inline]
+    fn add(&mut self, reading: u16, samples: u8, config: &SensorsConfig) -> Option<u16> {
+        let reading = self.median(reading, config.median);
+        match config.filter {
+            Filter::Average => self.add_average(reading, samples, 0),
+            Filter::Iir { shift } => self.add_iir(reading, samples, shift),
+            Filter::Oversample { .. } => {
+                self.add_average(reading, samples, config.filter.oversample_bits())
+            }
+            Filter::Max => self.add_hold(reading, samples, u16::max),
+            Filter::Min => self.add_hold(reading, samples, u16::min),
+        }
+    }
+
+    /// Min/max-hold accumulation
+    /// scratch holds the extreme of the current window, seeded with the first reading.
+    fn add_hold(&mut self, reading: u16, samples: u8, hold: fn(u16, u16) -> u16) -> Option<u16> {
+        self.scratch = if self.scratch_samples == 0 {
+            reading as u32
+        } else {
+            hold(self.scratch as u16, reading) as u32
+        };
+        self.scratch_samples += 1;
+        trace!(
+            "Reading: {}  Held: {}  Sample: {}/{}",
+            reading,
+            self.scratch,
+            self.scratch_samples,
+            samples
+        );
+
+        if self.scratch_samples >= samples {
+            let val = self.scratch as u16;
+            self.scratch = 0;
+            self.scratch_samples = 0;
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    /// Boxcar average accumulation
+    /// bits of extra resolution are kept (see Filter::Oversample), 0 is a full average.
+    #[inline]
+    fn add_average(&mut self, reading: u16, samples: u8, bits: u8) -> Option<u16> {
         self.scratch += reading as u32;
         self.scratch_samples += 1;
         trace!(
             "Reading: {}  Sample: {}/{}",
             reading,
             self.scratch_samples,
-            SC as u8
+            samples
         );
 
-        if self.scratch_samples == SC as u8 {
+        if self.scratch_samples >= samples {
             let val = if self.prev_scratch == 0 {
-                self.scratch / SC as u32
+                divide_samples(self.scratch << bits, samples as u32)
             } else {
                 // Average previous value if non-zero
-                (self.scratch + self.prev_scratch) / SC as u32 / SC as u32
+                divide_samples(
+                    (self.scratch + self.prev_scratch) << bits,
+                    2 * samples as u32,
+                )
             };
             self.prev_scratch = self.scratch;
             self.scratch = 0;
             self.scratch_samples = 0;
-            Some(val as u16)
+            Some(val.min(u16::MAX as u32) as u16)
+        } else {
+            None
+        }
+    }
+
+    /// Single-pole IIR accumulation
+    /// scratch holds the filter state scaled by 2^shift (y << shift), which is equivalent to
+    /// y += (x - y) >> shift without losing the fractional bits each sample.
+    /// The filter is seeded with the first reading.
+    fn add_iir(&mut self, reading: u16, samples: u8, shift: u8) -> Option<u16> {
+        let shift = shift.min(16);
+        if self.scratch == 0 {
+            self.scratch = (reading as u32) << shift;
+        } else {
+            self.scratch = self.scratch - (self.scratch >> shift) + reading as u32;
+        }
+        self.scratch_samples += 1;
+        trace!(
+            "Reading: {}  Filtered: {}  Sample: {}/{}",
+            reading,
+            self.scratch >> shift,
+            self.scratch_samples,
+            samples
+        );
+
+        if self.scratch_samples >= samples {
+            self.scratch_samples = 0;
+            Some((self.scratch >> shift) as u16)
         } else {
             None
         }
     }
 
+    /// Drop the partially accumulated samples
+    /// The previous average is kept so the next sample still averages against it.
+    /// The IIR filter state is also held in scratch, so the filter is reseeded by the next reading.
+    fn discard_partial(&mut self) {
+        self.scratch = 0;
+        self.scratch_samples = 0;
+    }
+
     /// Reset data, used when transitioning between calibration and normal modes
+    /// The median pre-filter history is kept (it's still valid for spike rejection).
     fn reset(&mut self) {
         self.scratch = 0;
         self.scratch_samples = 0;
@@ -187,9 +913,14 @@ impl RawData {
 #[repr(C)]
 #[derive(Clone, Debug, defmt::Format)]
 pub struct SenseStats {
-    pub min: u16,     // Minimum raw value (reset when out of calibration)
-    pub max: u16,     // Maximum raw value (reset when out of calibration)
-    pub samples: u32, // Total number of samples (does not reset)
+    pub min: u16,                       // Minimum raw value (reset when out of calibration)
+    pub max: u16,                       // Maximum raw value (reset when out of calibration)
+    pub samples: u32,                   // Total number of analysis samples (does not reset)
+    pub noise: u16, // Peak-to-peak rest noise, 0xFFFF if not measured (reset when out of calibration)
+    pub min_ok: u16, // Derived minimum magnet threshold, 0 if not derived (reset when out of calibration)
+    pub bottom_out: u16, // Calibrated full travel raw value, 0 if not confirmed (reset when out of calibration)
+    pub min_valid: bool, // min has been recorded, min is 0xFFFF otherwise (reset when out of calibration)
+    pub samples_since_calibration: u32, // Analysis samples since entering MagnetDetected (saturates)
 }
 
 impl SenseStats {
@@ -198,13 +929,96 @@ impl SenseStats {
             min: 0xFFFF,
             max: 0x0000,
             samples: 0,
+            noise: 0xFFFF,
+            min_ok: 0,
+            bottom_out: 0,
+            min_valid: false,
+            samples_since_calibration: 0,
         }
     }
 
     /// Reset, resettable stats (e.g. min, max, but not samples)
     fn reset(&mut self) {
         self.min = 0xFFFF;
+        self.min_valid = false;
         self.max = 0x0000;
+        self.noise = 0xFFFF;
+        self.min_ok = 0;
+        self.bottom_out = 0;
+    }
+}
+
+/// Tracks the peak-to-peak range of a window of averaged samples
+#[derive(Clone, Debug, defmt::Format)]
+struct NoiseWindow {
+    min: u16,
+    max: u16,
+    samples: u8,
+}
+
+impl NoiseWindow {
+    fn new() -> NoiseWindow {
+        NoiseWindow {
+            min: 0xFFFF,
+            max: 0x0000,
+            samples: 0,
+        }
+    }
+
+    /// Add a sample, returns the peak-to-peak range once the window is complete
+    fn add(&mut self, data: u16, window: u8) -> Option<u16> {
+        self.min = self.min.min(data);
+        self.max = self.max.max(data);
+        self.samples += 1;
+        if self.samples >= window {
+            let p2p = self.max - self.min;
+            *self = NoiseWindow::new();
+            Some(p2p)
+        } else {
+            None
+        }
+    }
+}
+
+/// Fixed-capacity ring buffer of the most recent analyses of a sensor (see SenseData::history)
+#[derive(Clone, Debug, defmt::Format)]
+struct History<const H: usize> {
+    entries: [SenseAnalysis; H],
+    /// Position of the next entry
+    head: u8,
+    /// Number of recorded entries (up to H)
+    len: u8,
+}
+
+impl<const H: usize> History<H> {
+    fn new() -> History<H> {
+        const {
+            assert!(
+                H <= u8::MAX as usize,
+                "History depth is limited to 255 entries"
+            )
+        };
+        History {
+            entries: core::array::from_fn(|_| SenseAnalysis::null()),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Record an analysis, replacing the oldest entry once full
+    fn push(&mut self, analysis: &SenseAnalysis) {
+        if H == 0 {
+            return;
+        }
+        self.entries[self.head as usize] = analysis.clone();
+        self.head = ((self.head as usize + 1) % H) as u8;
+        self.len = (self.len as usize + 1).min(H) as u8;
+    }
+
+    /// Recorded entries, oldest to newest
+    fn iter(&self) -> impl Iterator<Item = &SenseAnalysis> {
+        let oldest = self.head as usize + H - self.len as usize;
+        (oldest..oldest + self.len as usize).map(move |n| &self.entries[n % H])
     }
 }
 
@@ -251,207 +1065,3153 @@ impl SenseStats {
 /// * MNOK: Min valid calibration (Wrong magnet direction; wrong pole, less than a specific value)
 /// * MXOK: Max valid calibration (Bad Sensor threshold; sensor is bad if reading is higher than this value)
 /// * NS: No sensor detected (less than a specific value)
+///
+/// H is the analysis history depth (see history), 0 disables the history.
 #[derive(Clone, Debug, defmt::Format)]
-pub struct SenseData {
+pub struct SenseData<const H: usize = 0> {
     pub analysis: SenseAnalysis,
     pub cal: CalibrationStatus,
     pub data: RawData,
     pub stats: SenseStats,
+    /// Analysis held when the sensor last lost calibration, None if it never did after an
+    /// analysis (see last_good)
+    last_good: Option<SenseAnalysis>,
+    /// Consecutive analysis samples within IDLE_VELOCITY (see is_idle)
+    idle_samples: u32,
+    /// Consecutive analysis samples within the SensorsConfig::idle epsilon
+    idle_windows: u16,
+    /// Idle flag (see SensorsConfig::idle)
+    idle: bool,
+    /// Current noise measurement window (automatic threshold estimation)
+    noise_window: NoiseWindow,
+    /// Temperature compensation distance offset (see Sensors::set_temp_compensation)
+    temp_offset: i16,
+    /// Most recent raw reading
+    last_reading: u16,
+    /// Consecutive identical raw readings (including the first)
+    flat_readings: u32,
+    /// Reading decreases as the key is pressed (e.g. bottom mounted sensor)
+    inverted: bool,
+    /// Bottom-out calibration in progress (see Sensors::start_bottom_out_calibration)
+    bottom_out_mode: bool,
+    /// Key is pressed (see poll_actuation)
+    pressed: bool,
+    /// Previous calibration status if the most recent add changed it
+    transition_from: Option<CalibrationStatus>,
+    /// Consecutive windows disagreeing with MagnetDetected (see SensorsConfig::cal_debounce)
+    cal_pending: u8,
+    /// Consecutive windows clear of the fault threshold (see SensorsConfig::cal_recovery)
+    recovery_pending: u8,
+    /// Consecutive in-range windows before MagnetDetected (see Sensors CAL_CONFIRM)
+    confirm_pending: u8,
+    /// Largest downward velocity since the key was last fully released
+    peak_velocity: i16,
+    /// Lookup table index (see Sensors::set_lookup_table)
+    table_index: u8,
+    /// Bypass the lookup table, distance is in raw ADC counts (see Sensors::set_passthrough)
+    passthrough: bool,
+    /// Extra bits of resolution of the averaged readings (see Filter::Oversample)
+    oversample: u8,
+    /// Per-key actuation point, 0 uses the generic default (see Sensors::set_actuation)
+    actuation: u16,
+    /// Committed gain/offset recommendation
+    gain: GainRecommendation,
+    /// Consecutive windows disagreeing with the committed gain recommendation
+    gain_pending: u8,
+    /// Calibration status and min/max of the last gain evaluation
+    gain_inputs: (CalibrationStatus, u16, u16),
+    /// Windows spent in SensorBroken since the last retry (see SensorsConfig::broken_retry)
+    broken_windows: u16,
+    /// SensorBroken retries so far
+    broken_retries: u8,
+    /// Analysis windows since the last automatic min/max decay
+    decay_windows: u16,
+    /// Alpha-beta estimator state, None until seeded (see SensorsConfig::estimator)
+    estimate: Option<AlphaBetaState>,
+    /// Calibration kept after the magnet was removed (see SensorsConfig::hot_swap)
+    hot_swap: Option<HotSwapGrace>,
+    /// Hot-swap event caused by the most recent add
+    hot_swap_event: Option<HotSwapEvent>,
+    /// Recent analyses (see history)
+    history: History<H>,
+    /// Raw reading histogram
+    #[cfg(feature = "histogram")]
+    histogram: Histogram<HISTOGRAM_BINS>,
+    /// Actuation presses (see poll_actuation), saturating
+    #[cfg(feature = "press-count")]
+    presses: u32,
 }
 
-impl SenseData {
-    pub fn new() -> SenseData {
+impl<const H: usize> SenseData<H> {
+    pub fn new() -> SenseData<H> {
         SenseData {
             analysis: SenseAnalysis::null(),
             cal: CalibrationStatus::NotReady,
             data: RawData::new(),
             stats: SenseStats::new(),
+            last_good: None,
+            idle_samples: 0,
+            idle_windows: 0,
+            idle: false,
+            noise_window: NoiseWindow::new(),
+            temp_offset: 0,
+            last_reading: 0,
+            flat_readings: 0,
+            inverted: false,
+            bottom_out_mode: false,
+            pressed: false,
+            transition_from: None,
+            cal_pending: 0,
+            recovery_pending: 0,
+            confirm_pending: 0,
+            peak_velocity: 0,
+            table_index: 0,
+            passthrough: false,
+            oversample: 0,
+            actuation: 0,
+            gain: GainRecommendation::hold(),
+            gain_pending: 0,
+            gain_inputs: (CalibrationStatus::NotReady, 0xFFFF, 0x0000),
+            broken_windows: 0,
+            broken_retries: 0,
+            decay_windows: 0,
+            estimate: None,
+            hot_swap: None,
+            hot_swap_event: None,
+            history: History::new(),
+            #[cfg(feature = "histogram")]
+            histogram: Histogram::new(),
+            #[cfg(feature = "press-count")]
+            presses: 0,
         }
     }
 
     /// Acculumate a new sensor reading
-    /// Once the required number of samples is retrieved, do analysis
-    /// Analysis does a few more addition, subtraction and comparisions
-    /// so it's a more expensive operation.
+    /// Returns the averaged sample once the required number of samples is retrieved, which is
+    /// then analyzed (see sample and sample_test). Analysis does a few more addition,
+    /// subtraction and comparisions so it's a more expensive operation.
+    fn accumulate(&mut self, reading: u16, samples: u8, config: &SensorsConfig) -> Option<u16> {
+        let reading = self.mirror(scale_reading(reading, config.adc_bits));
+        self.track_flatline(reading);
+        #[cfg(feature = "histogram")]
+        self.histogram.add(reading);
+        self.transition_from = None;
+        self.hot_swap_event = None;
+
+        // Add value to accumulator
+        self.data.add(reading, samples, config)
+    }
+
+    /// Analyze an averaged sample
     /// Normal mode
-    fn add<const SC: usize>(
+    fn sample(
         &mut self,
-        reading: u16,
-    ) -> Result<Option<&SenseAnalysis>, SensorError> {
-        // Add value to accumulator
-        if let Some(data) = self.data.add::<SC>(reading) {
-            // Check min/max values
-            if data > self.stats.max {
-                self.stats.max = data;
-            }
-            if data < self.stats.min {
-                self.stats.min = data;
-            }
-            trace!("Reading: {}  Stats: {:?}", reading, self.stats);
+        data: u16,
+        table: &LookupTable,
+        config: &SensorsConfig,
+    ) -> Option<&SenseAnalysis> {
+        self.oversample = config.filter.oversample_bits();
+        self.track_extrema(data, config);
+        trace!("Data: {}  Stats: {:?}", data, self.stats);
 
-            // As soon as we have enough values accumulated, set magnet as detected in normal mode
-            self.cal = CalibrationStatus::MagnetDetected;
+        // As soon as we have enough values accumulated, set magnet as detected in normal mode
+        self.set_cal(CalibrationStatus::MagnetDetected);
 
-            // Calculate new analysis (requires previous results + min/max)
-            self.analysis = SenseAnalysis::new(data, self);
-            Ok(Some(&self.analysis))
-        } else {
-            Ok(None)
-        }
+        // Calculate new analysis (requires previous results + min/max)
+        self.analyze(data, table, config);
+        self.latch_bottom_out(data, config);
+        self.auto_decay_extrema(config);
+        self.update_gain();
+        Some(&self.analysis)
     }
 
-    /// Acculumate a new sensor reading
-    /// Once the required number of samples is retrieved, do analysis
-    /// Analysis does a few more addition, subtraction and comparisions
-    /// so it's a more expensive operation.
+    /// Analyze an averaged sample
     /// Test mode
-    fn add_test<const SC: usize, const MNOK: usize, const MXOK: usize, const NS: usize>(
+    /// Returns the calibration status if the sensor is not calibrated (the caller knows the index)
+    /// The accumulator is reset whenever the calibration status is returned.
+    fn sample_test<
+        const MNOK: usize,
+        const MXOK: usize,
+        const NS: usize,
+        const CAL_CONFIRM: usize,
+    >(
         &mut self,
-        reading: u16,
-    ) -> Result<Option<&SenseAnalysis>, SensorError> {
-        // Add value to accumulator
-        if let Some(data) = self.data.add::<SC>(reading) {
-            // Check min/max values
-            if data > self.stats.max {
-                self.stats.max = data;
-            }
-            if data < self.stats.min {
-                self.stats.min = data;
-            }
+        data: u16,
+        table: &LookupTable,
+        config: &SensorsConfig,
+    ) -> Result<Option<&SenseAnalysis>, CalibrationStatus> {
+        self.sample_test_with::<MNOK, MXOK, NS, CAL_CONFIRM>(data, None, table, config)
+    }
 
-            // Check calibration
-            self.cal = self.check_calibration::<MNOK, MXOK, NS>(data);
-            trace!(
-                "Reading: {}  Cal: {:?}  Stats: {:?}",
-                reading,
-                self.cal,
-                self.stats
-            );
-            match self.cal {
-                CalibrationStatus::MagnetDetected => {}
-                // Don't bother doing calculations if magnet+sensor isn't ready
-                _ => {
-                    // Reset min/max
-                    self.stats.reset();
-                    // Reset averaging
-                    self.data.reset();
-                    // Clear analysis, only set raw
-                    self.analysis = SenseAnalysis::null();
-                    self.analysis.raw = data;
-                    return Err(SensorError::CalibrationError(self.clone()));
+    /// Analyze an averaged sample, see sample_test
+    /// For the primary sensor of a differential pair data is the combined reading and pair is
+    /// the primary's own averaged sample (checked against the thresholds) and the calibration
+    /// status of the secondary.
+    fn sample_test_with<
+        const MNOK: usize,
+        const MXOK: usize,
+        const NS: usize,
+        const CAL_CONFIRM: usize,
+    >(
+        &mut self,
+        data: u16,
+        pair: Option<(u16, CalibrationStatus)>,
+        table: &LookupTable,
+        config: &SensorsConfig,
+    ) -> Result<Option<&SenseAnalysis>, CalibrationStatus> {
+        self.oversample = config.filter.oversample_bits();
+
+        // Cooling down (or latched) after SensorBroken
+        if self.hold_broken(config) {
+            self.data.reset();
+            self.analysis.raw = data;
+            return Err(self.cal.clone());
+        }
+
+        // Check calibration
+        // Thresholds are in the lookup table domain
+        let cal_data = pair.as_ref().map_or(data, |(own, _)| *own) >> self.oversample;
+        let min_ok = self.min_ok_threshold::<MNOK>();
+        let cal = self.check_calibration::<MXOK, NS>(cal_data, min_ok);
+        // Either sensor of a differential pair failing fails the pair
+        let cal = match &pair {
+            Some((_, partner)) if cal == CalibrationStatus::MagnetDetected => partner.clone(),
+            _ => cal,
+        };
+        let cal = self.recover_cal::<MXOK>(cal, cal_data, min_ok, config);
+        let cal = self.confirm_cal::<CAL_CONFIRM>(cal);
+        if self.debounce_cal(&cal, config) {
+            // Possible outlier, hold the previous analysis until the new status is confirmed
+            trace!(
+                "Data: {}  Cal (pending {}/{}): {:?}",
+                data,
+                self.cal_pending,
+                config.cal_debounce,
+                cal
+            );
+            return Ok(None);
+        }
+
+        self.track_hot_swap(&cal, cal_data, config);
+        self.track_extrema(data, config);
+        self.set_cal(cal);
+        trace!(
+            "Data: {}  Cal: {:?}  Stats: {:?}",
+            data,
+            self.cal,
+            self.stats
+        );
+        match self.cal {
+            CalibrationStatus::MagnetDetected => {}
+            // Don't bother doing calculations if magnet+sensor isn't ready
+            _ => {
+                // Reset min/max
+                self.stats.reset();
+                // Reset averaging
+                self.data.reset();
+                // Clear analysis, only set raw
+                // The analysis is held if calibration was just lost (see last_good)
+                let analysis = core::mem::replace(&mut self.analysis, SenseAnalysis::null());
+                if self.transition_from == Some(CalibrationStatus::MagnetDetected)
+                    && self.stats.samples_since_calibration > 0
+                {
+                    self.last_good = Some(analysis);
+                }
+                self.analysis.raw = data;
+                self.idle_samples = 0;
+                self.idle_windows = 0;
+                self.idle = false;
+                self.noise_window = NoiseWindow::new();
+                self.estimate = None;
+                self.update_gain();
+                return Err(self.cal.clone());
+            }
+        }
+
+        // Estimate rest noise (and the derived minimum magnet threshold)
+        // Not for differential pairs, the combined reading isn't in the threshold domain
+        if let (Some(auto), None) = (config.auto_threshold, &pair) {
+            self.estimate_noise(data, auto);
+        }
+
+        // Calculate new analysis (requires previous results + min/max)
+        self.analyze(data, table, config);
+        self.latch_bottom_out(data, config);
+        self.auto_decay_extrema(config);
+        self.update_gain();
+        Ok(Some(&self.analysis))
+    }
+
+    /// Check min/max values
+    /// With RestBoundary::Signed the rest position (min) is only learned while calibrating.
+    /// The first sample always sets min, including a full scale (0xFFFF) reading.
+    fn track_extrema(&mut self, data: u16, config: &SensorsConfig) {
+        if data > self.stats.max {
+            self.stats.max = data;
+        }
+        let learning =
+            config.rest == RestBoundary::Track || self.cal != CalibrationStatus::MagnetDetected;
+        if !self.stats.min_valid || (data < self.stats.min && learning) {
+            self.stats.min = data;
+            self.stats.min_valid = true;
+        }
+    }
+
+    /// Store the averaged sample of the secondary sensor of a differential pair
+    /// Used by the primary sensor (as analysis.raw), the secondary isn't analyzed itself.
+    /// Normal mode
+    fn partner_sample(&mut self, data: u16, config: &SensorsConfig) {
+        self.oversample = config.filter.oversample_bits();
+        self.set_cal(CalibrationStatus::MagnetDetected);
+        self.analysis.raw = data;
+    }
+
+    /// Store the averaged sample of the secondary sensor of a differential pair
+    /// Test mode
+    /// The secondary is checked against the same thresholds as the primary.
+    fn partner_sample_test<const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        data: u16,
+        config: &SensorsConfig,
+    ) -> Result<(), CalibrationStatus> {
+        self.oversample = config.filter.oversample_bits();
+        let cal = self.check_calibration::<MXOK, NS>(
+            data >> self.oversample,
+            self.min_ok_threshold::<MNOK>(),
+        );
+        self.set_cal(cal);
+        self.analysis.raw = data;
+        if self.cal == CalibrationStatus::MagnetDetected {
+            Ok(())
+        } else {
+            self.data.reset();
+            Err(self.cal.clone())
+        }
+    }
+
+    /// Minimum magnet threshold used for calibration
+    /// Uses the derived threshold (see AutoThreshold) if available, otherwise MNOK.
+    fn min_ok_threshold<const MNOK: usize>(&self) -> u16 {
+        if self.stats.min_ok != 0 {
+            self.stats.min_ok >> self.oversample
+        } else {
+            threshold(MNOK)
+        }
+    }
+
+    /// Measure the peak-to-peak noise of each window of averaged samples
+    /// The smallest window is kept as the noise floor (windows containing a keypress have a
+    /// larger range, so only windows where the sensor is sitting idle contribute).
+    /// The minimum magnet threshold is then derived from the rest value (min) and the noise.
+    fn estimate_noise(&mut self, data: u16, auto: AutoThreshold) {
+        if let Some(p2p) = self.noise_window.add(data, auto.window) {
+            self.stats.noise = self.stats.noise.min(p2p);
+            // Always allow at least a count of noise
+            let margin = self.stats.noise.max(1).saturating_mul(auto.multiple as u16);
+            self.stats.min_ok = self.stats.min.saturating_sub(margin).max(1);
+            trace!(
+                "Noise: {}  Min Ok Threshold: {}",
+                self.stats.noise,
+                self.stats.min_ok
+            );
+        }
+    }
+
+    /// Measured peak-to-peak rest noise
+    /// Only available when automatic threshold estimation is enabled and the sensor has been
+    /// calibrated for at least one window.
+    pub fn noise(&self) -> Option<u16> {
+        if self.stats.noise == 0xFFFF {
+            None
+        } else {
+            Some(self.stats.noise)
+        }
+    }
+
+    /// Calculate new analysis and update any tracking that depends on it
+    /// Sensor must be calibrated (MagnetDetected).
+    fn analyze(&mut self, data: u16, table: &LookupTable, config: &SensorsConfig) {
+        let mut analysis = SenseAnalysis::new(data, self, table);
+        if let Some(gains) = config.estimator {
+            let state = self
+                .estimate
+                .get_or_insert_with(|| AlphaBetaState::seed(analysis.distance));
+            analysis.estimate(&self.analysis, state, gains);
+        }
+        if let Some(smoothing) = config.velocity_smoothing {
+            analysis.smooth(&self.analysis, smoothing);
+        }
+        // Housing compression at the bottom isn't movement (see SensorsConfig::bottom_deadzone)
+        if config.bottom_deadzone != 0
+            && self.in_bottom_deadzone(table, self.analysis.distance, config.bottom_deadzone)
+            && self.in_bottom_deadzone(table, analysis.distance, config.bottom_deadzone)
+        {
+            analysis.velocity = 0;
+            analysis.smoothed_velocity = 0;
+        }
+        self.analysis = analysis;
+        self.history.push(&self.analysis);
+        self.stats.samples = self.stats.samples.wrapping_add(1);
+        self.stats.samples_since_calibration =
+            self.stats.samples_since_calibration.saturating_add(1);
+
+        // Idle tracking
+        let movement = self.analysis.velocity.unsigned_abs();
+        if movement <= IDLE_VELOCITY {
+            self.idle_samples = self.idle_samples.saturating_add(1);
+        } else {
+            self.idle_samples = 0;
+        }
+
+        // Idle flag, counted separately with its own epsilon
+        if let Some(idle) = config.idle {
+            if movement <= idle.epsilon {
+                self.idle_windows = self.idle_windows.saturating_add(1);
+            } else {
+                self.idle_windows = 0;
+            }
+            self.idle = self.idle_windows >= idle.windows;
+        }
+
+        // Peak velocity tracking
+        if self.analysis.distance < RELEASE_DISTANCE {
+            self.peak_velocity = 0;
+        } else if self.analysis.velocity > self.peak_velocity {
+            self.peak_velocity = self.analysis.velocity;
+        }
+    }
+
+    /// Sensor is reading above its calibrated rest position (negative distance)
+    /// Only happens with RestBoundary::Signed (or a temperature compensation offset).
+    pub fn above_rest(&self) -> bool {
+        self.cal == CalibrationStatus::MagnetDetected && self.analysis.distance < 0
+    }
+
+    /// Largest downward velocity since the key was last fully released
+    /// Reset once the distance drops below RELEASE_DISTANCE, so the peak of the press is still
+    /// available when releasing past an actuation point (e.g. to classify light vs. heavy taps).
+    pub fn peak_velocity(&self) -> i16 {
+        self.peak_velocity
+    }
+
+    /// Start calibration over, keeping the sensor configuration
+    /// (inversion, lookup table, passthrough, actuation point, temperature compensation, bottom-out
+    /// calibration mode)
+    /// Any partial accumulation is discarded and the sensor returns to NotReady.
+    pub fn recalibrate(&mut self) {
+        let mut stats = SenseStats::new();
+        stats.samples = self.stats.samples;
+        *self = SenseData {
+            stats,
+            inverted: self.inverted,
+            temp_offset: self.temp_offset,
+            table_index: self.table_index,
+            passthrough: self.passthrough,
+            oversample: self.oversample,
+            actuation: self.actuation,
+            bottom_out_mode: self.bottom_out_mode,
+            // Kept so the next poll_actuation releases the key
+            pressed: self.pressed,
+            #[cfg(feature = "press-count")]
+            presses: self.presses,
+            ..SenseData::new()
+        };
+    }
+
+    /// Reset the measurement stats (min and max), e.g. after a measurement session
+    /// Calibration (status, noise, derived thresholds and bottom-out) and samples are kept.
+    /// While calibrated, min and max are re-seeded from the current reading (the key should be at
+    /// rest) so the analysis stays valid; otherwise they are re-learned from the next sample.
+    pub fn reset_stats(&mut self) {
+        if self.cal == CalibrationStatus::MagnetDetected && self.analysis.raw != 0 {
+            let raw = self.analysis.raw.min(self.raw_max());
+            self.stats.min = raw;
+            self.stats.min_valid = true;
+            self.stats.max = raw;
+        } else {
+            self.stats.min = 0xFFFF;
+            self.stats.min_valid = false;
+            self.stats.max = 0x0000;
+        }
+    }
+
+    /// Override the calibrated rest position (stats.min)
+    /// Clamped to the lookup table range so the analysis lookup can never go out of bounds
+    /// (which is why this is preferred over writing stats.min directly).
+    /// max is raised to min if needed so the range stays valid.
+    pub fn set_rest(&mut self, min: u16) {
+        let min = min.min(self.raw_max());
+        self.stats.min = min;
+        self.stats.min_valid = true;
+        self.stats.max = self.stats.max.max(min);
+    }
+
+    /// Histogram of raw readings (after inversion, before filtering)
+    /// Accumulates from power-on (or the last recalibrate/clear_histogram).
+    #[cfg(feature = "histogram")]
+    pub fn histogram(&self) -> &Histogram<HISTOGRAM_BINS> {
+        &self.histogram
+    }
+
+    /// Clear the raw reading histogram (e.g. to start a new calibration window)
+    #[cfg(feature = "histogram")]
+    pub fn clear_histogram(&mut self) {
+        self.histogram.clear();
+    }
+
+    /// Lookup table index used by the sensor (see Sensors::lookup_table)
+    pub fn table_index(&self) -> u8 {
+        self.table_index
+    }
+
+    /// Distance is in raw ADC counts (raw - min) rather than lookup table units
+    /// See Sensors::set_passthrough
+    pub fn passthrough(&self) -> bool {
+        self.passthrough
+    }
+
+    /// Extra bits of resolution of the averaged readings (see Filter::Oversample)
+    /// raw, stats (min, max, bottom-out) and passthrough distances have TABLE_BITS + bits of
+    /// resolution; calibration thresholds (MNOK, MXOK, NS) are still in TABLE_BITS counts.
+    pub fn oversample_bits(&self) -> u8 {
+        self.oversample
+    }
+
+    /// Largest averaged reading (including the extra oversampling bits)
+    fn raw_max(&self) -> u16 {
+        (((ADC_MAX as u32 + 1) << self.oversample) - 1) as u16
+    }
+
+    /// Convert a raw reading to distance units
+    /// Table length is validated when registered (see Sensors::register_lookup_table)
+    /// Oversampled readings index tables covering the extended range directly (a correspondingly
+    /// larger table), otherwise adjacent table entries are linearly interpolated. Either way
+    /// the distance is in table units.
+    /// Readings past the end of the table (e.g. stats written directly) are clamped to it.
+    #[inline]
+    fn lookup_distance(&self, table: &LookupTable, raw: u16) -> i32 {
+        let table = table.table;
+        if self.passthrough {
+            return raw as i32;
+        }
+        let raw = raw.min(self.raw_max());
+        if table.len() > self.raw_max() as usize {
+            table[raw as usize] as i32
+        } else {
+            let index = (raw >> self.oversample) as usize;
+            let frac = (raw & ((1 << self.oversample) - 1)) as i32;
+            let low = table[index] as i32;
+            let high = table[(index + 1).min(ADC_MAX as usize)] as i32;
+            low + (((high - low) * frac) >> self.oversample)
+        }
+    }
+
+    /// Convert a raw reading to distance units, see lookup_distance
+    /// Interpolates between table entries without truncation.
+    #[cfg(feature = "f32")]
+    fn lookup_distance_f32(&self, table: &LookupTable, raw: u16) -> f32 {
+        let table = table.table;
+        if self.passthrough {
+            return raw as f32;
+        }
+        let raw = raw.min(self.raw_max());
+        if table.len() > self.raw_max() as usize {
+            table[raw as usize] as f32
+        } else {
+            let index = (raw >> self.oversample) as usize;
+            let frac = (raw & ((1 << self.oversample) - 1)) as f32 / (1 << self.oversample) as f32;
+            let low = table[index] as f32;
+            let high = table[(index + 1).min(ADC_MAX as usize)] as f32;
+            low + (high - low) * frac
+        }
+    }
+
+    /// Relax min/max back towards the most recent sample by RATE
+    /// Call periodically so that a single noisy sample doesn't permanently widen the range.
+    /// min never creeps past the most recent sample (or max), and max never drops below it.
+    /// A sample below the rest position (negative distance, see RestBoundary::Signed) moves
+    /// min down towards it instead, so the baseline follows downwards drift.
+    pub fn decay_extrema<const RATE: usize>(&mut self) {
+        // Nothing recorded yet
+        if !self.stats.min_valid {
+            return;
+        }
+        let rate = RATE.min(u16::MAX as usize) as u16;
+        let current = self.analysis.raw.min(self.stats.max);
+        self.stats.min = if current < self.stats.min {
+            self.stats.min.saturating_sub(rate).max(current)
+        } else {
+            self.stats.min.saturating_add(rate).min(current)
+        };
+        self.stats.max = self
+            .stats
+            .max
+            .saturating_sub(rate)
+            .max(current)
+            .max(self.stats.min);
+    }
+
+    /// Debounce losing calibration
+    /// Returns true if the new status should not be committed yet.
+    /// Only MagnetDetected -> MagnetWrongPoleOrMissing is debounced (e.g. a single noisy window);
+    /// SensorMissing and SensorBroken indicate hardware faults and are committed immediately.
+    fn debounce_cal(&mut self, cal: &CalibrationStatus, config: &SensorsConfig) -> bool {
+        if self.cal == CalibrationStatus::MagnetDetected
+            && *cal == CalibrationStatus::MagnetWrongPoleOrMissing
+        {
+            self.cal_pending = self.cal_pending.saturating_add(1);
+            if self.cal_pending < config.cal_debounce {
+                return true;
+            }
+        }
+        self.cal_pending = 0;
+        false
+    }
+
+    /// Hysteresis when recovering from a magnet fault
+    /// Returns the status to commit; the current fault is held until the reading has cleared
+    /// the threshold by the recovery margin for the configured number of windows.
+    /// MagnetWrongPoleOrMissing (magnet too weak) must clear min_ok, SensorBroken (magnet too
+    /// strong) must clear MXOK.
+    fn recover_cal<const MXOK: usize>(
+        &mut self,
+        cal: CalibrationStatus,
+        data: u16,
+        min_ok: u16,
+        config: &SensorsConfig,
+    ) -> CalibrationStatus {
+        let recovery = match config.cal_recovery {
+            Some(recovery) => recovery,
+            None => return cal,
+        };
+        let clear = match (&self.cal, &cal) {
+            (CalibrationStatus::MagnetWrongPoleOrMissing, CalibrationStatus::MagnetDetected) => {
+                data >= min_ok.saturating_add(recovery.margin)
+            }
+            (CalibrationStatus::SensorBroken, CalibrationStatus::MagnetDetected) => {
+                data <= threshold(MXOK).saturating_sub(recovery.margin)
+            }
+            _ => {
+                self.recovery_pending = 0;
+                return cal;
+            }
+        };
+
+        if clear {
+            self.recovery_pending = self.recovery_pending.saturating_add(1);
+            if self.recovery_pending >= recovery.windows {
+                self.recovery_pending = 0;
+                return cal;
+            }
+        } else {
+            self.recovery_pending = 0;
+        }
+        trace!(
+            "Reading: {}  Recovery (pending {}/{}): {:?}",
+            data,
+            self.recovery_pending,
+            recovery.windows,
+            self.cal
+        );
+        self.cal.clone()
+    }
+
+    /// SensorBroken cool-down (see SensorsConfig::broken_retry)
+    /// Returns true if the window should not be evaluated (still cooling down, or latched once
+    /// the retries are used up). After the cool-down the sensor is evaluated as if NotReady.
+    fn hold_broken(&mut self, config: &SensorsConfig) -> bool {
+        let retry = match config.broken_retry {
+            Some(retry) if self.cal == CalibrationStatus::SensorBroken => retry,
+            _ => return false,
+        };
+        if self.broken_retries >= retry.max_retries {
+            return true;
+        }
+        self.broken_windows = self.broken_windows.saturating_add(1);
+        if self.broken_windows < retry.cooldown {
+            return true;
+        }
+
+        self.broken_windows = 0;
+        self.broken_retries += 1;
+        trace!(
+            "SensorBroken retry {}/{}",
+            self.broken_retries,
+            retry.max_retries
+        );
+        self.cal = CalibrationStatus::NotReady;
+        false
+    }
+
+    /// SensorBroken retries so far (see SensorsConfig::broken_retry)
+    /// Not reset when the sensor recovers, only by recalibrate.
+    pub fn broken_retries(&self) -> u8 {
+        self.broken_retries
+    }
+
+    /// Require CAL_CONFIRM consecutive in-range windows before declaring MagnetDetected
+    /// Returns the status to commit; the current status is held until enough consecutive
+    /// windows have been in range. Any out-of-range window restarts the count.
+    /// A magnet reappearing within the hot-swap grace period is not confirmed (fast
+    /// recalibration, see SensorsConfig::hot_swap).
+    fn confirm_cal<const CAL_CONFIRM: usize>(
+        &mut self,
+        cal: CalibrationStatus,
+    ) -> CalibrationStatus {
+        const {
+            assert!(
+                CAL_CONFIRM <= u8::MAX as usize,
+                "CAL_CONFIRM is limited to 255"
+            )
+        };
+        if cal != CalibrationStatus::MagnetDetected {
+            self.confirm_pending = 0;
+            return cal;
+        }
+        if self.cal == CalibrationStatus::MagnetDetected || self.hot_swap.is_some() {
+            self.confirm_pending = 0;
+            return cal;
+        }
+
+        self.confirm_pending = self.confirm_pending.saturating_add(1);
+        if self.confirm_pending as usize >= CAL_CONFIRM {
+            self.confirm_pending = 0;
+            return cal;
+        }
+        trace!(
+            "Confirm (pending {}/{}): {:?}",
+            self.confirm_pending,
+            CAL_CONFIRM,
+            self.cal
+        );
+        self.cal.clone()
+    }
+
+    /// Hot-swap grace period (see SensorsConfig::hot_swap)
+    /// cal is the status about to be committed, data is the averaged sample in the lookup table
+    /// domain. Must be called before the stats are updated with the sample.
+    fn track_hot_swap(&mut self, cal: &CalibrationStatus, data: u16, config: &SensorsConfig) {
+        let hot_swap = match config.hot_swap {
+            Some(hot_swap) => hot_swap,
+            None => return,
+        };
+        let removed = matches!(
+            cal,
+            CalibrationStatus::MagnetWrongPoleOrMissing | CalibrationStatus::SensorMissing
+        );
+
+        match self.hot_swap.take() {
+            // Magnet removed
+            None if removed && self.cal == CalibrationStatus::MagnetDetected => {
+                trace!("Hot-swap: removed  Stats: {:?}", self.stats);
+                self.hot_swap = Some(HotSwapGrace {
+                    min: self.stats.min,
+                    min_valid: self.stats.min_valid,
+                    windows: 0,
+                });
+                self.hot_swap_event = Some(HotSwapEvent::Removed);
+            }
+            None => {}
+            // Magnet reappeared, restore the rest position if the reading is still close to it
+            Some(grace) if *cal == CalibrationStatus::MagnetDetected => {
+                let rest = grace.min >> self.oversample;
+                let seeded = grace.min_valid && data.abs_diff(rest) <= hot_swap.tolerance;
+                if seeded {
+                    self.stats.min = grace.min;
+                    self.stats.min_valid = true;
+                }
+                trace!("Hot-swap: reinserted  Seeded: {}", seeded);
+                self.hot_swap_event = Some(HotSwapEvent::Reinserted { seeded });
+            }
+            Some(mut grace) => {
+                grace.windows = grace.windows.saturating_add(1);
+                if grace.windows >= hot_swap.grace {
+                    trace!("Hot-swap: expired");
+                    self.hot_swap_event = Some(HotSwapEvent::Expired);
+                } else {
+                    self.hot_swap = Some(grace);
+                }
+            }
+        }
+    }
+
+    /// Hot-swap event caused by the most recent add (if any, see SensorsConfig::hot_swap)
+    /// Only set on the add that caused it, cleared by the next add.
+    pub fn hot_swap_event(&self) -> Option<HotSwapEvent> {
+        self.hot_swap_event
+    }
+
+    /// The magnet was removed and the previous rest position is kept for the grace period
+    pub fn hot_swap_pending(&self) -> bool {
+        self.hot_swap.is_some()
+    }
+
+    /// Automatic min/max decay (see SensorsConfig::extrema_decay)
+    /// Relaxes min/max by one count every extrema_decay analysis windows.
+    fn auto_decay_extrema(&mut self, config: &SensorsConfig) {
+        if let Some(windows) = config.extrema_decay {
+            self.decay_windows = self.decay_windows.saturating_add(1);
+            if self.decay_windows >= windows {
+                self.decay_windows = 0;
+                self.decay_extrema::<1>();
+            }
+        }
+    }
+
+    /// Update calibration status, recording a transition if the status changed
+    fn set_cal(&mut self, cal: CalibrationStatus) {
+        if cal != self.cal {
+            trace!("Calibration: {:?} -> {:?}", self.cal, cal);
+            if cal == CalibrationStatus::MagnetDetected {
+                self.stats.samples_since_calibration = 0;
+            }
+            self.transition_from = Some(self.cal.clone());
+        }
+        self.cal = cal;
+    }
+
+    /// Calibration status change caused by the most recent add (if any)
+    /// Only set on the add that changed the status, cleared by the next add.
+    pub fn transition(&self) -> Option<CalibrationTransition> {
+        self.transition_from
+            .clone()
+            .map(|from| CalibrationTransition {
+                from,
+                to: self.cal.clone(),
+            })
+    }
+
+    /// Analysis samples since the sensor last entered MagnetDetected
+    /// Saturates at u32::MAX rather than wrapping.
+    pub fn samples_since_calibration(&self) -> u32 {
+        self.stats.samples_since_calibration
+    }
+
+    /// Recent analyses, oldest to newest
+    /// Holds up to H entries (always empty if H is 0), cleared by recalibrate.
+    pub fn history(&self) -> impl Iterator<Item = &SenseAnalysis> {
+        self.history.iter()
+    }
+
+    /// Calibration confidence, 0 (untrustworthy) to 255 (healthy)
+    /// confidence = max(spread, doubt) where
+    /// - spread = 255 * (max - min) / CONFIDENCE_SPREAD (saturating, in ADC counts)
+    /// - doubt = 127 * (CONFIDENCE_SAMPLES - samples) / CONFIDENCE_SAMPLES (0 once
+    ///   CONFIDENCE_SAMPLES samples have been analyzed since calibration)
+    ///
+    /// Non-decreasing with spread, non-increasing with samples: a freshly calibrated key starts
+    /// out at 127 and drops towards 0 if the magnet never moves, a key that has been pressed
+    /// through CONFIDENCE_SPREAD counts is 255. Sensors that are not calibrated are 0.
+    pub fn confidence(&self) -> u8 {
+        if self.cal != CalibrationStatus::MagnetDetected {
+            return 0;
+        }
+        let spread = (self.stats.max.saturating_sub(self.stats.min) >> self.oversample) as u32;
+        let spread = (spread * 255 / CONFIDENCE_SPREAD as u32).min(255);
+        let samples = self.stats.samples_since_calibration.min(CONFIDENCE_SAMPLES);
+        let doubt = 127 * (CONFIDENCE_SAMPLES - samples) / CONFIDENCE_SAMPLES;
+        spread.max(doubt) as u8
+    }
+
+    /// Latch the full travel value while bottom-out calibration is in progress
+    /// Only readings that have travelled at least bottom_out_threshold are considered so that
+    /// keys that haven't been pressed yet don't get confirmed.
+    fn latch_bottom_out(&mut self, data: u16, config: &SensorsConfig) {
+        if self.bottom_out_mode
+            && self.analysis.distance >= config.bottom_out_threshold
+            && data > self.stats.bottom_out
+        {
+            self.stats.bottom_out = data;
+        }
+    }
+
+    /// Full travel has been confirmed using bottom-out calibration
+    pub fn bottom_out_confirmed(&self) -> bool {
+        self.stats.bottom_out != 0
+    }
+
+    /// Full travel distance
+    /// Uses the calibrated bottom-out value if confirmed, otherwise default_travel.
+    /// table is the lookup table of the sensor (see Sensors::lookup_table).
+    pub fn full_travel(&self, table: &LookupTable, default_travel: i16) -> i16 {
+        if self.bottom_out_confirmed() {
+            saturate(
+                self.lookup_distance(table, self.stats.bottom_out)
+                    - self.lookup_distance(table, self.stats.min),
+            )
+        } else {
+            default_travel
+        }
+    }
+
+    /// Observed travel span, distance of the largest reading (stats.max) since calibration
+    /// 0 if the sensor isn't calibrated. Shrinks again if min/max decay is used (see
+    /// decay_extrema). table is the lookup table of the sensor (see Sensors::lookup_table).
+    pub fn travel_span(&self, table: &LookupTable) -> i16 {
+        if self.cal != CalibrationStatus::MagnetDetected || !self.stats.min_valid {
+            return 0;
+        }
+        saturate(
+            self.lookup_distance(table, self.stats.max)
+                - self.lookup_distance(table, self.stats.min),
+        )
+    }
+
+    /// Distance is within deadzone of the observed maximum (travel_span), see
+    /// SensorsConfig::bottom_deadzone. Never with a deadzone of 0.
+    fn in_bottom_deadzone(&self, table: &LookupTable, distance: i16, deadzone: u16) -> bool {
+        if deadzone == 0 {
+            return false;
+        }
+        let span = self.travel_span(table) as i32;
+        span > deadzone as i32 && distance as i32 >= span - deadzone as i32
+    }
+
+    /// Distance clamped to full travel (travel_span) within the bottom deadzone
+    /// See SensorsConfig::bottom_deadzone.
+    pub fn distance_bottom_clamped(&self, table: &LookupTable, deadzone: u16) -> i16 {
+        if self.in_bottom_deadzone(table, self.analysis.distance, deadzone) {
+            self.travel_span(table)
+        } else {
+            self.analysis.distance
+        }
+    }
+
+    /// Key has been pressed through at least TRAVEL distance units since calibration
+    /// Normalized outputs (e.g. analog_value with a calibrated range) aren't meaningful until
+    /// the full travel has been seen.
+    pub fn travel_covered<const TRAVEL: usize>(&self, table: &LookupTable) -> bool {
+        self.travel_span(table) as i32 >= TRAVEL as i32
+    }
+
+    /// Calibration quality, computed from the stats and the calibration thresholds (test mode)
+    /// The key must have been pressed through expected_travel for a full travel component.
+    /// Noise reaching 1/16 of the raw travel span scores 0. Sensors that aren't MagnetDetected
+    /// score zero on every component.
+    pub fn calibration_quality<const MNOK: usize, const NS: usize>(
+        &self,
+        table: &LookupTable,
+        expected_travel: i16,
+    ) -> CalibrationQuality {
+        if self.cal != CalibrationStatus::MagnetDetected {
+            return CalibrationQuality::default();
+        }
+        let scale = |val: u32, full: u32| {
+            val.saturating_mul(255)
+                .checked_div(full)
+                .map_or(255, |val| val.min(255) as u8)
+        };
+
+        let travel = scale(
+            self.travel_span(table).max(0) as u32,
+            expected_travel.max(0) as u32,
+        );
+
+        let span = self.stats.max.saturating_sub(self.stats.min) as u32;
+        let noise = match self.noise() {
+            Some(noise) if span > 0 => 255 - scale(noise as u32 * 16, span),
+            Some(_) => 0,
+            None => 255,
+        };
+
+        let rest = self.stats.min >> self.oversample;
+        let weak = threshold(MNOK);
+        let gap = weak.saturating_sub(threshold(NS)) as u32;
+        let margin = scale(rest.saturating_sub(weak) as u32, gap);
+
+        CalibrationQuality {
+            travel,
+            noise,
+            margin,
+        }
+    }
+
+    /// Analog output value (e.g. gamepad axis emulation)
+    /// Linearly maps the distance between TOP (0) and BOTTOM (255).
+    /// Distances before TOP clamp to 0 and distances past BOTTOM clamp to 255.
+    pub fn analog_value<const TOP: usize, const BOTTOM: usize>(&self) -> u8 {
+        const { assert!(TOP < BOTTOM, "TOP must be less than BOTTOM") };
+        let distance = self.analysis.distance as i32 - TOP as i32;
+        let range = (BOTTOM - TOP) as i32;
+        (distance.clamp(0, range) * u8::MAX as i32 / range) as u8
+    }
+
+    /// Distance with a dead zone around the rest position
+    /// Distances below DZ (including negative distances) are reported as 0 to hide idle jitter.
+    /// The distance is already relative to the calibrated rest position (stats.min).
+    pub fn distance_deadzoned<const DZ: usize>(&self) -> i16 {
+        if (self.analysis.distance as i32) < DZ as i32 {
+            0
+        } else {
+            self.analysis.distance
+        }
+    }
+
+    /// Mirror the reading around the ADC midpoint for inverted sensors
+    /// Everything after this point (lookup, calibration thresholds, stats) works in the mirrored
+    /// domain so inverted sensors behave identically to normal sensors.
+    fn mirror(&self, reading: u16) -> u16 {
+        if self.inverted {
+            ADC_MAX.saturating_sub(reading)
+        } else {
+            reading
+        }
+    }
+
+    /// Mirror an averaged sample (see SampleProducer)
+    /// Equivalent to mirroring each reading before averaging (to within a count of rounding).
+    fn mirror_sample(&self, data: u16, bits: u8) -> u16 {
+        if self.inverted {
+            ((ADC_MAX as u32) << bits).saturating_sub(data as u32) as u16
+        } else {
+            data
+        }
+    }
+
+    /// Sensor reading is mirrored (see Sensors::set_inverted)
+    pub fn inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Track runs of identical raw readings
+    fn track_flatline(&mut self, reading: u16) {
+        if reading == self.last_reading && self.flat_readings > 0 {
+            self.flat_readings = self.flat_readings.saturating_add(1);
+        } else {
+            self.last_reading = reading;
+            self.flat_readings = 1;
+        }
+    }
+
+    /// Sensor reading has not changed over the last WINDOW raw readings (zero variance)
+    /// An ADC reading that never moves (not even by noise) usually means a disconnected or
+    /// shorted sensor, even if the value happens to be within the calibration thresholds.
+    /// Uses raw readings (before filtering/averaging), WINDOW should be long enough that a
+    /// healthy sensor would show at least some noise.
+    pub fn is_flatlined<const WINDOW: usize>(&self) -> bool {
+        WINDOW > 0 && self.flat_readings as usize >= WINDOW
+    }
+
+    /// Most recent analysis computed while the sensor was calibrated (MagnetDetected)
+    /// Unlike analysis, this is not cleared when the sensor drops out of calibration.
+    /// Returns None if the sensor has never been calibrated.
+    ///
+    /// NOTE: The snapshot is stale while the sensor is out of calibration; it holds the last
+    ///       known position (rather than dropping to zero and causing a release) but will not
+    ///       follow any movement until calibration recovers. Check cal to determine whether the
+    ///       snapshot is current.
+    pub fn last_good(&self) -> Option<&SenseAnalysis> {
+        // Current analysis while calibrated (once analyzed), no per-sample copy
+        if self.cal == CalibrationStatus::MagnetDetected && self.stats.samples_since_calibration > 0
+        {
+            Some(&self.analysis)
+        } else {
+            self.last_good.as_ref()
+        }
+    }
+
+    /// Idle detection
+    /// True when the key hasn't meaningfully moved (absolute velocity within IDLE_VELOCITY)
+    /// for at least IDLE_SAMPLES consecutive analysis samples.
+    /// Any movement resets the count. Sensors that are not calibrated are never idle.
+    pub fn is_idle<const IDLE_SAMPLES: usize>(&self) -> bool {
+        self.cal == CalibrationStatus::MagnetDetected && self.idle_samples as usize >= IDLE_SAMPLES
+    }
+
+    /// Number of consecutive analysis samples within IDLE_VELOCITY (see is_idle)
+    /// Independent of SensorsConfig::idle.
+    pub fn idle_samples(&self) -> u32 {
+        self.idle_samples
+    }
+
+    /// Idle flag, set once SensorsConfig::idle is satisfied
+    /// Cleared as soon as the distance changes by more than the epsilon. Always false if
+    /// SensorsConfig::idle is None or the sensor is not calibrated.
+    pub fn idle(&self) -> bool {
+        self.cal == CalibrationStatus::MagnetDetected && self.idle
+    }
+
+    /// Discard a partial accumulation (e.g. after a corrupted ADC conversion)
+    /// Stats and calibration are not modified.
+    pub fn discard_partial(&mut self) {
+        self.data.discard_partial();
+    }
+
+    /// Number of sub-samples accumulated towards the next averaged sample
+    /// 0 when no accumulation is in progress (i.e. the last add produced a sample).
+    pub fn pending_samples(&self) -> u8 {
+        self.data.scratch_samples
+    }
+
+    /// Actuation point of the key
+    /// Uses the per-key actuation point if set (see Sensors::set_actuation), otherwise AP.
+    pub fn actuation_point<const AP: usize>(&self) -> u16 {
+        if self.actuation != 0 {
+            self.actuation
+        } else {
+            AP as u16
+        }
+    }
+
+    /// Distance has reached the actuation point of the key (see actuation_point)
+    pub fn actuated<const AP: usize>(&self) -> bool {
+        self.analysis.distance as i32 >= self.actuation_point::<AP>() as i32
+    }
+
+    /// Poll the actuation state machine, returning only press/release transitions
+    /// The key is pressed once the distance reaches the actuation point (see actuation_point)
+    /// and released once it drops below the actuation point minus HYST.
+    /// If the sensor leaves MagnetDetected while pressed, a synthetic Release is returned and the
+    /// key stays released until calibrated again.
+    pub fn poll_actuation<const AP: usize, const HYST: usize>(&mut self) -> Option<ActuationEvent> {
+        if self.cal != CalibrationStatus::MagnetDetected {
+            return if core::mem::take(&mut self.pressed) {
+                Some(ActuationEvent::Release)
+            } else {
+                None
+            };
+        }
+
+        let distance = self.analysis.distance as i32;
+        let point = self.actuation_point::<AP>() as i32;
+        if !self.pressed && distance >= point {
+            self.pressed = true;
+            #[cfg(feature = "press-count")]
+            {
+                self.presses = self.presses.saturating_add(1);
+            }
+            Some(ActuationEvent::Press)
+        } else if self.pressed && distance < point - HYST as i32 {
+            self.pressed = false;
+            Some(ActuationEvent::Release)
+        } else {
+            None
+        }
+    }
+
+    /// Key pressed state of the actuation state machine (see poll_actuation)
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+
+    /// Presses reported by poll_actuation (e.g. for a heatmap), saturates at u32::MAX
+    /// Kept across recalibration.
+    #[cfg(feature = "press-count")]
+    pub fn presses(&self) -> u32 {
+        self.presses
+    }
+
+    /// Clear the press counter
+    #[cfg(feature = "press-count")]
+    pub fn reset_presses(&mut self) {
+        self.presses = 0;
+    }
+
+    /// Velocity strike detection
+    /// Returns an event if the downward (positive) velocity of the most recent analysis
+    /// exceeds VT. Upward movement (key release) never triggers.
+    /// The sensor must be calibrated (MagnetDetected).
+    pub fn velocity_event<const VT: usize>(&self) -> Option<VelocityEvent> {
+        if self.cal == CalibrationStatus::MagnetDetected
+            && self.analysis.velocity > 0
+            && self.analysis.velocity as usize > VT
+        {
+            Some(VelocityEvent {
+                velocity: self.analysis.velocity,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Raw ADC window comparator thresholds for an actuation distance
+    /// Inverts the lookup table (binary search, the table must be monotonic), accounting for
+    /// the calibrated rest position, temperature compensation and inversion.
+    /// The release threshold is hysteresis below the actuation distance.
+    /// Only valid while MagnetDetected, recompute after recalibration or temperature changes.
+    ///
+    /// NOTE: The comparator sees individual samples rather than the averaged analysis value,
+    ///       use it to wake the analysis loop rather than to actuate directly.
+    pub fn comparator_thresholds(
+        &self,
+        table: &LookupTable,
+        actuation: i16,
+        hysteresis: i16,
+    ) -> Result<ComparatorThresholds, SensorError> {
+        if self.cal != CalibrationStatus::MagnetDetected {
+            return Err(SensorError::CalibrationError(self.cal.clone()));
+        }
+        Ok(ComparatorThresholds {
+            press: self.mirror(self.raw_for_distance(table, actuation)),
+            release: self
+                .mirror(self.raw_for_distance(table, actuation.saturating_sub(hysteresis))),
+            rising: !self.inverted,
+        })
+    }
+
+    /// Smallest (mirrored) raw reading that reaches distance
+    /// Returns ADC_MAX if the distance is not reachable.
+    /// The search covers the oversampled range, the result is rounded up to a TABLE_BITS reading
+    /// (the comparator sees individual samples).
+    fn raw_for_distance(&self, table: &LookupTable, distance: i16) -> u16 {
+        let target =
+            distance as i32 + self.lookup_distance(table, self.stats.min) + self.temp_offset as i32;
+        let (mut low, mut high) = (0, self.raw_max());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.lookup_distance(table, mid) < target {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        let round = (1 << self.oversample) - 1;
+        ((low as u32 + round) >> self.oversample).min(ADC_MAX as u32) as u16
+    }
+
+    /// Analog frontend gain/offset adjustment recommended for this sensor
+    /// Re-evaluated when the calibration status or min/max change (see GainRecommendation), a
+    /// new recommendation is only committed once it has been consistent for GAIN_WINDOWS windows.
+    pub fn gain_recommendation(&self) -> GainRecommendation {
+        self.gain
+    }
+
+    /// Update the gain recommendation (with hysteresis)
+    /// Only re-evaluated once the calibration status or min/max change, or while a new
+    /// recommendation is pending.
+    fn update_gain(&mut self) {
+        let inputs = (self.cal.clone(), self.stats.min, self.stats.max);
+        if self.gain_pending == 0 && inputs == self.gain_inputs {
+            return;
+        }
+        self.gain_inputs = inputs;
+        let recommendation = self.evaluate_gain();
+        if recommendation == self.gain {
+            self.gain_pending = 0;
+            return;
+        }
+        self.gain_pending = self.gain_pending.saturating_add(1);
+        if self.gain_pending >= GAIN_WINDOWS {
+            trace!("Gain: {:?} -> {:?}", self.gain, recommendation);
+            self.gain = recommendation;
+            self.gain_pending = 0;
+        }
+    }
+
+    /// Gain recommendation for the current calibration status and min/max span
+    /// Steps towards recommended_frontend.
+    fn evaluate_gain(&self) -> GainRecommendation {
+        match self.cal {
+            CalibrationStatus::MagnetDetected => {}
+            // Readings above the valid range, likely clipping at the operating gain
+            CalibrationStatus::SensorBroken => {
+                return GainRecommendation {
+                    gain: GainStep::Decrease,
+                    offset: OffsetStep::Hold,
+                };
+            }
+            // Stay at the calibration gain
+            _ => return GainRecommendation::hold(),
+        }
+
+        let (min, max) = self.frontend_range();
+        let clipping = min < GAIN_RAIL || max > ADC_MAX - GAIN_RAIL;
+        if !clipping && max.saturating_sub(min) < GAIN_MIN_SPAN {
+            // Not enough travel observed yet, stay at the calibration gain
+            return GainRecommendation::hold();
+        }
+
+        let frontend = self.recommended_frontend();
+        let gain = if clipping {
+            GainStep::Decrease
+        } else if frontend.gain as u32 >= 2 * GAIN_SCALE {
+            GainStep::Increase
+        } else {
+            GainStep::Hold
+        };
+        let offset = if frontend.offset > GAIN_RAIL as i16 {
+            OffsetStep::Lower
+        } else if frontend.offset < -(GAIN_RAIL as i16) {
+            OffsetStep::Raise
+        } else {
+            OffsetStep::Hold
+        };
+        GainRecommendation { gain, offset }
+    }
+
+    /// Observed min/max in TABLE_BITS counts (the frontend doesn't see the oversampling)
+    fn frontend_range(&self) -> (u16, u16) {
+        (
+            self.stats.min >> self.oversample,
+            self.stats.max >> self.oversample,
+        )
+    }
+
+    /// Recommended analog frontend settings to maximize the usable ADC range
+    /// The offset centers min..max around the ADC midpoint and the gain scales min..max to fill
+    /// the ADC range, with 1/8 of the range of headroom on each side.
+    /// Uncalibrated sensors (or sensors without any observed travel) get unity gain and no
+    /// offset, i.e. keep running at the lower (uncalibrated) gain.
+    ///
+    /// NOTE: Reprogramming the frontend changes the raw readings, so the sensor must be
+    ///       recalibrated afterwards (see recalibrate).
+    pub fn recommended_frontend(&self) -> FrontendConfig {
+        let (min, max) = self.frontend_range();
+        let (min, max) = (min as u32, max as u32);
+        if self.cal != CalibrationStatus::MagnetDetected || max <= min {
+            return FrontendConfig::default();
+        }
+        let center = (min + max) / 2;
+        let range = max - min;
+        let range = range + range / 4;
+        let gain = (ADC_MAX as u32 + 1) * GAIN_SCALE / range;
+        FrontendConfig {
+            gain: gain.clamp(GAIN_SCALE, u16::MAX as u32) as u16,
+            offset: (center as i32 - (ADC_MAX as i32 + 1) / 2) as i16,
+        }
+    }
+
+    /// Update calibration state
+    /// Calibration is different depending on whether or not we've already been successfully
+    /// calibrated. Gain and offset are set differently depending on whether the sensor has been
+    /// calibrated. Uncalibrated sensors run at a lower gain to gather more details around voltage
+    /// limits. Wherease calibrated sensors run at higher gain (and likely an offset) to maximize
+    /// the voltage range of the desired sensor range.
+    /// NOTE: This implementation (currently) only works for a single magnet pole of a bipolar sensor.
+    fn check_calibration<const MXOK: usize, const NS: usize>(
+        &self,
+        data: u16,
+        min_ok: u16,
+    ) -> CalibrationStatus {
+        // Value too high, likely a bad sensor or bad soldering on the pcb
+        // Magnet may also be too strong.
+        // Readings beyond the lookup table saturate at ADC_MAX (see scale_reading), so a full
+        // scale reading is always broken, even if MXOK is larger than the table.
+        if data > threshold(MXOK).min(ADC_MAX - 1) {
+            return CalibrationStatus::SensorBroken;
+        }
+        // No sensor detected
+        if data < threshold(NS) {
+            return CalibrationStatus::SensorMissing;
+        }
+        // Wrong pole (or magnet may be too weak)
+        if data < min_ok {
+            return CalibrationStatus::MagnetWrongPoleOrMissing;
+        }
+
+        CalibrationStatus::MagnetDetected
+    }
+}
+
+impl<const H: usize> Default for SenseData<H> {
+    fn default() -> Self {
+        SenseData::new()
+    }
+}
+
+/// Scale of FrontendConfig::gain (a gain of 1.0)
+pub const GAIN_SCALE: u32 = 256;
+
+/// Recommended analog frontend (PGA/DAC) settings for a sensor
+/// See SenseData::recommended_frontend
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct FrontendConfig {
+    /// Gain relative to the current gain (GAIN_SCALE is 1.0)
+    pub gain: u16,
+    /// Offset (in ADC counts at the current gain) to subtract so the operating range is centered
+    /// around the ADC midpoint
+    pub offset: i16,
+}
+
+impl Default for FrontendConfig {
+    /// Unity gain, no offset (uncalibrated)
+    fn default() -> Self {
+        FrontendConfig {
+            gain: GAIN_SCALE as u16,
+            offset: 0,
+        }
+    }
+}
+
+/// Distance (in ADC counts) from either end of the ADC range considered clipping, also the
+/// offset dead band around the ADC midpoint
+pub const GAIN_RAIL: u16 = (ADC_MAX + 1) / 16;
+
+/// Minimum observed travel (max - min, in ADC counts) before a gain increase is recommended
+/// (i.e. at least one keypress, not just rest noise)
+pub const GAIN_MIN_SPAN: u16 = 128;
+
+/// Consecutive analysis windows before a new gain recommendation is committed
+pub const GAIN_WINDOWS: u8 = 4;
+
+/// Analog frontend gain adjustment
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum GainStep {
+    Hold = 0,     // Keep the current gain
+    Increase = 1, // Observed range would still fit at twice the gain
+    Decrease = 2, // Readings are clipping (or close to it)
+}
+
+/// Analog frontend offset adjustment
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum OffsetStep {
+    Hold = 0,  // Range is centered around the ADC midpoint
+    Lower = 1, // Range is centered above the ADC midpoint, shift readings down
+    Raise = 2, // Range is centered below the ADC midpoint, shift readings up
+}
+
+/// Analog frontend (PGA/DAC) adjustment recommendation (see SenseData::gain_recommendation)
+/// Uncalibrated sensors hold the (lower) calibration gain, SensorBroken recommends decreasing the
+/// gain. Calibrated sensors are evaluated using the observed min/max span.
+/// The steps move towards SenseData::recommended_frontend (the absolute settings).
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct GainRecommendation {
+    pub gain: GainStep,
+    pub offset: OffsetStep,
+}
+
+impl GainRecommendation {
+    /// No adjustment
+    pub const fn hold() -> GainRecommendation {
+        GainRecommendation {
+            gain: GainStep::Hold,
+            offset: OffsetStep::Hold,
+        }
+    }
+}
+
+/// Raw ADC window comparator thresholds (see SenseData::comparator_thresholds)
+/// If rising, the key is pressed once the reading is >= press and released once < release.
+/// Otherwise (inverted sensor) the key is pressed once <= press and released once > release.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct ComparatorThresholds {
+    pub press: u16,
+    pub release: u16,
+    pub rising: bool,
+}
+
+// ----- Hall Effect Interface ------
+
+/// Automatic minimum magnet threshold (MNOK) estimation
+/// While calibrated, the peak-to-peak noise of each window of averaged samples is measured. The
+/// per-sensor threshold is the rest value minus multiple * noise. Until a threshold is derived
+/// (and after calibration is lost) the static MNOK threshold is used.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct AutoThreshold {
+    /// Number of averaged samples per noise measurement window
+    pub window: u8,
+    /// Multiple of the peak-to-peak noise subtracted from the rest value
+    pub multiple: u8,
+}
+
+/// Calibration fault recovery hysteresis (test mode only)
+/// Prevents a magnet sitting right at a threshold from flapping between a fault and
+/// MagnetDetected (resetting the stats each time).
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct CalibrationRecovery {
+    /// Distance (in ADC counts) the averaged reading must be past the fault threshold
+    pub margin: u16,
+    /// Consecutive windows past the margin required to recover, 0 or 1 recovers immediately
+    pub windows: u8,
+}
+
+/// SensorBroken cool-down and retry (test mode only)
+/// SensorBroken may be caused by a transient glitch on the analog rail. Once the cool-down has
+/// elapsed the sensor is re-evaluated as if NotReady; once max_retries is reached, SensorBroken
+/// latches.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct BrokenRetry {
+    /// Windows to wait in SensorBroken before retrying (readings are ignored)
+    pub cooldown: u16,
+    /// Maximum number of retries, 0 latches immediately
+    pub max_retries: u8,
+}
+
+/// Per-sensor idle detection (e.g. to scan idle ADC channels less frequently)
+/// A key held still anywhere in its travel (including bottomed out) is idle.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct IdleDetection {
+    /// Maximum absolute distance change per analysis window still considered idle
+    pub epsilon: u16,
+    /// Consecutive windows within epsilon required to mark the sensor idle
+    pub windows: u16,
+}
+
+/// Exponential smoothing of velocity
+/// Velocity as a single first difference is noisy at high scan rates, the smoothed velocity is
+/// a shift-based EMA of it (larger shift is smoother, but slower to respond).
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct VelocitySmoothing {
+    /// EMA shift (between 1 and 15), 0 disables smoothing
+    pub shift: u8,
+    /// Derive acceleration and jerk from the smoothed velocity instead of the raw velocity
+    pub derivatives: bool,
+}
+
+/// Scale of the AlphaBeta gains (a gain of 1.0)
+pub const ALPHA_BETA_SCALE: i32 = 256;
+
+/// Alpha-beta filter over distance (a steady state 1D Kalman filter, constant velocity model)
+/// Each analysis predicts the distance from the previous estimate and velocity, then corrects
+/// both by the residual (measured - predicted) scaled by alpha and beta. Larger gains are more
+/// responsive, smaller gains reject more noise. Fixed-point, estimates are kept in 1/256 counts.
+/// A good starting point is the Benedict-Bordner relation beta = alpha^2 / (2 - alpha), e.g.
+/// alpha 64 with beta 9 (0.25 and 0.036).
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct AlphaBeta {
+    /// Distance correction gain (1 to ALPHA_BETA_SCALE)
+    pub alpha: u16,
+    /// Velocity correction gain (0 to ALPHA_BETA_SCALE)
+    pub beta: u16,
+}
+
+/// Per-sensor alpha-beta estimate, in 1/ALPHA_BETA_SCALE counts
+#[derive(Clone, Debug, defmt::Format)]
+struct AlphaBetaState {
+    distance: i32,
+    velocity: i32,
+}
+
+impl AlphaBetaState {
+    /// Start tracking at the measured distance (at rest)
+    fn seed(distance: i16) -> AlphaBetaState {
+        AlphaBetaState {
+            distance: distance as i32 * ALPHA_BETA_SCALE,
+            velocity: 0,
+        }
+    }
+
+    /// Update with a measured distance, returns the estimated (distance, velocity)
+    fn update(&mut self, measured: i16, gains: AlphaBeta) -> (i16, i16) {
+        let alpha = (gains.alpha as i32).clamp(1, ALPHA_BETA_SCALE);
+        let beta = (gains.beta as i32).min(ALPHA_BETA_SCALE);
+        let predicted = self.distance + self.velocity;
+        let residual = measured as i32 * ALPHA_BETA_SCALE - predicted;
+        self.distance = predicted + residual * alpha / ALPHA_BETA_SCALE;
+        self.velocity += residual * beta / ALPHA_BETA_SCALE;
+        // Keep the state within what can be reported
+        let max = i16::MAX as i32 * ALPHA_BETA_SCALE;
+        self.distance = self.distance.clamp(-max, max);
+        self.velocity = self.velocity.clamp(-max, max);
+        (
+            saturate(round_scaled(self.distance)),
+            saturate(round_scaled(self.velocity)),
+        )
+    }
+}
+
+/// Round a 1/ALPHA_BETA_SCALE fixed-point value to the nearest count
+fn round_scaled(val: i32) -> i32 {
+    (val + ALPHA_BETA_SCALE / 2).div_euclid(ALPHA_BETA_SCALE)
+}
+
+/// Hot-swap handling (test mode only)
+/// When a calibrated sensor loses its magnet (MagnetWrongPoleOrMissing or SensorMissing, e.g.
+/// the switch is pulled while powered) its rest position is remembered for a grace period. A
+/// magnet reappearing within the grace period is declared MagnetDetected immediately (without
+/// waiting for CAL_CONFIRM windows, see Sensors), and the previous rest position (min) is
+/// restored if the reading is close to it. Max, noise, derived thresholds and bottom-out are
+/// measured again.
+/// See SenseData::hot_swap_event.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct HotSwap {
+    /// Windows the previous rest position is kept after the magnet is removed
+    pub grace: u16,
+    /// Maximum distance (in ADC counts) from the previous rest position to restore it
+    pub tolerance: u16,
+}
+
+/// Rest position kept during the hot-swap grace period
+#[derive(Clone, Copy, Debug, defmt::Format)]
+struct HotSwapGrace {
+    /// SenseStats::min and min_valid when the magnet was removed
+    min: u16,
+    min_valid: bool,
+    /// Windows since the magnet was removed
+    windows: u16,
+}
+
+/// Keyboard-level travel normalization (see Sensors::normalize)
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Normalization {
+    /// Maximum deviation (percent) of a sensor's travel span from the median before it is
+    /// flagged as an outlier
+    pub tolerance: u8,
+}
+
+/// Runtime configuration shared by all sensors in a Sensors instance
+/// Stored once per Sensors (not per sensor) to save sram.
+#[derive(Clone, Debug, PartialEq, defmt::Format)]
+pub struct SensorsConfig {
+    /// Filter used to accumulate incoming raw samples
+    pub filter: Filter,
+    /// Median pre-filter applied to each raw sample before the filter
+    pub median: Median,
+    /// Automatic minimum magnet threshold estimation (test mode only), None uses MNOK
+    pub auto_threshold: Option<AutoThreshold>,
+    /// Full travel distance used for sensors without a confirmed bottom-out calibration
+    pub default_travel: i16,
+    /// Minimum distance a key must travel to be latched during bottom-out calibration
+    pub bottom_out_threshold: i16,
+    /// Consecutive windows required to lose calibration (MagnetDetected ->
+    /// MagnetWrongPoleOrMissing), 0 or 1 loses calibration immediately
+    pub cal_debounce: u8,
+    /// Automatic min/max decay, relax min/max by one count towards the current reading every
+    /// N analysis windows so transient extremes age out. None disables (see
+    /// SenseData::decay_extrema for manual decay)
+    pub extrema_decay: Option<u16>,
+    /// Hysteresis for recovering from MagnetWrongPoleOrMissing and SensorBroken, None recovers
+    /// as soon as a window is within the thresholds
+    pub cal_recovery: Option<CalibrationRecovery>,
+    /// Velocity smoothing, None keeps the smoothed velocity equal to the velocity
+    pub velocity_smoothing: Option<VelocitySmoothing>,
+    /// SensorBroken cool-down and retry, None re-evaluates SensorBroken every window
+    pub broken_retry: Option<BrokenRetry>,
+    /// Idle flag detection (see SenseData::idle), None never flags sensors as idle
+    /// Counted separately from SenseData::is_idle (which always uses IDLE_VELOCITY).
+    pub idle: Option<IdleDetection>,
+    /// Resolution (bits) of the incoming ADC readings, scaled to TABLE_BITS
+    pub adc_bits: u8,
+    /// Readings below the calibrated rest position (see RestBoundary)
+    pub rest: RestBoundary,
+    /// Keyboard-level travel normalization, None disables Sensors::normalize
+    pub normalization: Option<Normalization>,
+    /// Alpha-beta distance and velocity estimation, None uses the raw distance and its first
+    /// difference
+    pub estimator: Option<AlphaBeta>,
+    /// Hot-swap grace period and fast recalibration, None forgets the calibration as soon as
+    /// the magnet is lost
+    pub hot_swap: Option<HotSwap>,
+    /// Bottom deadzone (distance units), bottoming out compresses the switch housing for a few
+    /// counts of extra travel. Distances within the deadzone of the observed maximum
+    /// (SenseData::travel_span) are full travel for normalized output (see
+    /// SenseData::distance_bottom_clamped) and velocity is 0 while the key stays within it (e.g.
+    /// so wiggling at the bottom doesn't look like a release). The observed maximum grows with
+    /// the first press after calibration, which is affected as well. 0 disables
+    pub bottom_deadzone: u16,
+}
+
+impl Default for SensorsConfig {
+    fn default() -> Self {
+        SensorsConfig {
+            filter: Filter::default(),
+            median: Median::default(),
+            auto_threshold: None,
+            // Roughly 4 mm of travel (see DEFAULT_LOOKUP_TABLE)
+            default_travel: 1000,
+            bottom_out_threshold: 500,
+            cal_debounce: 3,
+            extrema_decay: None,
+            cal_recovery: None,
+            velocity_smoothing: None,
+            broken_retry: None,
+            idle: None,
+            adc_bits: TABLE_BITS,
+            rest: RestBoundary::Track,
+            normalization: None,
+            estimator: None,
+            hot_swap: None,
+            bottom_deadzone: 0,
+        }
+    }
+}
+
+/// Linear temperature compensation
+/// The distance offset is slope * (temperature - reference) / 256 (slope is in 1/256 distance
+/// units per temperature unit). Temperature units are whatever the onboard sensor reports, as
+/// long as temperature and reference use the same units.
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct TempCompensation {
+    pub slope: i16,
+    pub reference: i16,
+}
+
+impl TempCompensation {
+    /// Distance offset at the given temperature
+    pub fn offset(&self, temperature: i16) -> i16 {
+        saturate(self.slope as i32 * (temperature as i32 - self.reference as i32) / 256)
+    }
+}
+
+/// Calibration quality of a sensor (see SenseData::calibration_quality)
+/// Each component is 0 (bad) to 255 (good).
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct CalibrationQuality {
+    /// Observed travel span relative to the expected travel (255 once fully covered)
+    pub travel: u8,
+    /// Rest noise relative to the raw travel span, 255 if the noise wasn't measured
+    pub noise: u8,
+    /// Rest position margin above the too-weak magnet threshold (MNOK), relative to the gap
+    /// between the no-sensor (NS) and too-weak thresholds
+    pub margin: u8,
+}
+
+impl CalibrationQuality {
+    /// Overall score (0 to 255), the product of the components so any single weak component
+    /// pulls the score down
+    pub fn score(&self) -> u8 {
+        (self.travel as u32 * self.noise as u32 * self.margin as u32 / (255 * 255)) as u8
+    }
+}
+
+/// Summary of a processed frame (see Sensors::add_frame)
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct FrameResult {
+    /// Number of sensors that produced a new analysis
+    pub analyses: usize,
+}
+
+/// Role of a sensor in a differential pair (see Sensors::set_differential)
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+enum Pairing {
+    Single,
+    /// Analyzed using the difference with the secondary sensor
+    Primary(u16),
+    /// Only provides readings to the primary sensor
+    Secondary(u16),
+}
+
+/// Combined reading of a differential pair, half the difference centered at the ADC midpoint
+/// Offsets common to both sensors (ambient field, temperature) cancel out.
+fn differential(primary: u16, secondary: u16, bits: u8) -> u16 {
+    let mid = ((ADC_MAX as i32 + 1) / 2) << bits;
+    let max = ((ADC_MAX as i32 + 1) << bits) - 1;
+    (mid + (primary as i32 - secondary as i32) / 2).clamp(0, max) as u16
+}
+
+/// Hall effect sensor array
+/// * S: Number of sensors
+/// * H: Analysis history depth per sensor (see history), 0 disables the history (no memory used)
+/// * CAL_CONFIRM: Consecutive in-range windows required to declare MagnetDetected (test mode,
+///   e.g. to ignore ringing after a keypress), 0 or 1 promotes immediately
+pub struct Sensors<const S: usize, const H: usize = 0, const CAL_CONFIRM: usize = 1> {
+    config: SensorsConfig,
+    tables: [Option<&'static LookupTable>; MAX_LOOKUP_TABLES],
+    sample_count: u8,
+    temp_compensation: TempCompensation,
+    temperature: i16,
+    sensors: [SenseData<H>; S],
+    pairs: [Pairing; S],
+    scale: i16,
+    span_outliers: [bool; S],
+    remap: Option<&'static [usize; S]>,
+}
+
+impl<const S: usize, const H: usize, const CAL_CONFIRM: usize> Sensors<S, H, CAL_CONFIRM> {
+    /// Initializes full Sensor array
+    pub fn new() -> Sensors<S, H, CAL_CONFIRM> {
+        Sensors::with_config(SensorsConfig::default())
+    }
+
+    /// Initializes full Sensor array using the given configuration
+    pub fn with_config(config: SensorsConfig) -> Sensors<S, H, CAL_CONFIRM> {
+        let mut tables = [None; MAX_LOOKUP_TABLES];
+        tables[0] = Some(&DEFAULT_LOOKUP_TABLE);
+        Sensors {
+            config,
+            tables,
+            sample_count: 1,
+            temp_compensation: TempCompensation::default(),
+            temperature: 0,
+            sensors: core::array::from_fn(|_| SenseData::new()),
+            pairs: [Pairing::Single; S],
+            scale: 0,
+            span_outliers: [false; S],
+            remap: None,
+        }
+    }
+
+    /// Initializes full Sensor array with preset calibration states (status, min, max)
+    /// See restore_states.
+    pub fn with_states(
+        states: &[(CalibrationStatus, u16, u16)],
+    ) -> Result<Sensors<S, H, CAL_CONFIRM>, SensorError> {
+        let mut sensors = Sensors::new();
+        sensors.restore_states(states)?;
+        Ok(sensors)
+    }
+
+    /// Preset the calibration state (status, min, max) of the first states.len() sensors
+    /// Useful for restoring known-good calibration (or for tests) without feeding samples.
+    /// Sensors are recalibrated first, so only the status and min/max carry over.
+    /// min is clamped like SenseData::set_rest. Nothing is changed if there are more states
+    /// than sensors.
+    pub fn restore_states(
+        &mut self,
+        states: &[(CalibrationStatus, u16, u16)],
+    ) -> Result<(), SensorError> {
+        if states.len() > S {
+            return Err(SensorError::InvalidSensor(S));
+        }
+        for (sensor, (cal, min, max)) in self.sensors.iter_mut().zip(states) {
+            sensor.recalibrate();
+            sensor.cal = cal.clone();
+            sensor.stats.max = *max;
+            sensor.set_rest(*min);
+        }
+        Ok(())
+    }
+
+    /// Remap incoming ADC channels to logical sensor indices, channel n is sensor remap[n]
+    /// e.g. when the PCB routes ADC channels in layout order rather than key order.
+    /// Applied to the add functions (index is the channel), add_frame (frame position n is
+    /// channel n) and process (the SampleProducer index is the channel). Everything else uses
+    /// logical sensor indices.
+    /// Every entry must be in range and unique, otherwise the remap table isn't changed.
+    /// None restores the identity mapping (no remap overhead).
+    pub fn set_remap(&mut self, remap: Option<&'static [usize; S]>) -> Result<(), SensorError> {
+        if let Some(table) = remap {
+            let mut used = [false; S];
+            for (pos, index) in table.iter().enumerate() {
+                match used.get_mut(*index) {
+                    Some(used) if !*used => *used = true,
+                    _ => return Err(SensorError::InvalidRemap(pos)),
+                }
+            }
+        }
+        self.remap = remap;
+        Ok(())
+    }
+
+    /// Logical sensor index of an incoming ADC channel (see set_remap)
+    fn channel(&self, channel: usize) -> Result<usize, SensorError> {
+        if channel >= S {
+            return Err(SensorError::InvalidSensor(channel));
+        }
+        Ok(match self.remap {
+            Some(remap) => remap[channel],
+            None => channel,
+        })
+    }
+
+    /// Measurement of a sensor (as returned by add)
+    fn record(sensor: &SenseData<H>, ready: bool) -> Measurement<'_> {
+        if !ready {
+            return Measurement::Pending;
+        }
+        Measurement::Ready(&sensor.analysis)
+    }
+
+    /// Accumulate a reading, analyzing the averaged sample once complete
+    /// Returns true if there is a new analysis. index must be valid.
+    /// Normal mode
+    fn add_reading(&mut self, index: usize, reading: u16, samples: u8) -> bool {
+        match self.sensors[index].accumulate(reading, samples, &self.config) {
+            Some(data) => self.sample(index, data),
+            None => false,
+        }
+    }
+
+    /// Accumulate a reading, analyzing the averaged sample once complete
+    /// Returns true if there is a new analysis. index must be valid.
+    /// Test mode
+    fn add_reading_test<const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        index: usize,
+        reading: u16,
+        samples: u8,
+    ) -> Result<bool, CalibrationStatus> {
+        match self.sensors[index].accumulate(reading, samples, &self.config) {
+            Some(data) => self.sample_test::<MNOK, MXOK, NS>(index, data),
+            None => Ok(false),
+        }
+    }
+
+    /// Analyze an averaged sample, combining differential pairs
+    /// Returns true if there is a new analysis. index must be valid.
+    /// Normal mode
+    fn sample(&mut self, index: usize, data: u16) -> bool {
+        let table = self.table(self.sensors[index].table_index);
+        match self.pairs[index] {
+            Pairing::Single => self.sensors[index]
+                .sample(data, table, &self.config)
+                .is_some(),
+            Pairing::Secondary(_) => {
+                self.sensors[index].partner_sample(data, &self.config);
+                false
+            }
+            Pairing::Primary(secondary) => {
+                let secondary = &self.sensors[secondary as usize];
+                // Waiting for the first secondary sample
+                if secondary.cal != CalibrationStatus::MagnetDetected {
+                    return false;
+                }
+                let bits = self.config.filter.oversample_bits();
+                let data = differential(data, secondary.analysis.raw, bits);
+                self.sensors[index]
+                    .sample(data, table, &self.config)
+                    .is_some()
+            }
+        }
+    }
+
+    /// Analyze an averaged sample, combining differential pairs
+    /// Returns true if there is a new analysis. index must be valid.
+    /// Test mode
+    fn sample_test<const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        index: usize,
+        data: u16,
+    ) -> Result<bool, CalibrationStatus> {
+        let table = self.table(self.sensors[index].table_index);
+        match self.pairs[index] {
+            Pairing::Single => self.sensors[index]
+                .sample_test::<MNOK, MXOK, NS, CAL_CONFIRM>(data, table, &self.config)
+                .map(|analysis| analysis.is_some()),
+            Pairing::Secondary(_) => self.sensors[index]
+                .partner_sample_test::<MNOK, MXOK, NS>(data, &self.config)
+                .map(|_| false),
+            Pairing::Primary(secondary) => {
+                let secondary = &self.sensors[secondary as usize];
+                // Waiting for the first secondary sample
+                if secondary.cal == CalibrationStatus::NotReady {
+                    return Ok(false);
+                }
+                let bits = self.config.filter.oversample_bits();
+                let combined = differential(data, secondary.analysis.raw, bits);
+                let pair = Some((data, secondary.cal.clone()));
+                self.sensors[index]
+                    .sample_test_with::<MNOK, MXOK, NS, CAL_CONFIRM>(
+                        combined,
+                        pair,
+                        table,
+                        &self.config,
+                    )
+                    .map(|analysis| analysis.is_some())
+            }
+        }
+    }
+
+    /// Register two sensors (e.g. top and bottom of travel) as a differential pair
+    /// The primary sensor is analyzed using the difference of the averaged readings of both
+    /// sensors (half the difference, centered at the ADC midpoint), which rejects offsets common
+    /// to both (ambient field, temperature). The secondary sensor isn't analyzed itself.
+    ///
+    /// In test mode each sensor is checked against the calibration thresholds, either sensor
+    /// failing fails the pair (auto_threshold isn't used for pairs). The combined reading uses
+    /// the most recent secondary sample, so add the secondary's readings before the primary's.
+    ///
+    /// Configure pairs once at init, both sensors are recalibrated. Unpaired sensors are not
+    /// affected.
+    pub fn set_differential(
+        &mut self,
+        primary: usize,
+        secondary: usize,
+    ) -> Result<(), SensorError> {
+        for index in [primary, secondary] {
+            if index >= S {
+                return Err(SensorError::InvalidSensor(index));
+            }
+        }
+        if primary == secondary
+            || self.pairs[primary] != Pairing::Single
+            || self.pairs[secondary] != Pairing::Single
+        {
+            return Err(SensorError::InvalidPair(primary, secondary));
+        }
+        self.pairs[primary] = Pairing::Primary(secondary as u16);
+        self.pairs[secondary] = Pairing::Secondary(primary as u16);
+        self.sensors[primary].recalibrate();
+        self.sensors[secondary].recalibrate();
+        Ok(())
+    }
+
+    /// Differential partner of a sensor and whether it's the primary sensor of the pair
+    /// See set_differential
+    pub fn differential_partner(&self, index: usize) -> Option<(usize, bool)> {
+        match self.pairs.get(index)? {
+            Pairing::Single => None,
+            Pairing::Primary(secondary) => Some((*secondary as usize, true)),
+            Pairing::Secondary(primary) => Some((*primary as usize, false)),
+        }
+    }
+
+    /// Recent analyses of a specific sensor, oldest to newest
+    /// Holds up to H entries (always empty if H is 0).
+    pub fn history(
+        &self,
+        index: usize,
+    ) -> Result<impl Iterator<Item = &SenseAnalysis>, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => Ok(sensor.history()),
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Current configuration
+    pub fn config(&self) -> &SensorsConfig {
+        &self.config
+    }
+
+    /// Set the runtime sample count (used by add_runtime and add_test_runtime)
+    /// Must be a power of two (1, 2, 4, 8, 16, 32, 64 or 128).
+    /// Any partially accumulated samples are flushed so the new count takes effect on the next
+    /// accumulation cycle.
+    pub fn set_sample_count(&mut self, samples: u8) -> Result<(), SensorError> {
+        if !samples.is_power_of_two() {
+            return Err(SensorError::InvalidSampleCount(samples));
+        }
+        if samples != self.sample_count {
+            self.sample_count = samples;
+            for sensor in self.sensors.iter_mut() {
+                sensor.data.reset();
+            }
+        }
+        Ok(())
+    }
+
+    /// Current runtime sample count
+    pub fn sample_count(&self) -> u8 {
+        self.sample_count
+    }
+
+    /// Register a lookup table (e.g. for a different switch type)
+    /// The table must cover every ADC reading (ADC_MAX + 1 entries) so lookups never need to be
+    /// bounds checked. Sensors already using the index switch to the new table and recalibrate.
+    pub fn register_lookup_table(
+        &mut self,
+        index: u8,
+        table: &'static LookupTable,
+    ) -> Result<(), SensorError> {
+        if table.table.len() <= ADC_MAX as usize || table.um_den == 0 {
+            return Err(SensorError::InvalidLookupTable(index));
+        }
+        match self.tables.get_mut(index as usize) {
+            Some(entry) => *entry = Some(table),
+            None => return Err(SensorError::InvalidLookupTable(index)),
+        }
+        for sensor in self.sensors.iter_mut() {
+            if sensor.table_index == index {
+                sensor.recalibrate();
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the lookup table used by a specific sensor
+    /// The table must already be registered (see register_lookup_table).
+    /// Changing the table resets the calibration of the sensor.
+    pub fn set_lookup_table(&mut self, index: usize, table_index: u8) -> Result<(), SensorError> {
+        if !matches!(self.tables.get(table_index as usize), Some(Some(_))) {
+            return Err(SensorError::InvalidLookupTable(table_index));
+        }
+        match self.sensors.get_mut(index) {
+            Some(sensor) => {
+                if sensor.table_index != table_index {
+                    sensor.table_index = table_index;
+                    sensor.recalibrate();
+                }
+                Ok(())
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Registered lookup table with the given table index
+    /// Sensors only ever select registered tables (set_lookup_table), tables can't be removed.
+    fn table(&self, table_index: u8) -> &'static LookupTable {
+        match self.tables[table_index as usize] {
+            Some(table) => table,
+            None => &DEFAULT_LOOKUP_TABLE,
+        }
+    }
+
+    /// Lookup table used by a specific sensor (see SenseData::table_index)
+    pub fn lookup_table(&self, index: usize) -> Result<&'static LookupTable, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => Ok(self.table(sensor.table_index)),
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Observed travel span of a specific sensor (see SenseData::travel_span)
+    pub fn travel_span(&self, index: usize) -> Result<i16, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => Ok(sensor.travel_span(self.table(sensor.table_index))),
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Bypass the lookup table for every sensor, distance becomes raw - min (in ADC counts)
+    /// Useful for characterizing new sensors/magnets before a lookup table exists.
+    /// Everything else (filtering, calibration, temperature compensation) is unchanged.
+    /// Check SenseData::passthrough before interpreting distances, the units differ.
+    /// Sensors are recalibrated when the mode changes.
+    pub fn set_passthrough(&mut self, passthrough: bool) {
+        for sensor in self.sensors.iter_mut() {
+            if sensor.passthrough != passthrough {
+                sensor.passthrough = passthrough;
+                sensor.recalibrate();
+            }
+        }
+    }
+
+    /// Set the temperature compensation applied to the distance of every sensor
+    /// slope is in 1/256 distance units per temperature unit (see TempCompensation).
+    /// The sensor reading drifts with temperature, move the actuation point along with it.
+    /// A slope of 0 disables compensation (default).
+    pub fn set_temp_compensation(&mut self, slope: i16, reference: i16) {
+        self.temp_compensation = TempCompensation { slope, reference };
+        self.update_temp_offset();
+    }
+
+    /// Current temperature compensation
+    pub fn temp_compensation(&self) -> TempCompensation {
+        self.temp_compensation
+    }
+
+    /// Update the current temperature (from an onboard temperature sensor)
+    /// Takes effect on the next analysis.
+    pub fn set_temperature(&mut self, temperature: i16) {
+        self.temperature = temperature;
+        self.update_temp_offset();
+    }
+
+    fn update_temp_offset(&mut self) {
+        let offset = self.temp_compensation.offset(self.temperature);
+        for sensor in self.sensors.iter_mut() {
+            sensor.temp_offset = offset;
+        }
+    }
+
+    /// Add sense data for a specific sensor
+    pub fn add<const SC: usize>(
+        &mut self,
+        index: usize,
+        reading: u16,
+    ) -> Result<Measurement<'_>, SensorError> {
+        trace!("Index: {}  Reading: {}", index, reading);
+        let index = self.channel(index)?;
+        let ready = self.add_reading(index, reading, SC as u8);
+        Ok(Self::record(&self.sensors[index], ready))
+    }
+
+    /// Add sense data for a specific sensor
+    /// Test mode
+    /// Uncalibrated sensors return Measurement::NotCalibrated
+    pub fn add_test<const SC: usize, const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        index: usize,
+        reading: u16,
+    ) -> Result<Measurement<'_>, SensorError> {
+        trace!("Index: {}  Reading: {}", index, reading);
+        let index = self.channel(index)?;
+        match self.add_reading_test::<MNOK, MXOK, NS>(index, reading, SC as u8) {
+            Ok(ready) => Ok(Self::record(&self.sensors[index], ready)),
+            Err(cal) => Ok(Measurement::NotCalibrated(cal)),
+        }
+    }
+
+    /// Mutable sense data for a specific sensor
+    /// Unlike get_data, the sensor does not need to be calibrated.
+    ///
+    /// Safe mid-accumulation (only used once the next averaged sample is ready):
+    /// * SenseData::reset_stats
+    /// * SenseData::set_rest
+    /// * SenseData::decay_extrema
+    ///
+    /// Discards the partial accumulation:
+    /// * SenseData::discard_partial
+    /// * SenseData::recalibrate
+    ///
+    /// Prefer these methods over writing the public fields directly; e.g. stats.min is used to
+    /// index the lookup table.
+    pub fn get_data_mut(&mut self, index: usize) -> Result<&mut SenseData<H>, SensorError> {
+        match self.sensors.get_mut(index) {
+            Some(sensor) => Ok(sensor),
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Calibration quality of a sensor, relative to SensorsConfig::default_travel
+    /// See SenseData::calibration_quality.
+    pub fn calibration_quality<const MNOK: usize, const NS: usize>(
+        &self,
+        index: usize,
+    ) -> Result<CalibrationQuality, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => Ok(sensor.calibration_quality::<MNOK, NS>(
+                self.table(sensor.table_index),
+                self.config.default_travel,
+            )),
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Reset the measurement stats of a sensor, keeping calibration (see SenseData::reset_stats)
+    pub fn reset_stats(&mut self, index: usize) -> Result<(), SensorError> {
+        self.get_data_mut(index)?.reset_stats();
+        Ok(())
+    }
+
+    /// Reset the measurement stats of all sensors, keeping calibration
+    pub fn reset_all_stats(&mut self) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.reset_stats();
+        }
+    }
+
+    pub fn get_data(&self, index: usize) -> Result<&SenseData<H>, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => {
+                if sensor.cal == CalibrationStatus::NotReady {
+                    Err(SensorError::CalibrationError(sensor.cal.clone()))
+                } else {
+                    Ok(sensor)
+                }
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Add sense data for a specific sensor
+    /// Uses the runtime sample count (see set_sample_count)
+    pub fn add_runtime(
+        &mut self,
+        index: usize,
+        reading: u16,
+    ) -> Result<Measurement<'_>, SensorError> {
+        trace!("Index: {}  Reading: {}", index, reading);
+        let index = self.channel(index)?;
+        let ready = self.add_reading(index, reading, self.sample_count);
+        Ok(Self::record(&self.sensors[index], ready))
+    }
+
+    /// Add a full frame of readings (e.g. from a DMA buffer), one reading per sensor
+    /// Uses the runtime sample count (see set_sample_count)
+    /// Frame position n is channel n (see set_remap), the frame must contain exactly one reading
+    /// per sensor.
+    pub fn add_frame(&mut self, frame: &[u16]) -> Result<FrameResult, SensorError> {
+        if frame.len() != S {
+            return Err(SensorError::InvalidFrameLength(frame.len()));
+        }
+
+        let mut result = FrameResult::default();
+        for (channel, reading) in frame.iter().enumerate() {
+            let index = self.remap.map_or(channel, |remap| remap[channel]);
+            if self.add_reading(index, *reading, self.sample_count) {
+                Self::record(&self.sensors[index], true);
+                result.analyses += 1;
+            }
+        }
+        trace!("Frame: {:?}", result);
+        Ok(result)
+    }
+
+    /// Analyze the samples completed by a SampleProducer (e.g. from an ISR)
+    /// Only sensors with a new averaged sample are analyzed, returns the number of new analyses.
+    /// Readings are accumulated by the producer, so flatline detection and the histogram are
+    /// not updated.
+    /// Normal mode
+    pub fn process(&mut self, handoff: &SampleHandoff<S>) -> usize {
+        let bits = self.config.filter.oversample_bits();
+        let mut analyses = 0;
+        for channel in 0..S {
+            if let Some(data) = handoff.take(channel) {
+                let index = self.remap.map_or(channel, |remap| remap[channel]);
+                let sensor = &mut self.sensors[index];
+                sensor.transition_from = None;
+                sensor.hot_swap_event = None;
+                let data = sensor.mirror_sample(data, bits);
+                if self.sample(index, data) {
+                    Self::record(&self.sensors[index], true);
+                    analyses += 1;
+                }
+            }
+        }
+        analyses
+    }
+
+    /// Analyze the samples completed by a SampleProducer (e.g. from an ISR)
+    /// See process, the calibration status of each sensor is available through get_data.
+    /// Sensors that are not calibrated request the producer to discard its accumulation.
+    /// Test mode
+    pub fn process_test<const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        handoff: &SampleHandoff<S>,
+    ) -> usize {
+        let bits = self.config.filter.oversample_bits();
+        let mut analyses = 0;
+        for channel in 0..S {
+            if let Some(data) = handoff.take(channel) {
+                let index = self.remap.map_or(channel, |remap| remap[channel]);
+                let sensor = &mut self.sensors[index];
+                sensor.transition_from = None;
+                sensor.hot_swap_event = None;
+                let data = sensor.mirror_sample(data, bits);
+                match self.sample_test::<MNOK, MXOK, NS>(index, data) {
+                    Ok(true) => {
+                        Self::record(&self.sensors[index], true);
+                        analyses += 1;
+                    }
+                    Ok(false) => {}
+                    Err(_) => handoff.request_reset(channel),
+                }
+            }
+        }
+        analyses
+    }
+
+    /// Set whether the sensor reading is inverted (decreases as the key is pressed)
+    /// Inverted readings are mirrored around the ADC midpoint before the lookup and calibration
+    /// checks, so the same calibration thresholds apply in the mirrored domain.
+    /// Changing the flag resets the calibration of the sensor.
+    pub fn set_inverted(&mut self, index: usize, inverted: bool) -> Result<(), SensorError> {
+        match self.sensors.get_mut(index) {
+            Some(sensor) => {
+                if sensor.inverted != inverted {
+                    sensor.inverted = inverted;
+                    sensor.recalibrate();
+                }
+                Ok(())
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Set the inverted flag for all sensors using a bitmask
+    /// Bit n % 8 of mask[n / 8] is sensor n. Sensors beyond the end of the mask are not inverted.
+    pub fn set_inverted_mask(&mut self, mask: &[u8]) {
+        for index in 0..S {
+            let inverted = mask
+                .get(index / 8)
+                .is_some_and(|byte| byte & (1 << (index % 8)) != 0);
+            // Index is always valid
+            self.set_inverted(index, inverted).ok();
+        }
+    }
+
+    /// Idle flags for all sensors as a bitmask (see SenseData::idle)
+    /// Bit n % 8 of mask[n / 8] is sensor n. Bits for sensors beyond S are cleared, sensors
+    /// beyond the end of the mask are skipped.
+    pub fn idle_mask(&self, mask: &mut [u8]) {
+        mask.fill(0);
+        for (index, sensor) in self.sensors.iter().enumerate() {
+            if let Some(byte) = mask.get_mut(index / 8) {
+                if sensor.idle() {
+                    *byte |= 1 << (index % 8);
+                }
+            }
+        }
+    }
+
+    /// Press counters of all sensors (see SenseData::presses)
+    /// counts[n] is sensor n. Entries beyond S are cleared, sensors beyond the end of counts are
+    /// skipped.
+    #[cfg(feature = "press-count")]
+    pub fn press_counts(&self, counts: &mut [u32]) {
+        counts.fill(0);
+        for (count, sensor) in counts.iter_mut().zip(self.sensors.iter()) {
+            *count = sensor.presses();
+        }
+    }
+
+    /// Clear the press counters of all sensors
+    #[cfg(feature = "press-count")]
+    pub fn reset_all_presses(&mut self) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.reset_presses();
+        }
+    }
+
+    /// Bitmask of calibrated sensors that haven't been pressed through TRAVEL yet
+    /// (see SenseData::travel_covered), e.g. for a setup wizard to prompt for specific keys.
+    /// Same layout as idle_mask. Uncalibrated sensors are not set (they may not have a key).
+    pub fn travel_pending_mask<const TRAVEL: usize>(&self, mask: &mut [u8]) {
+        mask.fill(0);
+        for (index, sensor) in self.sensors.iter().enumerate() {
+            if let Some(byte) = mask.get_mut(index / 8) {
+                if sensor.cal == CalibrationStatus::MagnetDetected
+                    && !sensor.travel_covered::<TRAVEL>(self.table(sensor.table_index))
+                {
+                    *byte |= 1 << (index % 8);
                 }
             }
+        }
+    }
 
-            // Calculate new analysis (requires previous results + min/max)
-            self.analysis = SenseAnalysis::new(data, self);
-            Ok(Some(&self.analysis))
+    /// Compute the keyboard-level travel scale, the median travel span (SenseData::travel_span)
+    /// of the calibrated sensors that have been pressed. Each key should be pressed fully first.
+    /// Sensors whose span deviates from the median by more than Normalization::tolerance percent
+    /// are flagged (see span_outlier_mask) and are not rescaled by normalized_distance.
+    /// Returns the scale, None if normalization is disabled or no sensor has been pressed.
+    pub fn normalize(&mut self) -> Option<i16> {
+        self.scale = 0;
+        self.span_outliers = [false; S];
+        let tolerance = self.config.normalization?.tolerance as i32;
+
+        let mut spans = [0; S];
+        let mut count = 0;
+        for sensor in self.sensors.iter() {
+            let span = sensor.travel_span(self.table(sensor.table_index));
+            if span > 0 {
+                spans[count] = span;
+                count += 1;
+            }
+        }
+        if count == 0 {
+            return None;
+        }
+        let spans = &mut spans[..count];
+        spans.sort_unstable();
+        let median = if count % 2 == 0 {
+            ((spans[count / 2 - 1] as i32 + spans[count / 2] as i32) / 2) as i16
         } else {
-            Ok(None)
+            spans[count / 2]
+        };
+
+        for index in 0..S {
+            let sensor = &self.sensors[index];
+            let span = sensor.travel_span(self.table(sensor.table_index));
+            self.span_outliers[index] =
+                span > 0 && (span as i32 - median as i32).abs() * 100 > median as i32 * tolerance;
         }
+        self.scale = median;
+        Some(median)
     }
 
-    /// Update calibration state
-    /// Calibration is different depending on whether or not we've already been successfully
-    /// calibrated. Gain and offset are set differently depending on whether the sensor has been
-    /// calibrated. Uncalibrated sensors run at a lower gain to gather more details around voltage
-    /// limits. Wherease calibrated sensors run at higher gain (and likely an offset) to maximize
-    /// the voltage range of the desired sensor range.
-    /// NOTE: This implementation (currently) only works for a single magnet pole of a bipolar sensor.
-    fn check_calibration<const MNOK: usize, const MXOK: usize, const NS: usize>(
-        &self,
-        data: u16,
-    ) -> CalibrationStatus {
-        // Value too high, likely a bad sensor or bad soldering on the pcb
-        // Magnet may also be too strong.
-        if data > MXOK as u16 {
-            return CalibrationStatus::SensorBroken;
+    /// Distance of a sensor re-expressed on the keyboard-level scale (see normalize)
+    /// Identical physical travel gives the same normalized distance on every key.
+    /// The distance is returned unscaled for outliers, sensors that haven't been pressed or if
+    /// normalize hasn't found a scale.
+    /// Distances within the bottom deadzone are full travel (see SensorsConfig::bottom_deadzone).
+    pub fn normalized_distance(&self, index: usize) -> Result<i16, SensorError> {
+        let data = self.get_data(index)?;
+        let table = self.table(data.table_index);
+        let distance = data.distance_bottom_clamped(table, self.config.bottom_deadzone);
+        let span = data.travel_span(table);
+        if self.scale == 0 || span <= 0 || self.span_outliers[index] {
+            return Ok(distance);
         }
-        // No sensor detected
-        if data < NS as u16 {
-            return CalibrationStatus::SensorMissing;
+        Ok(saturate(distance as i32 * self.scale as i32 / span as i32))
+    }
+
+    /// Bitmask of sensors flagged as travel span outliers by the last normalize
+    /// Same layout as idle_mask.
+    pub fn span_outlier_mask(&self, mask: &mut [u8]) {
+        mask.fill(0);
+        for (index, outlier) in self.span_outliers.iter().enumerate() {
+            if let Some(byte) = mask.get_mut(index / 8) {
+                if *outlier {
+                    *byte |= 1 << (index % 8);
+                }
+            }
         }
-        // Wrong pole (or magnet may be too weak)
-        if data < MNOK as u16 {
-            return CalibrationStatus::MagnetWrongPoleOrMissing;
+    }
+
+    /// Start bottom-out (full travel) calibration
+    /// The user should then press each key fully, see bottom_out_progress.
+    /// Any previous bottom-out calibration is cleared.
+    pub fn start_bottom_out_calibration(&mut self) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.bottom_out_mode = true;
+            sensor.stats.bottom_out = 0;
         }
+    }
 
-        CalibrationStatus::MagnetDetected
+    /// Finish bottom-out calibration, latched values are kept
+    /// Sensors that were not confirmed fall back to SensorsConfig::default_travel.
+    pub fn finish_bottom_out_calibration(&mut self) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.bottom_out_mode = false;
+        }
     }
-}
 
-impl Default for SenseData {
-    fn default() -> Self {
-        SenseData::new()
+    /// Bottom-out calibration is in progress
+    pub fn bottom_out_calibrating(&self) -> bool {
+        self.sensors.iter().any(|sensor| sensor.bottom_out_mode)
     }
-}
 
-// ----- Hall Effect Interface ------
+    /// Number of sensors with a confirmed bottom-out calibration
+    /// Use SenseData::bottom_out_confirmed for per-sensor completion.
+    pub fn bottom_out_progress(&self) -> usize {
+        self.sensors
+            .iter()
+            .filter(|sensor| sensor.bottom_out_confirmed())
+            .count()
+    }
 
-pub struct Sensors<const S: usize> {
-    sensors: Vec<SenseData, S>,
-}
+    /// Full travel distance of a specific sensor (see SenseData::full_travel)
+    pub fn full_travel(&self, index: usize) -> Result<i16, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => {
+                Ok(sensor.full_travel(self.table(sensor.table_index), self.config.default_travel))
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
 
-impl<const S: usize> Sensors<S> {
-    /// Initializes full Sensor array
-    /// Only fails if static allocation fails (very unlikely)
-    pub fn new() -> Result<Sensors<S>, SensorError> {
-        let mut sensors = Vec::new();
-        if sensors.resize_default(S).is_err() {
-            Err(SensorError::FailedToResize(S))
-        } else {
-            Ok(Sensors { sensors })
+    /// Set the actuation point of a specific sensor
+    /// Overrides the generic default actuation point (e.g. to compensate for mechanical
+    /// tolerances of a row), 0 restores the default.
+    pub fn set_actuation(&mut self, index: usize, point: u16) -> Result<(), SensorError> {
+        match self.sensors.get_mut(index) {
+            Some(sensor) => {
+                sensor.actuation = point;
+                Ok(())
+            }
+            None => Err(SensorError::InvalidSensor(index)),
         }
     }
 
-    /// Add sense data for a specific sensor
-    pub fn add<const SC: usize>(
-        &mut self,
+    /// Comparator thresholds of a specific sensor (see SenseData::comparator_thresholds)
+    pub fn comparator_thresholds(
+        &self,
         index: usize,
-        reading: u16,
-    ) -> Result<Option<&SenseAnalysis>, SensorError> {
-        trace!("Index: {}  Reading: {}", index, reading);
-        if index < self.sensors.len() {
-            self.sensors[index].add::<SC>(reading)
-        } else {
-            Err(SensorError::InvalidSensor(index))
+        actuation: i16,
+        hysteresis: i16,
+    ) -> Result<ComparatorThresholds, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => {
+                sensor.comparator_thresholds(self.table(sensor.table_index), actuation, hysteresis)
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Relax min/max of all sensors (see SenseData::decay_extrema)
+    pub fn decay_extrema<const RATE: usize>(&mut self) {
+        for sensor in self.sensors.iter_mut() {
+            sensor.decay_extrema::<RATE>();
+        }
+    }
+
+    /// Discard a partial accumulation for a specific sensor (see SenseData::discard_partial)
+    pub fn discard_partial(&mut self, index: usize) -> Result<(), SensorError> {
+        match self.sensors.get_mut(index) {
+            Some(sensor) => {
+                sensor.discard_partial();
+                Ok(())
+            }
+            None => Err(SensorError::InvalidSensor(index)),
         }
     }
 
     /// Add sense data for a specific sensor
+    /// Uses the runtime sample count (see set_sample_count)
     /// Test mode
-    pub fn add_test<const SC: usize, const MNOK: usize, const MXOK: usize, const NS: usize>(
+    /// Uncalibrated sensors return Measurement::NotCalibrated
+    pub fn add_test_runtime<const MNOK: usize, const MXOK: usize, const NS: usize>(
         &mut self,
         index: usize,
         reading: u16,
-    ) -> Result<Option<&SenseAnalysis>, SensorError> {
+    ) -> Result<Measurement<'_>, SensorError> {
         trace!("Index: {}  Reading: {}", index, reading);
-        if index < self.sensors.len() {
-            self.sensors[index].add_test::<SC, MNOK, MXOK, NS>(reading)
-        } else {
-            Err(SensorError::InvalidSensor(index))
+        let index = self.channel(index)?;
+        match self.add_reading_test::<MNOK, MXOK, NS>(index, reading, self.sample_count) {
+            Ok(ready) => Ok(Self::record(&self.sensors[index], ready)),
+            Err(cal) => Ok(Measurement::NotCalibrated(cal)),
         }
     }
 
-    pub fn get_data(&self, index: usize) -> Result<&SenseData, SensorError> {
-        if index < self.sensors.len() {
-            if self.sensors[index].cal == CalibrationStatus::NotReady {
-                Err(SensorError::CalibrationError(self.sensors[index].clone()))
-            } else {
-                Ok(&self.sensors[index])
+    /// Retrieve the most recent analysis computed while the sensor was calibrated
+    /// Useful to hold the previous position during a brief recalibration glitch instead of
+    /// dropping the reading entirely. See SenseData::last_good for staleness details.
+    pub fn get_last_good(&self, index: usize) -> Result<&SenseAnalysis, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => match sensor.last_good() {
+                Some(analysis) => Ok(analysis),
+                None => Err(SensorError::CalibrationError(sensor.cal.clone())),
+            },
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Combined analysis of two redundant sensors measuring the same key
+    /// Only sensors that are calibrated (MagnetDetected) are used: both are averaged, a single
+    /// one is used as-is and a null analysis is returned if neither is calibrated. This keeps
+    /// the key working when one of the sensors fails.
+    pub fn redundant(&self, a: usize, b: usize) -> Result<SenseAnalysis, SensorError> {
+        let calibrated =
+            |index: usize| -> Result<Option<&SenseAnalysis>, SensorError> {
+                match self.sensors.get(index) {
+                    Some(sensor) => Ok((sensor.cal == CalibrationStatus::MagnetDetected)
+                        .then_some(&sensor.analysis)),
+                    None => Err(SensorError::InvalidSensor(index)),
+                }
+            };
+        Ok(match (calibrated(a)?, calibrated(b)?) {
+            (Some(a), Some(b)) => a.average(b),
+            (Some(analysis), None) | (None, Some(analysis)) => analysis.clone(),
+            (None, None) => SenseAnalysis::null(),
+        })
+    }
+
+    /// Retrieve sense data for a specific sensor, only if calibrated (MagnetDetected)
+    fn get_calibrated(&self, index: usize) -> Result<&SenseData<H>, SensorError> {
+        match self.sensors.get(index) {
+            Some(sensor) => {
+                if sensor.cal == CalibrationStatus::MagnetDetected {
+                    Ok(sensor)
+                } else {
+                    Err(SensorError::CalibrationError(sensor.cal.clone()))
+                }
             }
-        } else {
-            Err(SensorError::InvalidSensor(index))
+            None => Err(SensorError::InvalidSensor(index)),
         }
     }
 
     /// Max number of sensors
-    pub fn len(&self) -> usize {
+    /// Valid sensor indices are 0..len(), useful for board-agnostic scan loops that don't know S.
+    pub const fn len(&self) -> usize {
         S
     }
 
-    pub fn is_empty(&self) -> bool {
+    /// No sensors
+    pub const fn is_empty(&self) -> bool {
         S == 0
     }
+
+    /// Number of calibrated (MagnetDetected) sensors
+    /// O(n), walks every sensor.
+    pub fn calibrated_count(&self) -> usize {
+        self.sensors
+            .iter()
+            .filter(|sensor| sensor.cal == CalibrationStatus::MagnetDetected)
+            .count()
+    }
+
+    /// All sensors are calibrated (MagnetDetected)
+    /// O(n), stops at the first sensor that isn't calibrated.
+    pub fn all_calibrated(&self) -> bool {
+        self.sensors
+            .iter()
+            .all(|sensor| sensor.cal == CalibrationStatus::MagnetDetected)
+    }
+
+    /// Number of sensors in each calibration status
+    /// Indexed by the CalibrationStatus discriminant (e.g. CalibrationStatus::MagnetDetected as usize).
+    /// Computed on demand by walking every sensor.
+    pub fn status_summary(&self) -> [usize; CALIBRATION_STATUS_COUNT] {
+        let mut summary = [0; CALIBRATION_STATUS_COUNT];
+        for sensor in self.sensors.iter() {
+            summary[sensor.cal.clone() as usize] += 1;
+        }
+        summary
+    }
+
+    /// Lowest sensor index that is in a fault state (see CalibrationStatus::is_fault)
+    pub fn first_fault(&self) -> Option<usize> {
+        self.sensors.iter().position(|sensor| sensor.cal.is_fault())
+    }
+}
+
+impl<const S: usize, const H: usize, const CAL_CONFIRM: usize> Default
+    for Sensors<S, H, CAL_CONFIRM>
+{
+    fn default() -> Self {
+        Sensors::new()
+    }
+}
+
+// ----- ISR Handoff -----
+
+/// Lock-free handoff of averaged samples from a SampleProducer to Sensors::process
+/// Lets an ISR accumulate ADC readings while the main loop runs the (more expensive) analysis,
+/// without a critical section around Sensors.
+///
+/// Memory ordering assumptions:
+/// * There is a single producer (SampleProducer::add) and a single consumer (Sensors::process
+///   or process_test) per handoff. Every atomic has a single writer, so only atomic load and
+///   store are used (no read-modify-write), which is available on every Cortex-M target
+///   (including thumbv6m).
+/// * Each sample is stored as a single 32-bit value (16-bit sequence + 16-bit averaged value)
+///   with Release ordering and loaded with Acquire ordering, so it can never be torn.
+/// * Samples are latest-wins: if the producer completes more than one window before the
+///   consumer runs, the older windows are dropped.
+/// * Reset requests (consumer -> producer, after a calibration fault) are a counter written by
+///   the consumer and compared by the producer on its next reading.
+pub struct SampleHandoff<const S: usize> {
+    /// Sequence (upper 16 bits) and averaged value (lower 16 bits), written by the producer
+    samples: [AtomicU32; S],
+    /// Sequence of the most recently processed sample, written by the consumer
+    consumed: [AtomicU16; S],
+    /// Accumulation reset request counter, written by the consumer
+    reset_request: [AtomicU8; S],
+}
+
+impl<const S: usize> SampleHandoff<S> {
+    /// Empty handoff, const so it can be placed in a static
+    pub const fn new() -> Self {
+        SampleHandoff {
+            samples: [const { AtomicU32::new(0) }; S],
+            consumed: [const { AtomicU16::new(0) }; S],
+            reset_request: [const { AtomicU8::new(0) }; S],
+        }
+    }
+
+    /// Take the next unprocessed averaged sample (consumer)
+    fn take(&self, index: usize) -> Option<u16> {
+        let packed = self.samples[index].load(Ordering::Acquire);
+        let seq = (packed >> 16) as u16;
+        if seq == self.consumed[index].load(Ordering::Relaxed) {
+            return None;
+        }
+        self.consumed[index].store(seq, Ordering::Relaxed);
+        Some(packed as u16)
+    }
+
+    /// Ask the producer to discard the accumulation of a sensor (consumer)
+    fn request_reset(&self, index: usize) {
+        let request = self.reset_request[index].load(Ordering::Relaxed);
+        self.reset_request[index].store(request.wrapping_add(1), Ordering::Release);
+    }
+}
+
+impl<const S: usize> Default for SampleHandoff<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Accumulates raw readings into averaged samples (e.g. from an ADC ISR)
+/// Owned by the producer side, see SampleHandoff. Uses the SensorsConfig filter, median and
+/// ADC resolution; inversion is applied by the consumer.
+pub struct SampleProducer<const S: usize> {
+    config: SensorsConfig,
+    samples: u8,
+    data: [RawData; S],
+    seq: [u16; S],
+    reset_ack: [u8; S],
+}
+
+impl<const S: usize> SampleProducer<S> {
+    /// Producer averaging samples readings per sensor
+    /// Must be a power of two (see Sensors::set_sample_count).
+    pub fn new(samples: u8, config: &SensorsConfig) -> Result<Self, SensorError> {
+        if !samples.is_power_of_two() {
+            return Err(SensorError::InvalidSampleCount(samples));
+        }
+        Ok(SampleProducer {
+            config: config.clone(),
+            samples,
+            data: core::array::from_fn(|_| RawData::new()),
+            seq: [0; S],
+            reset_ack: [0; S],
+        })
+    }
+
+    /// Add a raw reading for a specific sensor
+    /// Returns true if an averaged sample was completed and handed off.
+    pub fn add(
+        &mut self,
+        index: usize,
+        reading: u16,
+        handoff: &SampleHandoff<S>,
+    ) -> Result<bool, SensorError> {
+        let data = match self.data.get_mut(index) {
+            Some(data) => data,
+            None => return Err(SensorError::InvalidSensor(index)),
+        };
+
+        // Discard the accumulation if the consumer lost calibration
+        let request = handoff.reset_request[index].load(Ordering::Acquire);
+        if request != self.reset_ack[index] {
+            data.reset();
+            self.reset_ack[index] = request;
+        }
+
+        let reading = scale_reading(reading, self.config.adc_bits);
+        match data.add(reading, self.samples, &self.config) {
+            Some(value) => {
+                self.seq[index] = self.seq[index].wrapping_add(1);
+                let packed = (self.seq[index] as u32) << 16 | value as u32;
+                handoff.samples[index].store(packed, Ordering::Release);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+// ----- Sensor Fusion -----
+
+/// Scale of Fusion::Ratio (a ratio of 1.0)
+pub const RATIO_SCALE: i32 = 1024;
+
+/// Method used to combine the distances of two sensors
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Fusion {
+    /// a + b
+    Sum,
+    /// a - b (cancels common-mode drift, e.g. temperature)
+    Difference,
+    /// a * RATIO_SCALE / (a + b), 0 if a + b is not positive
+    Ratio,
+}
+
+/// Two sensors that together report a single position (e.g. rotary control or analog stick axis)
+/// Uses the linearized distance of each sensor.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct SensorPair {
+    pub a: usize,
+    pub b: usize,
+    pub fusion: Fusion,
+}
+
+impl SensorPair {
+    pub fn new(a: usize, b: usize, fusion: Fusion) -> SensorPair {
+        SensorPair { a, b, fusion }
+    }
+
+    /// Combine two distances
+    pub fn fuse(&self, a: i16, b: i16) -> i16 {
+        let (a, b) = (a as i32, b as i32);
+        match self.fusion {
+            Fusion::Sum => saturate(a + b),
+            Fusion::Difference => saturate(a - b),
+            Fusion::Ratio => {
+                if a + b > 0 {
+                    saturate(a * RATIO_SCALE / (a + b))
+                } else {
+                    0
+                }
+            }
+        }
+    }
+
+    /// Combined distance of the pair
+    /// Both sensors must be calibrated (MagnetDetected).
+    pub fn distance<const S: usize, const H: usize, const CAL_CONFIRM: usize>(
+        &self,
+        sensors: &Sensors<S, H, CAL_CONFIRM>,
+    ) -> Result<i16, SensorError> {
+        let a = sensors.get_calibrated(self.a)?;
+        let b = sensors.get_calibrated(self.b)?;
+        Ok(self.fuse(a.analysis.distance, b.analysis.distance))
+    }
+}
+
+// ----- Tap Detection -----
+
+/// Light tap event (see TapDetector)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct TapEvent {
+    /// Deepest distance reached during the tap
+    pub depth: i16,
+    /// Analysis samples from the jerk until the reversal
+    pub samples: u16,
+}
+
+/// Tap detection phase
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+enum TapPhase {
+    Idle,
+    Pressing { depth: i16, samples: u16 },
+    Released,
+}
+
+/// Detects sharp taps that reverse before reaching full actuation travel
+/// A tap starts with a downward analysis whose jerk exceeds JT (see SenseAnalysis::jerk_exceeds)
+/// and ends on the first upward (negative velocity) analysis. It is only reported if the depth
+/// reached at least DEPTH within DURATION analysis samples. Slow presses never exceed the
+/// jerk threshold, and presses held past DURATION are ignored until the key is released.
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct TapDetector {
+    phase: TapPhase,
+}
+
+impl TapDetector {
+    pub fn new() -> TapDetector {
+        TapDetector {
+            phase: TapPhase::Idle,
+        }
+    }
+
+    /// Add the next analysis of the key
+    pub fn add<const JT: usize, const DEPTH: usize, const DURATION: usize>(
+        &mut self,
+        analysis: &SenseAnalysis,
+    ) -> Option<TapEvent> {
+        match self.phase {
+            TapPhase::Idle => {
+                if analysis.velocity > 0 && analysis.jerk_exceeds::<JT>() {
+                    self.phase = TapPhase::Pressing {
+                        depth: analysis.distance,
+                        samples: 0,
+                    };
+                }
+                None
+            }
+            TapPhase::Pressing { depth, samples } => {
+                let depth = depth.max(analysis.distance);
+                let samples = samples.saturating_add(1);
+                if analysis.velocity < 0 {
+                    self.phase = TapPhase::Idle;
+                    if depth as i32 >= DEPTH as i32 && samples as usize <= DURATION {
+                        return Some(TapEvent { depth, samples });
+                    }
+                } else if samples as usize > DURATION {
+                    // Held too long, wait for release
+                    self.phase = TapPhase::Released;
+                } else {
+                    self.phase = TapPhase::Pressing { depth, samples };
+                }
+                None
+            }
+            TapPhase::Released => {
+                if analysis.distance < RELEASE_DISTANCE {
+                    self.phase = TapPhase::Idle;
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Default for TapDetector {
+    fn default() -> Self {
+        TapDetector::new()
+    }
+}
+
+// ----- SOCD -----
+
+/// Simultaneous Opposing Cardinal Directions resolution rule
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum SocdRule {
+    Neutral = 0,   // Neither key while both are actuated
+    LastWins = 1,  // Most recently actuated key (snap tap), released key hands back to the other
+    FirstWins = 2, // Key that was actuated first
+}
+
+/// Which key of a SocdPair was actuated most recently
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+enum SocdKey {
+    A,
+    B,
+}
+
+/// Two opposing keys (e.g. left/right movement) that should not be reported at the same time
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct SocdPair {
+    pub a: usize,
+    pub b: usize,
+    pub rule: SocdRule,
+    /// Most recently actuated key (None if both actuated in the same update)
+    last: Option<SocdKey>,
+    /// Actuation state from the previous update
+    prev: (bool, bool),
+}
+
+impl SocdPair {
+    pub fn new(a: usize, b: usize, rule: SocdRule) -> SocdPair {
+        SocdPair {
+            a,
+            b,
+            rule,
+            last: None,
+            prev: (false, false),
+        }
+    }
+
+    /// Resolve the actuation state of the pair
+    /// Call once per update with the actuation state of a and b.
+    /// Returns the resolved (a, b) state, at most one is true while both are actuated.
+    /// If both keys actuate in the same update there is no ordering and the result is neutral.
+    pub fn resolve(&mut self, a: bool, b: bool) -> (bool, bool) {
+        match (a && !self.prev.0, b && !self.prev.1) {
+            (true, false) => self.last = Some(SocdKey::A),
+            (false, true) => self.last = Some(SocdKey::B),
+            (true, true) => self.last = None,
+            (false, false) => {}
+        }
+        self.prev = (a, b);
+
+        if !(a && b) {
+            return (a, b);
+        }
+        match (self.rule, self.last) {
+            (SocdRule::Neutral, _) | (_, None) => (false, false),
+            (SocdRule::LastWins, Some(SocdKey::A)) | (SocdRule::FirstWins, Some(SocdKey::B)) => {
+                (true, false)
+            }
+            (SocdRule::LastWins, Some(SocdKey::B)) | (SocdRule::FirstWins, Some(SocdKey::A)) => {
+                (false, true)
+            }
+        }
+    }
+
+    /// Resolve the pair using the actuation point of each key (see SenseData::actuated)
+    /// Both sensors must be calibrated (MagnetDetected).
+    pub fn resolve_sensors<
+        const AP: usize,
+        const S: usize,
+        const H: usize,
+        const CAL_CONFIRM: usize,
+    >(
+        &mut self,
+        sensors: &Sensors<S, H, CAL_CONFIRM>,
+    ) -> Result<(bool, bool), SensorError> {
+        let a = sensors.get_calibrated(self.a)?.actuated::<AP>();
+        let b = sensors.get_calibrated(self.b)?.actuated::<AP>();
+        Ok(self.resolve(a, b))
+    }
+}
+
+// ----- Manufacturing Test -----
+
+/// Manufacturing test result of a single sensor
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum TestResult {
+    Pass = 0,       // Rest and press readings are within the expected ranges
+    NoSensor = 1,   // No readings, ADC value below NS or above MXOK (missing or broken sensor)
+    WrongPole = 2,  // Rest reading below MNOK, or reading decreases when pressed
+    WeakMagnet = 3, // Press travel is less than the expected travel
+    Noisy = 4,      // Rest peak-to-peak noise is higher than the expected noise
+}
+
+/// Manufacturing test phase
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum TestPhase {
+    Idle = 0,         // Readings are ignored
+    RestCapture = 1,  // No keys held
+    PressCapture = 2, // All keys pressed (e.g. using a jig)
+    Done = 3,         // Evaluated
+}
+
+/// Captured raw reading range
+#[derive(Clone, Copy, Debug, defmt::Format)]
+struct Capture {
+    min: u16,
+    max: u16,
+    samples: u32,
+}
+
+impl Capture {
+    fn new() -> Capture {
+        Capture {
+            min: 0xFFFF,
+            max: 0x0000,
+            samples: 0,
+        }
+    }
+
+    fn add(&mut self, reading: u16) {
+        self.min = self.min.min(reading);
+        self.max = self.max.max(reading);
+        self.samples = self.samples.saturating_add(1);
+    }
+}
+
+/// Deterministic manufacturing (factory bring-up) test
+/// 1. start_rest_capture(), feed readings with no keys held
+/// 2. start_press_capture(), feed readings with all keys pressed
+/// 3. evaluate() for the per-sensor results
+///
+/// Readings are captured separately from Sensors so the normal calibration state is not
+/// disturbed; the same raw readings can be fed to both.
+///
+/// Thresholds (evaluate):
+/// * MNOK, MXOK, NS: Same calibration thresholds used by Sensors::add_test
+/// * NOISE: Max rest peak-to-peak noise
+/// * TRAVEL: Min raw reading increase when pressed
+pub struct TestMode<const S: usize> {
+    phase: TestPhase,
+    rest: [Capture; S],
+    press: [Capture; S],
+}
+
+impl<const S: usize> TestMode<S> {
+    pub fn new() -> TestMode<S> {
+        TestMode {
+            phase: TestPhase::Idle,
+            rest: [Capture::new(); S],
+            press: [Capture::new(); S],
+        }
+    }
+
+    /// Current test phase
+    pub fn phase(&self) -> TestPhase {
+        self.phase
+    }
+
+    /// Start capturing rest readings (no keys held)
+    /// Clears any previous captures.
+    pub fn start_rest_capture(&mut self) {
+        self.rest = [Capture::new(); S];
+        self.press = [Capture::new(); S];
+        self.phase = TestPhase::RestCapture;
+    }
+
+    /// Start capturing pressed readings (all keys pressed)
+    pub fn start_press_capture(&mut self) {
+        self.press = [Capture::new(); S];
+        self.phase = TestPhase::PressCapture;
+    }
+
+    /// Add a raw reading for a specific sensor to the current capture
+    pub fn add(&mut self, index: usize, reading: u16) -> Result<(), SensorError> {
+        let capture = match self.phase {
+            TestPhase::RestCapture => self.rest.get_mut(index),
+            TestPhase::PressCapture => self.press.get_mut(index),
+            _ => {
+                if index >= S {
+                    return Err(SensorError::InvalidSensor(index));
+                }
+                return Ok(());
+            }
+        };
+        match capture {
+            Some(capture) => {
+                capture.add(reading);
+                Ok(())
+            }
+            None => Err(SensorError::InvalidSensor(index)),
+        }
+    }
+
+    /// Evaluate the captured readings
+    pub fn evaluate<
+        const MNOK: usize,
+        const MXOK: usize,
+        const NS: usize,
+        const NOISE: usize,
+        const TRAVEL: usize,
+    >(
+        &mut self,
+    ) -> [TestResult; S] {
+        self.phase = TestPhase::Done;
+        core::array::from_fn(|index| {
+            let rest = &self.rest[index];
+            let press = &self.press[index];
+
+            // Rest position (same order as calibration)
+            if rest.samples == 0 || rest.max > threshold(MXOK) || rest.min < threshold(NS) {
+                return TestResult::NoSensor;
+            }
+            if rest.min < threshold(MNOK) {
+                return TestResult::WrongPole;
+            }
+            if (rest.max - rest.min) as usize > NOISE {
+                return TestResult::Noisy;
+            }
+
+            // Pressed position
+            if press.samples == 0 {
+                return TestResult::WeakMagnet;
+            }
+            if press.max as usize >= rest.max as usize + TRAVEL {
+                TestResult::Pass
+            } else if press.min as usize + TRAVEL <= rest.min as usize {
+                TestResult::WrongPole
+            } else {
+                TestResult::WeakMagnet
+            }
+        })
+    }
+}
+
+impl<const S: usize> Default for TestMode<S> {
+    fn default() -> Self {
+        TestMode::new()
+    }
 }
 
 #[cfg(feature = "kll-core")]
@@ -500,7 +4260,7 @@ mod converters {
         }
     }
 
-    impl SenseData {
+    impl<const H: usize> SenseData<H> {
         /// Conveniece conversion, uses earlier analysis
         /// Also validates calibration status
         pub fn trigger_event(&self, index: usize, ignore_off: bool) -> Vec<TriggerEvent, 4> {
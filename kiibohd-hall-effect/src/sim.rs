@@ -0,0 +1,201 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Host-side simulation harness
+// Runs synthetic key press waveforms through Sensors and collects the resulting analyses and
+// calibration transitions for assertions (no hardware required).
+
+// ----- Crates -----
+
+extern crate std;
+
+use super::*;
+use std::vec::Vec;
+
+// ----- Profiles -----
+
+/// Synthetic raw ADC waveform of a single key
+#[derive(Clone, Debug, PartialEq)]
+pub enum Profile {
+    /// Constant reading, e.g. a key at rest
+    Hold { raw: u16, samples: usize },
+    /// Linear ramp (press or release)
+    Ramp { from: u16, to: u16, samples: usize },
+    /// Sinusoid around center, period is in samples
+    Sine {
+        center: u16,
+        amplitude: u16,
+        period: usize,
+        samples: usize,
+    },
+    /// Press from rest to bottom over the first half, then a decaying bounce off the bottom
+    BottomBounce {
+        rest: u16,
+        bottom: u16,
+        amplitude: u16,
+        samples: usize,
+    },
+    /// Magnet pulled away, drops from rest to the sensor baseline over the first quarter
+    /// and stays there (e.g. a keycap and stem yanked out)
+    MagnetRemoval {
+        rest: u16,
+        baseline: u16,
+        samples: usize,
+    },
+}
+
+impl Profile {
+    /// Generate the raw readings of the profile
+    pub fn readings(&self) -> Vec<u16> {
+        match *self {
+            Profile::Hold { raw, samples } => std::vec![raw; samples],
+            Profile::Ramp { from, to, samples } => ramp(from, to, samples),
+            Profile::Sine {
+                center,
+                amplitude,
+                period,
+                samples,
+            } => (0..samples)
+                .map(|i| {
+                    let phase = 2.0 * core::f64::consts::PI * i as f64 / period.max(1) as f64;
+                    let raw = center as f64 + amplitude as f64 * phase.sin();
+                    raw.round().clamp(0.0, u16::MAX as f64) as u16
+                })
+                .collect(),
+            Profile::BottomBounce {
+                rest,
+                bottom,
+                amplitude,
+                samples,
+            } => {
+                let press = samples / 2;
+                let bounce = samples - press;
+                let mut readings = ramp(rest, bottom, press);
+                readings.extend((0..bounce).map(|i| {
+                    if i % 2 == 0 {
+                        bottom
+                    } else {
+                        let decay = (amplitude as usize * (bounce - i) / bounce) as u16;
+                        bottom.saturating_sub(decay)
+                    }
+                }));
+                readings
+            }
+            Profile::MagnetRemoval {
+                rest,
+                baseline,
+                samples,
+            } => {
+                let drop = samples / 4;
+                let mut readings = ramp(rest, baseline, drop);
+                readings.resize(samples, baseline);
+                readings
+            }
+        }
+    }
+}
+
+/// Linear ramp, ending exactly at to
+fn ramp(from: u16, to: u16, samples: usize) -> Vec<u16> {
+    (1..=samples)
+        .map(|i| (from as i64 + (to as i64 - from as i64) * i as i64 / samples as i64) as u16)
+        .collect()
+}
+
+// ----- Simulation -----
+
+/// Value recorded while running a profile
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recorded<T> {
+    /// Sensor index
+    pub index: usize,
+    /// Position of the reading since the simulation started (across all runs)
+    pub reading: usize,
+    pub value: T,
+}
+
+/// Drives Sensors with profiles, collecting analyses and calibration transitions
+pub struct Simulation<const S: usize, const H: usize = 0, const CAL_CONFIRM: usize = 1> {
+    pub sensors: Sensors<S, H, CAL_CONFIRM>,
+    pub analyses: Vec<Recorded<SenseAnalysis>>,
+    pub transitions: Vec<Recorded<CalibrationTransition>>,
+    readings: usize,
+}
+
+impl<const S: usize, const H: usize, const CAL_CONFIRM: usize> Simulation<S, H, CAL_CONFIRM> {
+    pub fn new(sensors: Sensors<S, H, CAL_CONFIRM>) -> Simulation<S, H, CAL_CONFIRM> {
+        Simulation {
+            sensors,
+            analyses: Vec::new(),
+            transitions: Vec::new(),
+            readings: 0,
+        }
+    }
+
+    /// Run a profile through Sensors::add (normal mode)
+    pub fn run<const SC: usize>(
+        &mut self,
+        index: usize,
+        profile: &Profile,
+    ) -> Result<(), SensorError> {
+        for raw in profile.readings() {
+            let analysis = self.sensors.add::<SC>(index, raw)?.analysis().cloned();
+            self.record(index, analysis);
+        }
+        Ok(())
+    }
+
+    /// Run a profile through Sensors::add_test (test mode, with calibration checks)
+    pub fn run_test<const SC: usize, const MNOK: usize, const MXOK: usize, const NS: usize>(
+        &mut self,
+        index: usize,
+        profile: &Profile,
+    ) -> Result<(), SensorError> {
+        for raw in profile.readings() {
+            let analysis = self
+                .sensors
+                .add_test::<SC, MNOK, MXOK, NS>(index, raw)?
+                .analysis()
+                .cloned();
+            self.record(index, analysis);
+        }
+        Ok(())
+    }
+
+    /// Analyses of a single sensor
+    pub fn analyses_of(&self, index: usize) -> impl Iterator<Item = &SenseAnalysis> + '_ {
+        self.analyses
+            .iter()
+            .filter(move |recorded| recorded.index == index)
+            .map(|recorded| &recorded.value)
+    }
+
+    /// Clear the collected analyses and transitions
+    pub fn clear(&mut self) {
+        self.analyses.clear();
+        self.transitions.clear();
+    }
+
+    fn record(&mut self, index: usize, analysis: Option<SenseAnalysis>) {
+        let reading = self.readings;
+        self.readings += 1;
+        if let Some(value) = analysis {
+            self.analyses.push(Recorded {
+                index,
+                reading,
+                value,
+            });
+        }
+        if let Some(value) = self.sensors.sensors[index].transition() {
+            self.transitions.push(Recorded {
+                index,
+                reading,
+                value,
+            });
+        }
+    }
+}
@@ -57,7 +57,7 @@ fn invalid_index() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Add data to an invalid location
     assert!(sensors
@@ -73,18 +73,12 @@ fn not_ready() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let sensors = Sensors::<1>::new().unwrap();
+    let sensors = Sensors::<1>::new();
 
     // Retrieve before sending any data
     let state = sensors.get_data(0);
-    match state.clone() {
-        Err(SensorError::CalibrationError(data)) => match data.cal {
-            CalibrationStatus::NotReady => {
-                return;
-            }
-            _ => {}
-        },
-        _ => {}
+    if let Err(SensorError::CalibrationError(CalibrationStatus::NotReady)) = state {
+        return;
     }
     assert!(false, "Unexpected state: {:?}", state);
 }
@@ -94,7 +88,7 @@ fn sensor_missing() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Add a sensor value of 0
     // (needs 2 samples to finish averaging)
@@ -110,14 +104,8 @@ fn sensor_missing() {
         NO_SENSOR_THRESHOLD as u16 - 1,
     );
 
-    match state.clone() {
-        Err(SensorError::CalibrationError(data)) => match data.cal {
-            CalibrationStatus::SensorMissing => {
-                return;
-            }
-            _ => {}
-        },
-        _ => {}
+    if let Ok(Measurement::NotCalibrated(CalibrationStatus::SensorMissing)) = state {
+        return;
     }
     assert!(false, "Unexpected state: {:?}", state);
 }
@@ -127,7 +115,7 @@ fn sensor_broken() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Add max sensor value
     // (needs 2 samples to finish averaging)
@@ -138,14 +126,8 @@ fn sensor_broken() {
     let state =
         sensors.add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 0xFFFF);
 
-    match state.clone() {
-        Err(SensorError::CalibrationError(data)) => match data.cal {
-            CalibrationStatus::SensorBroken => {
-                return;
-            }
-            _ => {}
-        },
-        _ => {}
+    if let Ok(Measurement::NotCalibrated(CalibrationStatus::SensorBroken)) = state {
+        return;
     }
     assert!(false, "Unexpected state: {:?}", state);
 }
@@ -155,7 +137,7 @@ fn magnet_missing() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Add max sensor value
     // (needs 2 samples to finish averaging)
@@ -171,18 +153,44 @@ fn magnet_missing() {
         MIN_OK_THRESHOLD as u16 - 1,
     );
 
-    match state.clone() {
-        Err(SensorError::CalibrationError(data)) => match data.cal {
-            CalibrationStatus::MagnetWrongPoleOrMissing => {
-                return;
-            }
-            _ => {}
-        },
-        _ => {}
+    if let Ok(Measurement::NotCalibrated(CalibrationStatus::MagnetWrongPoleOrMissing)) = state {
+        return;
     }
     assert!(false, "Unexpected state: {:?}", state);
 }
 
+#[test]
+fn calibration_measurement() {
+    setup_logging_lite().ok();
+
+    // Calibration states are not errors
+    let mut sensors = Sensors::<1>::new();
+    let measurement = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 0)
+        .unwrap();
+    assert!(measurement.analysis().is_none());
+    assert!(!measurement.is_pending());
+    assert_eq!(
+        measurement.not_calibrated(),
+        Some(CalibrationStatus::SensorMissing)
+    );
+    // Details are still available from the sensor data
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::SensorMissing
+    );
+
+    // Index errors are still errors
+    assert!(matches!(
+        sensors.add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(3, 0),
+        Err(SensorError::InvalidSensor(3))
+    ));
+    assert!(matches!(
+        sensors.add::<1>(3, 0),
+        Err(SensorError::InvalidSensor(3))
+    ));
+}
+
 fn magnet_check_calibration<const U: usize>(sensors: &mut Sensors<U>) {
     // Add two values, larger MIN_OK_THRESHOLD
     let val = MIN_OK_THRESHOLD as u16 + 2;
@@ -197,7 +205,7 @@ fn magnet_check_calibration<const U: usize>(sensors: &mut Sensors<U>) {
     let mut test = false;
     match state.clone() {
         Ok(rval) => {
-            if let Some(rval) = rval {
+            if let Some(rval) = rval.analysis() {
                 if rval.raw == val {
                     test = true;
                 }
@@ -235,7 +243,7 @@ fn magnet_check_normal<const U: usize>(sensors: &mut Sensors<U>) {
     let mut test = false;
     match state.clone() {
         Ok(rval) => {
-            if let Some(rval) = rval {
+            if let Some(rval) = rval.analysis() {
                 if rval.raw == val {
                     test = true;
                 }
@@ -272,7 +280,7 @@ fn magnet_detected() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Two sets of samples that will put the sensor into normal mode (and check both MagnetDetected
     // states)
@@ -284,7 +292,7 @@ fn sensor_min_adjust() {
     setup_logging_lite().ok();
 
     // Allocate a single sensor
-    let mut sensors = Sensors::<1>::new().unwrap();
+    let mut sensors = Sensors::<1>::new();
 
     // Baseline
     magnet_check_calibration::<1>(&mut sensors);
@@ -302,7 +310,7 @@ fn sensor_min_adjust() {
     let mut test = false;
     match state.clone() {
         Ok(rval) => {
-            if let Some(rval) = rval {
+            if let Some(rval) = rval.analysis() {
                 if rval.raw == val {
                     test = true;
                 }
@@ -316,3 +324,3923 @@ fn sensor_min_adjust() {
     let new_min = sensors.get_data(0).unwrap().stats.min;
     assert!(val == new_min);
 }
+
+/// Feeds a step from `from` to `to` and returns the number of samples (after the step) until the
+/// filtered value reaches `threshold`
+fn step_response<const SC: usize>(
+    config: SensorsConfig,
+    from: u16,
+    to: u16,
+    threshold: u16,
+) -> usize {
+    let mut sensors = Sensors::<1>::with_config(config);
+
+    // Settle on the initial value
+    for _ in 0..SC * 4 {
+        sensors.add::<SC>(0, from).unwrap();
+    }
+
+    // Step
+    for sample in 1..SC * 16 {
+        if let Ok(Measurement::Ready(rval)) = sensors.add::<SC>(0, to) {
+            if rval.raw >= threshold {
+                return sample;
+            }
+        }
+    }
+    panic!("Step response never reached {}", threshold);
+}
+
+#[test]
+fn average_previous_window() {
+    setup_logging_lite().ok();
+
+    // Averaged with the previous window, SC = 4 divides the 8 samples by 8
+    // (regression, this used to be divided by SC twice, i.e. by 16)
+    let mut data = RawData::new();
+    let window = |data: &mut RawData, reading| {
+        for _ in 0..3 {
+            assert_eq!(data.add_average(reading, 4, 0), None);
+        }
+        data.add_average(reading, 4, 0)
+    };
+    assert_eq!(window(&mut data, 1400), Some(1400));
+    assert_eq!(window(&mut data, 2000), Some(1700));
+    assert_eq!(window(&mut data, 2000), Some(2000));
+
+    // Same through Sensors::add
+    let mut sensors = Sensors::<1>::new();
+    let mut last = None;
+    for reading in [1400, 1400, 1400, 1400, 2000, 2000, 2000, 2000] {
+        last = sensors
+            .add::<4>(0, reading)
+            .unwrap()
+            .analysis()
+            .map(|a| a.raw);
+    }
+    assert_eq!(last, Some(1700));
+}
+
+#[test]
+fn iir_filter_steady_state() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        filter: Filter::Iir { shift: 2 },
+        ..Default::default()
+    });
+
+    // A constant input must settle on exactly the same value (no truncation drift)
+    let val = MIN_OK_THRESHOLD as u16 + 123;
+    for _ in 0..64 {
+        sensors.add::<1>(0, val).unwrap();
+    }
+    assert_eq!(
+        sensors.add::<1>(0, val).unwrap().analysis().unwrap().raw,
+        val
+    );
+}
+
+#[test]
+fn iir_filter_divider() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        filter: Filter::Iir { shift: 2 },
+        ..Default::default()
+    });
+
+    // Analysis is only produced every SC samples
+    for _ in 0..3 {
+        assert!(sensors.add::<4>(0, 1400).unwrap().is_pending());
+        assert!(sensors.add::<4>(0, 1400).unwrap().is_pending());
+        assert!(sensors.add::<4>(0, 1400).unwrap().is_pending());
+        assert!(sensors.add::<4>(0, 1400).unwrap().analysis().is_some());
+    }
+}
+
+#[test]
+fn iir_filter_step_latency() {
+    setup_logging_lite().ok();
+
+    let from = 1400;
+    let to = 2000;
+    let threshold = 1900;
+
+    let average = step_response::<4>(SensorsConfig::default(), from, to, threshold);
+    let iir = step_response::<4>(
+        SensorsConfig {
+            filter: Filter::Iir { shift: 1 },
+            ..Default::default()
+        },
+        from,
+        to,
+        threshold,
+    );
+    info!("Step latency - Average: {}  IIR: {}", average, iir);
+
+    // Boxcar average (with the previous window) needs two full windows
+    assert_eq!(average, 8);
+    // IIR is within range after the first divided sample
+    assert_eq!(iir, 4);
+}
+
+#[test]
+fn hold_filters() {
+    setup_logging_lite().ok();
+
+    // Sawtooth, one tooth per window
+    let run = |filter, base: u16| {
+        let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+            filter,
+            ..Default::default()
+        });
+        let mut values = [0; 3];
+        for value in values.iter_mut() {
+            for step in 0..4 {
+                if let Some(analysis) = sensors
+                    .add_test::<4, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                        0,
+                        base + step * 100,
+                    )
+                    .unwrap()
+                    .analysis()
+                {
+                    *value = analysis.raw;
+                }
+            }
+        }
+        (values, sensors.sensors[0].cal.clone())
+    };
+
+    let base = MIN_OK_THRESHOLD as u16 + 50;
+    let (values, _) = run(Filter::Average, base);
+    assert_eq!(values[1..], [base + 150; 2]);
+    let (values, _) = run(Filter::Max, base);
+    assert_eq!(values[1..], [base + 300; 2]);
+    let (values, _) = run(Filter::Min, base);
+    assert_eq!(values[1..], [base; 2]);
+
+    // Calibration thresholds apply to the held value
+    let base = MAX_OK_THRESHOLD as u16 - 200;
+    assert_eq!(
+        run(Filter::Average, base).1,
+        CalibrationStatus::MagnetDetected
+    );
+    assert_eq!(run(Filter::Max, base).1, CalibrationStatus::SensorBroken);
+    let base = NO_SENSOR_THRESHOLD as u16 - 50;
+    assert_eq!(
+        run(Filter::Average, base).1,
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(run(Filter::Min, base).1, CalibrationStatus::SensorMissing);
+}
+
+#[test]
+fn velocity_event() {
+    setup_logging_lite().ok();
+
+    // Allocate a single sensor
+    let mut sensors = Sensors::<1>::new();
+
+    // Baseline
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Not moving, no strike
+    assert_eq!(sensors.get_data(0).unwrap().velocity_event::<0>(), None);
+
+    // Strike the key (averaged with the previous window, so velocity is 100)
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 200)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(
+        data.velocity_event::<50>(),
+        Some(VelocityEvent { velocity: 100 })
+    );
+    assert_eq!(data.velocity_event::<100>(), None);
+
+    // Release the key, upward movement must not trigger
+    // (two windows to clear the averaging with the previous window)
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert!(data.analysis.velocity < 0);
+    assert_eq!(data.velocity_event::<0>(), None);
+}
+
+/// Calibrates a sensor then injects a single sample spike
+/// Returns the largest absolute velocity seen
+fn spike(config: SensorsConfig) -> i16 {
+    let median = config.median;
+    let mut sensors = Sensors::<1>::with_config(config);
+    let val = MIN_OK_THRESHOLD as u16 + 2;
+
+    // Baseline
+    for _ in 0..8 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+
+    // Spike
+    let mut max_velocity = 0;
+    for reading in [val, val + 500, val, val, val, val] {
+        if let Measurement::Ready(rval) = sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, reading)
+            .unwrap()
+        {
+            if median != Median::Off {
+                assert_eq!(rval.raw, val);
+            }
+            max_velocity = max_velocity.max(rval.velocity.abs());
+        }
+    }
+    max_velocity
+}
+
+#[test]
+fn median_spike_rejection() {
+    setup_logging_lite().ok();
+
+    // Without the median filter the spike leaks into the velocity
+    assert!(spike(SensorsConfig::default()) > 0);
+
+    // Spike is rejected entirely
+    assert_eq!(
+        spike(SensorsConfig {
+            median: Median::Of3,
+            ..Default::default()
+        }),
+        0
+    );
+    assert_eq!(
+        spike(SensorsConfig {
+            median: Median::Of5,
+            ..Default::default()
+        }),
+        0
+    );
+}
+
+#[test]
+fn zones() {
+    setup_logging_lite().ok();
+
+    let boundaries = [0, 100, 200];
+    let mut analysis = SenseAnalysis::null();
+    for (distance, zone) in [
+        (-50, 0),
+        (-1, 0),
+        (0, 0),
+        (99, 0),
+        (100, 1),
+        (199, 1),
+        (200, 2),
+        (300, 2),
+        (i16::MAX, 2),
+    ] {
+        analysis.distance = distance;
+        assert_eq!(analysis.zone(&boundaries), zone, "Distance: {}", distance);
+    }
+}
+
+#[test]
+fn last_good() {
+    setup_logging_lite().ok();
+
+    // Allocate a single sensor
+    let mut sensors = Sensors::<1>::new();
+
+    // Never calibrated
+    assert!(sensors.get_last_good(0).is_err());
+    assert!(sensors.get_last_good(1).is_err());
+
+    // Baseline, then press the key
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 200)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_last_good(0).unwrap().distance, 200);
+
+    // Glitch out of calibration
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 0)
+            .ok();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.cal, CalibrationStatus::SensorMissing);
+    assert_eq!(data.analysis.distance, 0);
+
+    // Previous position is held
+    let last_good = sensors.get_last_good(0).unwrap();
+    assert_eq!(last_good.distance, 200);
+    assert_eq!(last_good.raw, rest + 200);
+
+    // Forgotten by recalibration
+    sensors.get_data_mut(0).unwrap().recalibrate();
+    assert!(sensors.get_last_good(0).is_err());
+
+    // A calibrated reading of 0 (no NS or MNOK threshold) is still a good analysis
+    for _ in 0..4 {
+        sensors.add_test::<2, 0, MAX_OK_THRESHOLD, 0>(0, 0).unwrap();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+    assert_eq!(sensors.get_last_good(0).unwrap().raw, 0);
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, 0, MAX_OK_THRESHOLD, 0>(0, ADC_MAX)
+            .ok();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::SensorBroken
+    );
+    assert_eq!(sensors.get_last_good(0).unwrap().raw, 0);
+}
+
+#[test]
+fn runtime_sample_count() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+
+    // Must be a power of two
+    assert!(sensors.set_sample_count(0).is_err());
+    assert!(sensors.set_sample_count(3).is_err());
+    assert_eq!(sensors.sample_count(), 1);
+
+    // Start a partial accumulation, then change the sample count
+    sensors.set_sample_count(2).unwrap();
+    assert!(sensors.add_runtime(0, 2000).unwrap().is_pending());
+    sensors.set_sample_count(4).unwrap();
+
+    // The partial sample was flushed
+    for _ in 0..3 {
+        assert!(sensors.add_runtime(0, 1400).unwrap().is_pending());
+    }
+    assert_eq!(
+        sensors
+            .add_runtime(0, 1400)
+            .unwrap()
+            .analysis()
+            .unwrap()
+            .raw,
+        1400
+    );
+}
+
+#[test]
+fn pending_samples() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    assert_eq!(sensors.get_data(0).unwrap().pending_samples(), 0);
+
+    // Partial accumulation is visible without producing a sample
+    sensors.set_sample_count(4).unwrap();
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    for pending in 1..4 {
+        assert!(sensors.add_runtime(0, rest).unwrap().is_pending());
+        assert_eq!(sensors.get_data(0).unwrap().pending_samples(), pending);
+    }
+    assert!(sensors.add_runtime(0, rest).unwrap().analysis().is_some());
+    assert_eq!(sensors.get_data(0).unwrap().pending_samples(), 0);
+}
+
+#[test]
+fn runtime_sample_count_matches_generic() {
+    setup_logging_lite().ok();
+
+    let mut generic = Sensors::<1>::new();
+    let mut runtime = Sensors::<1>::new();
+    runtime.set_sample_count(8).unwrap();
+
+    for reading in (1400..1800).step_by(7) {
+        let expected = generic
+            .add_test::<8, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, reading)
+            .unwrap()
+            .analysis()
+            .map(|analysis| (analysis.raw, analysis.distance, analysis.velocity));
+        let result = runtime
+            .add_test_runtime::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, reading)
+            .unwrap()
+            .analysis()
+            .map(|analysis| (analysis.raw, analysis.distance, analysis.velocity));
+        assert_eq!(expected, result);
+    }
+}
+
+#[test]
+fn max_step() {
+    setup_logging_lite().ok();
+
+    // Allocate a single sensor
+    let mut sensors = Sensors::<1>::new();
+
+    // 0 -> 0xFFF -> 0 in single samples (normal mode)
+    let rval = sensors.add::<1>(0, 0).unwrap().analysis().unwrap();
+    assert_eq!(rval.distance, 0);
+    let rval = sensors.add::<1>(0, 0xFFF).unwrap().analysis().unwrap();
+    assert!(rval.distance > 0 && rval.velocity > 0 && rval.acceleration > 0);
+    let rval = sensors.add::<1>(0, 0).unwrap().analysis().unwrap();
+    assert!(rval.velocity < 0 && rval.acceleration < 0 && rval.jerk < 0);
+    let rval = sensors.add::<1>(0, 0xFFF).unwrap().analysis().unwrap();
+    assert!(rval.velocity > 0 && rval.acceleration > 0 && rval.jerk > 0);
+}
+
+#[test]
+fn saturating_analysis() {
+    setup_logging_lite().ok();
+
+    // Previous analysis at the extremes, next sample must clamp instead of wrapping
+    let mut data = SenseData::<0>::new();
+    data.stats.min = 0;
+    data.analysis = SenseAnalysis {
+        raw: 0,
+        distance: i16::MIN,
+        velocity: i16::MIN,
+        acceleration: i16::MIN,
+        jerk: 0,
+        smoothed_velocity: 0,
+    };
+    let analysis = SenseAnalysis::new(0xFFF, &data, &DEFAULT_LOOKUP_TABLE);
+    assert_eq!(analysis.velocity, i16::MAX);
+    assert_eq!(analysis.acceleration, i16::MAX);
+    assert_eq!(analysis.jerk, i16::MAX);
+
+    data.analysis = SenseAnalysis {
+        raw: 0,
+        distance: i16::MAX,
+        velocity: i16::MAX,
+        acceleration: i16::MAX,
+        jerk: 0,
+        smoothed_velocity: 0,
+    };
+    let analysis = SenseAnalysis::new(0, &data, &DEFAULT_LOOKUP_TABLE);
+    assert_eq!(analysis.distance, 0);
+    assert_eq!(analysis.velocity, -i16::MAX);
+    assert_eq!(analysis.acceleration, -i16::MAX);
+    assert_eq!(analysis.jerk, i16::MIN);
+}
+
+#[test]
+fn idle() {
+    setup_logging_lite().ok();
+
+    // Allocate a single sensor
+    let mut sensors = Sensors::<1>::new();
+
+    // Uncalibrated sensors are never idle
+    assert!(!SenseData::<0>::new().is_idle::<0>());
+
+    // Baseline
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Hold still
+    for _ in 0..8 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert!(data.is_idle::<4>());
+    assert!(!data.is_idle::<16>());
+
+    // Noise within the idle velocity doesn't reset idle
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 1)
+            .unwrap();
+    }
+    assert!(sensors.get_data(0).unwrap().is_idle::<4>());
+
+    // Movement resets idle
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 100)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.idle_samples(), 0);
+    assert!(!data.is_idle::<1>());
+}
+
+#[test]
+fn physical_units() {
+    setup_logging_lite().ok();
+
+    let mut analysis = SenseAnalysis::null();
+    analysis.distance = 250;
+    analysis.velocity = -25;
+    assert_eq!(analysis.distance_um(), 1000);
+    assert_eq!(analysis.velocity_um_per_sample(), -100);
+
+    // Rounding to the nearest micrometer
+    let table = LookupTable {
+        table: &[],
+        um_num: 5,
+        um_den: 2,
+    };
+    assert_eq!(table.to_um(0), 0);
+    assert_eq!(table.to_um(1), 3); // 2.5
+    assert_eq!(table.to_um(-1), -3); // -2.5
+    assert_eq!(table.to_um(2), 5);
+    assert_eq!(table.to_um(3), 8); // 7.5
+    let table = LookupTable {
+        table: &[],
+        um_num: 1,
+        um_den: 3,
+    };
+    assert_eq!(table.to_um(1), 0); // 0.33
+    assert_eq!(table.to_um(2), 1); // 0.66
+    assert_eq!(table.to_um(-2), -1); // -0.66
+}
+
+#[test]
+fn auto_threshold() {
+    setup_logging_lite().ok();
+
+    let config = SensorsConfig {
+        auto_threshold: Some(AutoThreshold {
+            window: 4,
+            multiple: 4,
+        }),
+        ..Default::default()
+    };
+    let mut auto = Sensors::<1>::with_config(config);
+    let mut fixed = Sensors::<1>::new();
+
+    // Noisy rest position
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    for _ in 0..4 {
+        for reading in [rest, rest, rest + 4, rest + 4] {
+            auto.add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, reading)
+                .unwrap();
+            fixed
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, reading)
+                .unwrap();
+        }
+    }
+
+    // Noise is measured and the threshold derived from the rest value
+    let data = auto.get_data(0).unwrap();
+    assert_eq!(data.noise(), Some(4));
+    assert_eq!(data.stats.min_ok, rest - 4 * 4);
+    assert_eq!(fixed.get_data(0).unwrap().noise(), None);
+
+    // Magnet weakens, still well above the static threshold
+    // (calibration is lost after SensorsConfig::cal_debounce windows)
+    let weak = rest - 40;
+    for _ in 0..2 {
+        assert!(auto
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, weak)
+            .unwrap()
+            .is_pending());
+    }
+    assert!(auto
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, weak)
+        .unwrap()
+        .not_calibrated()
+        .is_some());
+    assert!(fixed
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, weak)
+        .unwrap()
+        .not_calibrated()
+        .is_none());
+
+    // Calibration lost, falls back to the static threshold
+    let data = auto.get_data(0).unwrap();
+    assert_eq!(data.stats.min_ok, 0);
+    assert_eq!(data.noise(), None);
+}
+
+#[test]
+fn temp_compensation() {
+    setup_logging_lite().ok();
+
+    // Linear correction, 1/256 units per degree
+    let comp = TempCompensation {
+        slope: 128,
+        reference: 25,
+    };
+    assert_eq!(comp.offset(25), 0);
+    assert_eq!(comp.offset(45), 10);
+    assert_eq!(comp.offset(5), -10);
+    assert_eq!(
+        TempCompensation {
+            slope: i16::MAX,
+            reference: i16::MIN,
+        }
+        .offset(i16::MAX),
+        i16::MAX
+    );
+
+    // Allocate a single sensor
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Sensor drifts up 10 counts when 20 degrees warmer
+    sensors.set_temp_compensation(128, 25);
+    sensors.set_temperature(45);
+    assert_eq!(sensors.temp_compensation(), comp);
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 110)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance, 100);
+
+    // Disabled
+    sensors.set_temp_compensation(0, 25);
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 110)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance, 110);
+}
+
+#[test]
+fn status_summary() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<5>::new();
+    assert_eq!(sensors.status_summary(), [5, 0, 0, 0, 0, 0]);
+    assert_eq!(sensors.first_fault(), None);
+
+    // 0: MagnetDetected
+    // 1: NotReady
+    // 2: SensorBroken
+    // 3: SensorMissing
+    // 4: MagnetWrongPoleOrMissing
+    for (index, val) in [
+        (0, MIN_OK_THRESHOLD as u16 + 2),
+        (2, MAX_OK_THRESHOLD as u16 + 1),
+        (3, NO_SENSOR_THRESHOLD as u16 - 1),
+        (4, MIN_OK_THRESHOLD as u16 - 1),
+    ] {
+        for _ in 0..2 {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .ok();
+        }
+    }
+
+    let summary = sensors.status_summary();
+    assert_eq!(summary[CalibrationStatus::NotReady as usize], 1);
+    assert_eq!(summary[CalibrationStatus::SensorMissing as usize], 1);
+    assert_eq!(summary[CalibrationStatus::SensorBroken as usize], 1);
+    assert_eq!(summary[CalibrationStatus::MagnetDetected as usize], 1);
+    assert_eq!(
+        summary[CalibrationStatus::MagnetWrongPoleOrMissing as usize],
+        1
+    );
+    assert_eq!(summary[CalibrationStatus::InvalidIndex as usize], 0);
+    assert_eq!(sensors.first_fault(), Some(2));
+}
+
+#[test]
+fn add_frame() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<3>::new();
+
+    // Length mismatch
+    assert!(matches!(
+        sensors.add_frame(&[1400, 1500]),
+        Err(SensorError::InvalidFrameLength(2))
+    ));
+    assert!(matches!(
+        sensors.add_frame(&[1400, 1500, 1600, 1700]),
+        Err(SensorError::InvalidFrameLength(4))
+    ));
+
+    // Frame order is sensor order
+    sensors.set_sample_count(2).unwrap();
+    let frame = [1400, 1500, 1600];
+    assert_eq!(sensors.add_frame(&frame).unwrap().analyses, 0);
+    assert_eq!(
+        sensors.add_frame(&frame).unwrap(),
+        FrameResult { analyses: 3 }
+    );
+    for (index, val) in frame.iter().enumerate() {
+        assert_eq!(sensors.get_data(index).unwrap().analysis.raw, *val);
+    }
+
+    // Remapped channel order (see set_remap)
+    let mut remapped = Sensors::<3>::new();
+    remapped.set_sample_count(2).unwrap();
+    static REMAP: [usize; 3] = [2, 0, 1];
+    remapped.set_remap(Some(&REMAP)).unwrap();
+    for _ in 0..2 {
+        remapped.add_frame(&frame).unwrap();
+    }
+    for (pos, index) in REMAP.iter().enumerate() {
+        assert_eq!(remapped.get_data(*index).unwrap().analysis.raw, frame[pos]);
+    }
+}
+
+#[test]
+fn discard_partial() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    assert!(sensors.discard_partial(1).is_err());
+    sensors.set_sample_count(4).unwrap();
+
+    // Corrupted block
+    for _ in 0..3 {
+        assert!(sensors.add_runtime(0, 4000).unwrap().is_pending());
+    }
+    sensors.discard_partial(0).unwrap();
+
+    // Fresh block
+    for _ in 0..3 {
+        assert!(sensors.add_runtime(0, 1400).unwrap().is_pending());
+    }
+    assert_eq!(
+        sensors
+            .add_runtime(0, 1400)
+            .unwrap()
+            .analysis()
+            .unwrap()
+            .raw,
+        1400
+    );
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.stats.max, 1400);
+    assert_eq!(data.pending_samples(), 0);
+}
+
+#[test]
+fn flatlined() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    assert!(!SenseData::<0>::new().is_flatlined::<1>());
+
+    // Both sensors are calibrated, only one is noisy
+    let val = MIN_OK_THRESHOLD as u16 + 100;
+    for n in 0..32 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, val + n % 2)
+            .unwrap();
+    }
+    let stuck = sensors.get_data(0).unwrap();
+    let noisy = sensors.get_data(1).unwrap();
+    assert_eq!(stuck.cal, CalibrationStatus::MagnetDetected);
+    assert_eq!(noisy.cal, CalibrationStatus::MagnetDetected);
+    assert!(stuck.is_flatlined::<32>());
+    assert!(!stuck.is_flatlined::<33>());
+    assert!(!noisy.is_flatlined::<2>());
+
+    // Any change restarts the window
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val + 1)
+        .unwrap();
+    assert!(!sensors.get_data(0).unwrap().is_flatlined::<2>());
+}
+
+#[test]
+fn inverted() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    assert!(sensors.set_inverted(2, true).is_err());
+    sensors.set_inverted(1, true).unwrap();
+    assert!(!sensors.sensors[0].inverted());
+    assert!(sensors.sensors[1].inverted());
+
+    // Same waveform, sensor 1 is mounted upside down
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    let waveform = (0..8)
+        .map(|_| rest)
+        .chain((0..800).step_by(25).map(|offset| rest + offset))
+        .chain((0..800).step_by(40).rev().map(|offset| rest + offset));
+    for val in waveform {
+        let normal = sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap()
+            .analysis()
+            .map(|analysis| analysis.distance);
+        let inverted = sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                1,
+                ADC_MAX - val,
+            )
+            .unwrap()
+            .analysis()
+            .map(|analysis| analysis.distance);
+        assert_eq!(normal, inverted, "Reading: {}", val);
+    }
+
+    // Thresholds apply in the mirrored domain
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, rest)
+        .ok();
+    assert!(sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, rest)
+        .unwrap()
+        .not_calibrated()
+        .is_some());
+
+    // Bulk mask
+    sensors.set_inverted_mask(&[0b01]);
+    assert!(sensors.sensors[0].inverted());
+    assert!(!sensors.sensors[1].inverted());
+}
+
+#[test]
+fn bottom_out_calibration() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for index in 0..2 {
+        for _ in 0..2 {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, rest)
+                .unwrap();
+        }
+    }
+
+    // Pressing outside of bottom-out calibration does not confirm
+    for val in [rest + 900, rest + 900, rest, rest] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.bottom_out_progress(), 0);
+    assert_eq!(sensors.full_travel(0).unwrap(), 1000);
+
+    // Only press (and release) sensor 0, sensor 1 is lightly touched
+    sensors.start_bottom_out_calibration();
+    assert!(sensors.bottom_out_calibrating());
+    for val in [rest + 800, rest + 800, rest, rest] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    for val in [rest + 100, rest + 100, rest, rest] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, val)
+            .unwrap();
+    }
+    sensors.finish_bottom_out_calibration();
+    assert!(!sensors.bottom_out_calibrating());
+
+    assert_eq!(sensors.bottom_out_progress(), 1);
+    assert!(sensors.get_data(0).unwrap().bottom_out_confirmed());
+    assert!(!sensors.get_data(1).unwrap().bottom_out_confirmed());
+    assert_eq!(sensors.full_travel(0).unwrap(), 800);
+    assert_eq!(sensors.full_travel(1).unwrap(), 1000);
+    assert!(sensors.full_travel(2).is_err());
+
+    // Pressing further after calibration does not change the latched value
+    for _ in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 900)
+            .unwrap();
+    }
+    assert_eq!(sensors.full_travel(0).unwrap(), 800);
+}
+
+#[test]
+fn calibration_transition() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    let ok = MIN_OK_THRESHOLD as u16 + 2;
+    let weak = MIN_OK_THRESHOLD as u16 - 1;
+
+    // Transitions only reported when the status changes
+    let mut transitions: [Option<CalibrationTransition>; 4] = Default::default();
+    let mut count = 0;
+    for val in [ok, ok, ok, ok]
+        .into_iter()
+        .chain([weak; 8])
+        .chain([ok, ok, ok, ok])
+    {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        if let Some(transition) = sensors.sensors[0].transition() {
+            transitions[count] = Some(transition);
+            count += 1;
+        }
+    }
+    assert_eq!(
+        transitions,
+        [
+            Some(CalibrationTransition {
+                from: CalibrationStatus::NotReady,
+                to: CalibrationStatus::MagnetDetected,
+            }),
+            Some(CalibrationTransition {
+                from: CalibrationStatus::MagnetDetected,
+                to: CalibrationStatus::MagnetWrongPoleOrMissing,
+            }),
+            Some(CalibrationTransition {
+                from: CalibrationStatus::MagnetWrongPoleOrMissing,
+                to: CalibrationStatus::MagnetDetected,
+            }),
+            None,
+        ]
+    );
+}
+
+#[test]
+fn manufacturing_test() {
+    setup_logging_lite().ok();
+
+    const NOISE: usize = 8;
+    const TRAVEL: usize = 400;
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+
+    // Rest and pressed readings of each sensor
+    let sensors = [
+        (rest, rest + 600, TestResult::Pass),
+        (0, 0, TestResult::NoSensor),
+        (MIN_OK_THRESHOLD as u16 - 50, rest, TestResult::WrongPole),
+        (rest, rest - 500, TestResult::WrongPole),
+        (rest, rest + 200, TestResult::WeakMagnet),
+        (rest, rest + 600, TestResult::Noisy),
+    ];
+    let mut test = TestMode::<6>::new();
+    let mut normal = Sensors::<6>::new();
+    assert_eq!(test.phase(), TestPhase::Idle);
+    assert!(test.add(6, rest).is_err());
+
+    test.start_rest_capture();
+    for n in 0..16 {
+        for (index, (val, _, _)) in sensors.iter().enumerate() {
+            let val = if index == 5 { val + n % 2 * 20 } else { *val };
+            test.add(index, val).unwrap();
+            normal.add::<1>(index, val).unwrap();
+        }
+    }
+
+    test.start_press_capture();
+    assert_eq!(test.phase(), TestPhase::PressCapture);
+    for _ in 0..16 {
+        for (index, (_, val, _)) in sensors.iter().enumerate() {
+            test.add(index, *val).unwrap();
+        }
+    }
+
+    let results =
+        test.evaluate::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD, NOISE, TRAVEL>();
+    assert_eq!(test.phase(), TestPhase::Done);
+    for (index, (_, _, result)) in sensors.iter().enumerate() {
+        assert_eq!(results[index], *result, "Index: {}", index);
+    }
+
+    // Normal calibration state is untouched
+    assert_eq!(normal.get_data(0).unwrap().stats.min, rest);
+    assert!(test.add(0, rest).is_ok());
+}
+
+#[test]
+fn analysis_accessors() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+
+    // Press (averaging spreads the step over two windows)
+    let analysis = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 300)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.raw(), rest + 150);
+    assert_eq!(analysis.distance(), 150);
+    assert_eq!(analysis.velocity(), 150);
+    assert!(analysis.is_moving_down());
+    assert!(!analysis.is_moving_up());
+
+    let analysis = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 300)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.distance(), 300);
+    assert_eq!(analysis.velocity(), 150);
+    assert_eq!(analysis.acceleration(), 0);
+    assert_eq!(analysis.jerk(), -75);
+
+    // Holding
+    let analysis = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 300)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.distance(), 300);
+    assert!(!analysis.is_moving_down());
+    assert!(!analysis.is_moving_up());
+
+    // Release
+    let analysis = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.distance(), 150);
+    assert_eq!(analysis.velocity(), -150);
+    assert!(analysis.is_moving_up());
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance(), 150);
+}
+
+#[test]
+fn sensor_pair() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<3>::new();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for (index, offset) in [(0, 300), (1, 100)] {
+        for val in [rest, rest, rest + offset, rest + offset] {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+    }
+
+    assert_eq!(
+        SensorPair::new(0, 1, Fusion::Sum)
+            .distance(&sensors)
+            .unwrap(),
+        400
+    );
+    assert_eq!(
+        SensorPair::new(0, 1, Fusion::Difference)
+            .distance(&sensors)
+            .unwrap(),
+        200
+    );
+    assert_eq!(
+        SensorPair::new(0, 1, Fusion::Ratio)
+            .distance(&sensors)
+            .unwrap(),
+        768
+    );
+
+    // Common-mode drift cancels out
+    let pair = SensorPair::new(0, 1, Fusion::Difference);
+    assert_eq!(pair.fuse(300 + 50, 100 + 50), pair.fuse(300, 100));
+    assert_eq!(SensorPair::new(0, 1, Fusion::Ratio).fuse(0, 0), 0);
+    assert_eq!(
+        SensorPair::new(0, 1, Fusion::Sum).fuse(i16::MAX, 1),
+        i16::MAX
+    );
+
+    // Uncalibrated or invalid sensor
+    assert!(matches!(
+        SensorPair::new(0, 2, Fusion::Sum).distance(&sensors),
+        Err(SensorError::CalibrationError(_))
+    ));
+    assert!(matches!(
+        SensorPair::new(3, 0, Fusion::Sum).distance(&sensors),
+        Err(SensorError::InvalidSensor(3))
+    ));
+}
+
+#[test]
+fn decay_extrema() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    sensors.decay_extrema::<4>();
+    assert_eq!(sensors.sensors[0].stats.min, 0xFFFF);
+
+    // Noisy spike below the rest value, then settle
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    for val in [
+        rest - 40,
+        rest - 40,
+        rest,
+        rest,
+        rest + 20,
+        rest + 20,
+        rest,
+        rest,
+    ] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    let stats = &sensors.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest - 40, rest + 20));
+
+    // Extrema relax towards the current reading
+    sensors.decay_extrema::<4>();
+    let stats = &sensors.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest - 36, rest + 16));
+
+    // Never past the current reading (or each other)
+    for _ in 0..20 {
+        sensors.decay_extrema::<4>();
+    }
+    let stats = &sensors.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest, rest));
+    sensors.decay_extrema::<0xFFFF>();
+    let stats = &sensors.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest, rest));
+}
+
+#[test]
+fn calibration_debounce() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    assert_eq!(sensors.config().cal_debounce, 3);
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    let outlier = MIN_OK_THRESHOLD as u16 - 200;
+    for _ in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+
+    // Single outlier window, calibration (and the previous analysis) is held
+    assert!(sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, outlier)
+        .unwrap()
+        .is_pending());
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.cal, CalibrationStatus::MagnetDetected);
+    assert_eq!(data.analysis.raw, rest);
+    assert_eq!(data.stats.min, rest);
+    for _ in 0..4 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+
+    // Three in a row lose calibration
+    for _ in 0..2 {
+        assert!(sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, outlier)
+            .unwrap()
+            .is_pending());
+    }
+    assert!(sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, outlier)
+        .unwrap()
+        .not_calibrated()
+        .is_some());
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+
+    // Hardware faults are immediate
+    for _ in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .ok();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 0)
+        .ok();
+    assert!(sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 0)
+        .unwrap()
+        .not_calibrated()
+        .is_some());
+
+    // No debounce
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        cal_debounce: 1,
+        ..Default::default()
+    });
+    for _ in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    assert!(sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, outlier)
+        .unwrap()
+        .not_calibrated()
+        .is_some());
+}
+
+#[test]
+fn peak_velocity() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Fast press, slow hold, partial release
+    for val in [rest + 200, rest + 300, rest + 320, rest + 330, rest + 100] {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    // Distance 100, 250, 310, 325, 215 (averaged with the previous window)
+    assert_eq!(sensors.get_data(0).unwrap().peak_velocity(), 150);
+
+    // Fully released
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().peak_velocity(), 0);
+
+    // Light tap
+    for val in [rest + 20, rest + 40, rest + 60] {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().peak_velocity(), 20);
+}
+
+/// Heavier curve, half the distance of MODEL
+static HALF_MODEL: [i16; 4096] = {
+    let mut table = [0; 4096];
+    let mut i = 0;
+    while i < 4096 {
+        table[i] = MODEL[i] / 2;
+        i += 1;
+    }
+    table
+};
+
+#[test]
+fn lookup_table_index() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    static HALF: LookupTable = LookupTable {
+        table: &HALF_MODEL,
+        um_num: 8,
+        um_den: 1,
+    };
+    static SHORT: LookupTable = LookupTable {
+        table: &[0; 100],
+        um_num: 4,
+        um_den: 1,
+    };
+
+    // Rejected at configuration time
+    assert!(matches!(
+        sensors.set_lookup_table(0, 1),
+        Err(SensorError::InvalidLookupTable(1))
+    ));
+    assert!(sensors
+        .register_lookup_table(MAX_LOOKUP_TABLES as u8, &HALF)
+        .is_err());
+    assert!(sensors.register_lookup_table(1, &SHORT).is_err());
+
+    sensors.register_lookup_table(1, &HALF).unwrap();
+    assert!(sensors.set_lookup_table(2, 1).is_err());
+    sensors.set_lookup_table(1, 1).unwrap();
+    assert_eq!(sensors.sensors[0].table_index(), 0);
+    assert_eq!(sensors.sensors[1].table_index(), 1);
+    assert_eq!(sensors.lookup_table(0).unwrap(), &DEFAULT_LOOKUP_TABLE);
+    assert_eq!(sensors.lookup_table(1).unwrap(), &HALF);
+    assert!(sensors.lookup_table(2).is_err());
+
+    // Same press, different curves
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest, rest + 400, rest + 400] {
+        for index in 0..2 {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+    }
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance, 400);
+    assert_eq!(sensors.get_data(1).unwrap().analysis.distance, 200);
+    assert_eq!(sensors.travel_span(0).unwrap(), 400);
+    assert_eq!(sensors.travel_span(1).unwrap(), 200);
+}
+
+#[test]
+fn passthrough() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    static HALF: LookupTable = LookupTable {
+        table: &HALF_MODEL,
+        um_num: 8,
+        um_den: 1,
+    };
+    sensors.register_lookup_table(1, &HALF).unwrap();
+    sensors.set_lookup_table(0, 1).unwrap();
+    assert!(!sensors.sensors[0].passthrough());
+
+    // Mode change recalibrates
+    magnet_calibrate::<1>(&mut sensors);
+    sensors.set_passthrough(true);
+    assert!(sensors.sensors[0].passthrough());
+    assert_eq!(sensors.sensors[0].cal, CalibrationStatus::NotReady);
+    assert_eq!(sensors.sensors[0].table_index(), 1);
+
+    // Table is ignored, distance is raw - min
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest, rest + 400, rest + 400] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance, 400);
+
+    sensors.set_passthrough(false);
+    assert!(!sensors.sensors[0].passthrough());
+}
+
+#[test]
+fn socd() {
+    setup_logging_lite().ok();
+
+    // Snap tap: newest key wins, releasing it hands back to the held key
+    let mut pair = SocdPair::new(0, 1, SocdRule::LastWins);
+    assert_eq!(pair.resolve(true, false), (true, false));
+    assert_eq!(pair.resolve(true, true), (false, true));
+    assert_eq!(pair.resolve(true, true), (false, true));
+    assert_eq!(pair.resolve(true, false), (true, false));
+    assert_eq!(pair.resolve(true, true), (false, true));
+    assert_eq!(pair.resolve(false, true), (false, true));
+    assert_eq!(pair.resolve(true, true), (true, false));
+    assert_eq!(pair.resolve(false, false), (false, false));
+    // No ordering
+    assert_eq!(pair.resolve(true, true), (false, false));
+
+    let mut pair = SocdPair::new(0, 1, SocdRule::FirstWins);
+    assert_eq!(pair.resolve(false, true), (false, true));
+    assert_eq!(pair.resolve(true, true), (false, true));
+    assert_eq!(pair.resolve(true, false), (true, false));
+
+    let mut pair = SocdPair::new(0, 1, SocdRule::Neutral);
+    assert_eq!(pair.resolve(true, false), (true, false));
+    assert_eq!(pair.resolve(true, true), (false, false));
+
+    // From sensor distances
+    let mut sensors = Sensors::<2>::new();
+    let mut pair = SocdPair::new(0, 1, SocdRule::LastWins);
+    assert!(pair.resolve_sensors::<200, 2, 0, 1>(&sensors).is_err());
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for (index, offset) in [(0, 300), (1, 100)] {
+        for val in [rest, rest, rest + offset, rest + offset] {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+    }
+    assert_eq!(
+        pair.resolve_sensors::<200, 2, 0, 1>(&sensors).unwrap(),
+        (true, false)
+    );
+    for val in [rest + 300, rest + 300] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, val)
+            .unwrap();
+    }
+    assert_eq!(
+        pair.resolve_sensors::<200, 2, 0, 1>(&sensors).unwrap(),
+        (false, true)
+    );
+}
+
+#[test]
+fn comparator_thresholds() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<3>::new();
+    static HALF: LookupTable = LookupTable {
+        table: &HALF_MODEL,
+        um_num: 8,
+        um_den: 1,
+    };
+    sensors.register_lookup_table(1, &HALF).unwrap();
+    sensors.set_lookup_table(1, 1).unwrap();
+    sensors.set_inverted(2, true).unwrap();
+    assert!(matches!(
+        sensors.comparator_thresholds(0, 400, 50),
+        Err(SensorError::CalibrationError(_))
+    ));
+    assert!(matches!(
+        sensors.comparator_thresholds(3, 400, 50),
+        Err(SensorError::InvalidSensor(3))
+    ));
+
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest] {
+        for index in 0..2 {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                2,
+                ADC_MAX - val,
+            )
+            .unwrap();
+    }
+
+    // Linear table
+    assert_eq!(
+        sensors.comparator_thresholds(0, 400, 50).unwrap(),
+        ComparatorThresholds {
+            press: rest + 400,
+            release: rest + 350,
+            rising: true,
+        }
+    );
+
+    // Half scale table, first reading reaching -247 (-494 / 2, rounding towards 0)
+    let thresholds = sensors.comparator_thresholds(1, 100, 10).unwrap();
+    assert_eq!(thresholds.press, 1552);
+    assert_eq!(thresholds.release, 1532);
+
+    // Inverted sensor is pressed when the reading drops
+    assert_eq!(
+        sensors.comparator_thresholds(2, 400, 50).unwrap(),
+        ComparatorThresholds {
+            press: ADC_MAX - (rest + 400),
+            release: ADC_MAX - (rest + 350),
+            rising: false,
+        }
+    );
+
+    // Press threshold actuates, one count less does not
+    for val in [1551, 1551] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(1).unwrap().analysis.distance(), 99);
+    for val in [1552, 1552] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(1).unwrap().analysis.distance(), 100);
+
+    // Unreachable distance
+    assert_eq!(
+        sensors.comparator_thresholds(0, i16::MAX, 0).unwrap().press,
+        ADC_MAX
+    );
+}
+
+#[test]
+fn analog_value() {
+    setup_logging_lite().ok();
+
+    let mut data = SenseData::<0>::new();
+    for (distance, value) in [
+        (i16::MIN, 0),
+        (-50, 0),
+        (0, 0),
+        (100, 0),
+        (101, 0),
+        (102, 1),
+        (300, 127),
+        (500, 255),
+        (501, 255),
+        (i16::MAX, 255),
+    ] {
+        data.analysis.distance = distance;
+        assert_eq!(
+            data.analog_value::<100, 500>(),
+            value,
+            "Distance: {}",
+            distance
+        );
+    }
+
+    // Monotonic across the range
+    let mut prev = 0;
+    for distance in 0..600 {
+        data.analysis.distance = distance;
+        let value = data.analog_value::<100, 500>();
+        assert!(value >= prev, "Distance: {}", distance);
+        prev = value;
+    }
+}
+
+#[test]
+fn distance_deadzoned() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Jitter around rest
+    for val in [rest + 3, rest + 3, rest + 1, rest + 1] {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        let data = sensors.get_data(0).unwrap();
+        assert_ne!(data.analysis.distance, 0);
+        assert_eq!(data.distance_deadzoned::<5>(), 0);
+    }
+
+    // Presses pass through untouched
+    let mut data = SenseData::<0>::new();
+    for (distance, deadzoned) in [(4, 0), (5, 5), (6, 6), (300, 300), (-10, 0)] {
+        data.analysis.distance = distance;
+        assert_eq!(data.distance_deadzoned::<5>(), deadzoned);
+    }
+    // Negative distances never leak out
+    assert_eq!(data.distance_deadzoned::<0>(), 0);
+}
+
+#[test]
+fn calibration_recovery() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::with_config(SensorsConfig {
+        cal_recovery: Some(CalibrationRecovery {
+            margin: 20,
+            windows: 3,
+        }),
+        ..SensorsConfig::default()
+    });
+    let add = |sensors: &mut Sensors<2>, val| {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        sensors.get_data(0).unwrap().cal.clone()
+    };
+
+    // Lose calibration (magnet too weak)
+    for val in [1352, 1352, 1300, 1300, 1300] {
+        add(&mut sensors, val);
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+
+    // Noise right at the threshold does not flap
+    for val in [1350, 1349, 1350, 1350, 1350, 1369, 1350] {
+        assert_eq!(
+            add(&mut sensors, val),
+            CalibrationStatus::MagnetWrongPoleOrMissing,
+            "Reading: {}",
+            val
+        );
+    }
+
+    // Clear of the margin for enough windows
+    assert_eq!(
+        add(&mut sensors, 1370),
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(
+        add(&mut sensors, 1370),
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(add(&mut sensors, 1370), CalibrationStatus::MagnetDetected);
+
+    // Magnet too strong (first window is averaged with the previous one)
+    add(&mut sensors, MAX_OK_THRESHOLD as u16 + 100);
+    assert_eq!(
+        add(&mut sensors, MAX_OK_THRESHOLD as u16 + 100),
+        CalibrationStatus::SensorBroken
+    );
+    for _ in 0..5 {
+        assert_eq!(
+            add(&mut sensors, MAX_OK_THRESHOLD as u16),
+            CalibrationStatus::SensorBroken
+        );
+    }
+    for _ in 0..2 {
+        add(&mut sensors, MAX_OK_THRESHOLD as u16 - 20);
+    }
+    assert_eq!(
+        add(&mut sensors, MAX_OK_THRESHOLD as u16 - 20),
+        CalibrationStatus::MagnetDetected
+    );
+
+    // Recovers immediately without hysteresis
+    let mut sensors = Sensors::<2>::new();
+    for val in [1352, 1352, 1300, 1300, 1300] {
+        add(&mut sensors, val);
+    }
+    assert_eq!(add(&mut sensors, 1350), CalibrationStatus::MagnetDetected);
+}
+
+#[test]
+fn adc_resolution() {
+    setup_logging_lite().ok();
+
+    // 16-bit oversampled source, shifted down
+    let mut sensors = Sensors::<2>::with_config(SensorsConfig {
+        adc_bits: 16,
+        ..SensorsConfig::default()
+    });
+    for val in [1352 << 4, (1352 << 4) + 15, 1752 << 4, 1752 << 4] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.stats.min, 1352);
+    assert_eq!(data.analysis.distance(), 400);
+    // Full scale stays within the table
+    sensors.add::<1>(1, u16::MAX).unwrap();
+    assert_eq!(sensors.get_data(1).unwrap().analysis.raw(), ADC_MAX);
+
+    // 10-bit source, shifted up
+    let mut sensors = Sensors::<2>::with_config(SensorsConfig {
+        adc_bits: 10,
+        ..SensorsConfig::default()
+    });
+    for val in [338, 338, 438, 438] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.stats.min, 1352);
+    assert_eq!(data.analysis.distance(), 400);
+    // Out of range readings saturate instead of wrapping
+    sensors.add::<1>(1, 0x4000).unwrap();
+    assert_eq!(sensors.get_data(1).unwrap().analysis.raw(), ADC_MAX);
+
+    // Native resolution readings are also clamped to the table
+    let mut sensors = Sensors::<1>::new();
+    sensors.add::<1>(0, u16::MAX).unwrap();
+    assert_eq!(sensors.get_data(0).unwrap().analysis.raw(), ADC_MAX);
+}
+
+#[test]
+fn redundant() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<3>::new();
+    assert!(matches!(
+        sensors.redundant(0, 3),
+        Err(SensorError::InvalidSensor(3))
+    ));
+    // Neither calibrated
+    assert_eq!(sensors.redundant(0, 1).unwrap().distance(), 0);
+
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for (index, offset) in [(0, 300), (1, 100)] {
+        for val in [rest, rest, rest + offset, rest + offset] {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+    }
+    let analysis = sensors.redundant(0, 1).unwrap();
+    assert_eq!(analysis.distance(), 200);
+    assert_eq!(analysis.raw(), rest + 200);
+    // Sensor 2 never calibrated
+    assert_eq!(sensors.redundant(2, 0).unwrap().distance(), 300);
+
+    // Sensor 1 fails
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, 0)
+        .ok();
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, 0)
+        .ok();
+    assert_eq!(
+        sensors.get_data(1).unwrap().cal,
+        CalibrationStatus::SensorMissing
+    );
+    assert_eq!(sensors.redundant(0, 1).unwrap().distance(), 300);
+}
+
+#[test]
+fn linearize() {
+    setup_logging_lite().ok();
+
+    assert_eq!(super::linearize(0), MODEL[0]);
+    assert_eq!(super::linearize(2047), MODEL[2047]);
+    assert_eq!(super::linearize(ADC_MAX), MODEL[ADC_MAX as usize]);
+    assert_eq!(super::linearize(ADC_MAX + 1), MODEL[ADC_MAX as usize]);
+    assert_eq!(super::linearize(u16::MAX), MODEL[ADC_MAX as usize]);
+
+    assert_eq!(linearize_checked(ADC_MAX), Some(MODEL[ADC_MAX as usize]));
+    assert_eq!(linearize_checked(ADC_MAX + 1), None);
+
+    // Matches the distance calculated by the sensors
+    let mut sensors = Sensors::<1>::new();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest, rest + 400, rest + 400] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().analysis.distance(),
+        super::linearize(rest + 400) - super::linearize(rest)
+    );
+}
+
+static QUARTER_MODEL: [i16; 16384] = {
+    let mut table = [0; 16384];
+    let mut i = 0;
+    while i < 16384 {
+        table[i] = i as i16 - 8191;
+        i += 1;
+    }
+    table
+};
+
+#[test]
+fn oversample() {
+    setup_logging_lite().ok();
+
+    // Dithered input, level + quarter / 4 on average
+    fn window<const S: usize>(sensors: &mut Sensors<S>, index: usize, level: u16, quarter: u16) {
+        for n in 0..16 {
+            let val = if n % 4 < quarter { level + 1 } else { level };
+            sensors
+                .add_test::<16, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .ok();
+        }
+    }
+
+    let mut full = Sensors::<1>::new();
+    let mut over = Sensors::<1>::with_config(SensorsConfig {
+        filter: Filter::Oversample { bits: 2 },
+        ..SensorsConfig::default()
+    });
+    over.set_passthrough(true);
+    full.set_passthrough(true);
+    for quarter in 0..4 {
+        // Second window is no longer averaged with the previous level
+        for _ in 0..2 {
+            window(&mut full, 0, 1400, quarter);
+            window(&mut over, 0, 1400, quarter);
+        }
+        assert_eq!(full.get_data(0).unwrap().analysis.raw(), 1400);
+        assert_eq!(over.get_data(0).unwrap().analysis.raw(), 5600 + quarter);
+        assert_eq!(
+            over.get_data(0).unwrap().analysis.distance(),
+            quarter as i16
+        );
+    }
+    assert_eq!(full.get_data(0).unwrap().analysis.distance(), 0);
+    assert_eq!(over.get_data(0).unwrap().oversample_bits(), 2);
+
+    // Bits are limited to 16 bit readings
+    assert_eq!(
+        Filter::Oversample { bits: 8 }.oversample_bits(),
+        MAX_OVERSAMPLE_BITS
+    );
+    assert_eq!(Filter::Average.oversample_bits(), 0);
+
+    // 10-bit source scaled to 12 bits, then 2 extra bits
+    static QUARTER: LookupTable = LookupTable {
+        table: &QUARTER_MODEL,
+        um_num: 1,
+        um_den: 1,
+    };
+    let mut sensors = Sensors::<2>::with_config(SensorsConfig {
+        filter: Filter::Oversample { bits: 2 },
+        adc_bits: 10,
+        ..SensorsConfig::default()
+    });
+    sensors.register_lookup_table(1, &QUARTER).unwrap();
+    sensors.set_lookup_table(1, 1).unwrap();
+    for level in [338, 338, 388, 388] {
+        for index in 0..2 {
+            window(&mut sensors, index, level, 0);
+        }
+    }
+    for index in 0..2 {
+        let data = sensors.get_data(index).unwrap();
+        assert_eq!(data.cal, CalibrationStatus::MagnetDetected);
+        assert_eq!(data.stats.min, 1352 << 2);
+        assert_eq!(data.analysis.raw(), 1552 << 2);
+    }
+    // Default table is interpolated (12-bit table units)
+    assert_eq!(sensors.get_data(0).unwrap().analysis.distance(), 200);
+    // Larger table is indexed directly (quarter count units)
+    assert_eq!(sensors.get_data(1).unwrap().analysis.distance(), 800);
+}
+
+#[test]
+fn calibration_confirm() {
+    setup_logging_lite().ok();
+
+    fn add<const CAL_CONFIRM: usize>(
+        sensors: &mut Sensors<1, 0, CAL_CONFIRM>,
+        val: u16,
+    ) -> CalibrationStatus {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        sensors.sensors[0].cal.clone()
+    }
+    let mut sensors = Sensors::<1, 0, 3>::new();
+
+    // Ringing resets the count
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::NotReady);
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::NotReady);
+    assert_eq!(
+        add(&mut sensors, 1300),
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(
+        add(&mut sensors, 1352),
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(
+        add(&mut sensors, 1352),
+        CalibrationStatus::MagnetWrongPoleOrMissing
+    );
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::MagnetDetected);
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::MagnetDetected);
+
+    // Default promotes immediately, as does 0
+    let mut sensors = Sensors::<1>::new();
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::MagnetDetected);
+    let mut sensors = Sensors::<1, 0, 0>::new();
+    assert_eq!(add(&mut sensors, 1352), CalibrationStatus::MagnetDetected);
+}
+
+#[test]
+fn velocity_smoothing() {
+    setup_logging_lite().ok();
+
+    // Peak-to-peak (velocity, smoothed velocity, acceleration) of a jittery ramp
+    fn ramp(smoothing: Option<VelocitySmoothing>) -> (i16, i16, i16) {
+        let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+            velocity_smoothing: smoothing,
+            ..SensorsConfig::default()
+        });
+        let rest = MIN_OK_THRESHOLD as u16 + 2;
+        let mut range = [(i16::MAX, i16::MIN); 3];
+        for n in 0..64u16 {
+            let jitter = [0, 12, 3, 9][n as usize % 4];
+            let analysis = sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    0,
+                    rest + n * 8 + jitter,
+                )
+                .unwrap()
+                .analysis()
+                .unwrap();
+            // Let the EMA settle
+            if n < 16 {
+                continue;
+            }
+            let values = [
+                analysis.velocity(),
+                analysis.smoothed_velocity(),
+                analysis.acceleration(),
+            ];
+            for (range, value) in range.iter_mut().zip(values) {
+                *range = (range.0.min(value), range.1.max(value));
+            }
+        }
+        (
+            range[0].1 - range[0].0,
+            range[1].1 - range[1].0,
+            range[2].1 - range[2].0,
+        )
+    }
+
+    // Without smoothing they're the same
+    let (raw, smoothed, raw_accel) = ramp(None);
+    assert!(raw > 0);
+    assert_eq!(raw, smoothed);
+
+    let (raw2, smoothed, accel) = ramp(Some(VelocitySmoothing {
+        shift: 2,
+        derivatives: false,
+    }));
+    assert_eq!(raw, raw2);
+    assert!(smoothed * 2 < raw, "Raw: {}  Smoothed: {}", raw, smoothed);
+    assert_eq!(accel, raw_accel);
+
+    // Derivatives from the smoothed velocity
+    let (_, _, smoothed_accel) = ramp(Some(VelocitySmoothing {
+        shift: 2,
+        derivatives: true,
+    }));
+    assert!(
+        smoothed_accel * 2 < raw_accel,
+        "Raw: {}  Smoothed: {}",
+        raw_accel,
+        smoothed_accel
+    );
+}
+
+#[test]
+fn tap_detection() {
+    setup_logging_lite().ok();
+
+    fn press(readings: &[u16]) -> Option<TapEvent> {
+        let mut sensors = Sensors::<1>::new();
+        let mut tap = TapDetector::new();
+        let mut event = None;
+        let rest = MIN_OK_THRESHOLD as u16 + 2;
+        for offset in [0, 0].iter().chain(readings) {
+            if let Measurement::Ready(analysis) = sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    0,
+                    rest + offset,
+                )
+                .unwrap()
+            {
+                if let Some(new) = tap.add::<20, 200, 4>(analysis) {
+                    assert!(event.is_none());
+                    event = Some(new);
+                }
+            }
+        }
+        event
+    }
+
+    // Sharp tap, reverses right away
+    assert_eq!(
+        press(&[300, 300, 0, 0]),
+        Some(TapEvent {
+            depth: 300,
+            samples: 2
+        })
+    );
+
+    // Slow press to the same depth
+    let slow: [u16; 61] = core::array::from_fn(|n| (30 - (n as i32 - 30).abs()) as u16 * 10);
+    assert_eq!(press(&slow), None);
+
+    // Too shallow
+    assert_eq!(press(&[150, 150, 0, 0]), None);
+
+    // Sharp press that is held
+    assert_eq!(press(&[300, 300, 300, 300, 300, 300, 300, 0, 0]), None);
+}
+
+#[test]
+fn actuation_point() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    assert!(sensors.set_actuation(2, 100).is_err());
+    sensors.set_actuation(1, 350).unwrap();
+    assert_eq!(sensors.sensors[0].actuation_point::<250>(), 250);
+    assert_eq!(sensors.sensors[1].actuation_point::<250>(), 350);
+
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest, rest + 300, rest + 300] {
+        for index in 0..2 {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .unwrap();
+        }
+    }
+    assert!(sensors.sensors[0].actuated::<250>());
+    assert!(!sensors.sensors[1].actuated::<250>());
+
+    // Kept across recalibration, cleared with 0
+    sensors.sensors[1].recalibrate();
+    assert_eq!(sensors.sensors[1].actuation_point::<250>(), 350);
+    sensors.set_actuation(1, 0).unwrap();
+    assert_eq!(sensors.sensors[1].actuation_point::<250>(), 250);
+}
+
+#[cfg(feature = "press-count")]
+#[test]
+fn press_count() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    magnet_calibrate::<2>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    let poll = |sensors: &mut Sensors<2>, val: u16| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .ok();
+        }
+        sensors.sensors[0].poll_actuation::<200, 50>()
+    };
+
+    // Only press edges are counted, not while held (or wiggling within the hysteresis)
+    for _ in 0..3 {
+        assert_eq!(poll(&mut sensors, rest + 250), Some(ActuationEvent::Press));
+        for val in [rest + 250, rest + 175, rest + 250, rest + 300] {
+            assert_eq!(poll(&mut sensors, val), None);
+        }
+        assert_eq!(poll(&mut sensors, rest), Some(ActuationEvent::Release));
+        assert_eq!(poll(&mut sensors, rest), None);
+    }
+    assert_eq!(sensors.sensors[0].presses(), 3);
+
+    // Kept across recalibration
+    sensors.sensors[0].recalibrate();
+    assert_eq!(sensors.sensors[0].presses(), 3);
+
+    // Bulk read
+    let mut counts = [7; 3];
+    sensors.press_counts(&mut counts);
+    assert_eq!(counts, [3, 0, 0]);
+    let mut counts = [7; 1];
+    sensors.press_counts(&mut counts);
+    assert_eq!(counts, [3]);
+
+    // Saturates
+    sensors.sensors[1].presses = u32::MAX;
+    sensors.sensors[1].cal = CalibrationStatus::MagnetDetected;
+    sensors.sensors[1].analysis.distance = 300;
+    assert_eq!(
+        sensors.sensors[1].poll_actuation::<200, 50>(),
+        Some(ActuationEvent::Press)
+    );
+    assert_eq!(sensors.sensors[1].presses(), u32::MAX);
+
+    sensors.sensors[0].reset_presses();
+    assert_eq!(sensors.sensors[0].presses(), 0);
+    sensors.reset_all_presses();
+    sensors.press_counts(&mut counts);
+    assert_eq!(counts, [0]);
+}
+
+#[test]
+fn gain_recommendation() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    let add = |sensors: &mut Sensors<1>, val| {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        sensors.sensors[0].gain_recommendation()
+    };
+
+    // Calibration gain until a keypress has been observed
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for _ in 0..8 {
+        assert_eq!(add(&mut sensors, rest), GainRecommendation::hold());
+    }
+    let operating = GainRecommendation {
+        gain: GainStep::Increase,
+        offset: OffsetStep::Raise,
+    };
+    assert_eq!(add(&mut sensors, rest + 400), GainRecommendation::hold());
+    assert_eq!(add(&mut sensors, rest + 400), GainRecommendation::hold());
+    assert_eq!(add(&mut sensors, rest), GainRecommendation::hold());
+    assert_eq!(add(&mut sensors, rest), operating);
+
+    // Doesn't flip on a few outlier windows (max close to the rail)
+    let max = sensors.sensors[0].stats.max;
+    for _ in 0..GAIN_WINDOWS - 1 {
+        sensors.sensors[0].stats.max = ADC_MAX - 10;
+        assert_eq!(add(&mut sensors, rest), operating);
+    }
+    sensors.sensors[0].stats.max = max;
+    for _ in 0..GAIN_WINDOWS {
+        assert_eq!(add(&mut sensors, rest), operating);
+    }
+
+    // Losing calibration (clipping) recommends lowering the gain
+    for _ in 0..GAIN_WINDOWS - 1 {
+        assert_eq!(add(&mut sensors, 0xFFF), operating);
+    }
+    assert_eq!(
+        add(&mut sensors, 0xFFF),
+        GainRecommendation {
+            gain: GainStep::Decrease,
+            offset: OffsetStep::Hold,
+        }
+    );
+}
+
+#[test]
+fn broken_retry() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        broken_retry: Some(BrokenRetry {
+            cooldown: 4,
+            max_retries: 2,
+        }),
+        ..SensorsConfig::default()
+    });
+    let add = |sensors: &mut Sensors<1>, val| {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .ok();
+        sensors.sensors[0].cal.clone()
+    };
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    add(&mut sensors, rest);
+
+    // Glitch, reading returns to normal but the sensor cools down first
+    assert_eq!(add(&mut sensors, 0xFFF), CalibrationStatus::SensorBroken);
+    for _ in 0..3 {
+        assert_eq!(add(&mut sensors, rest), CalibrationStatus::SensorBroken);
+    }
+    assert_eq!(add(&mut sensors, rest), CalibrationStatus::MagnetDetected);
+    assert_eq!(sensors.sensors[0].broken_retries(), 1);
+
+    // Still broken after the second retry
+    assert_eq!(add(&mut sensors, 0xFFF), CalibrationStatus::SensorBroken);
+    for _ in 0..4 {
+        assert_eq!(add(&mut sensors, 0xFFF), CalibrationStatus::SensorBroken);
+    }
+    assert_eq!(sensors.sensors[0].broken_retries(), 2);
+
+    // Latched
+    for _ in 0..10 {
+        assert_eq!(add(&mut sensors, rest), CalibrationStatus::SensorBroken);
+    }
+    assert_eq!(sensors.sensors[0].broken_retries(), 2);
+
+    // Recalibration starts over
+    sensors.sensors[0].recalibrate();
+    assert_eq!(sensors.sensors[0].broken_retries(), 0);
+    assert_eq!(add(&mut sensors, rest), CalibrationStatus::MagnetDetected);
+}
+
+#[test]
+fn with_states() {
+    setup_logging_lite().ok();
+
+    assert!(matches!(
+        Sensors::<1>::with_states(&[
+            (CalibrationStatus::MagnetDetected, 1352, 1352),
+            (CalibrationStatus::MagnetDetected, 1352, 1352),
+        ]),
+        Err(SensorError::InvalidSensor(1))
+    ));
+
+    let mut sensors = Sensors::<3>::with_states(&[
+        (CalibrationStatus::MagnetDetected, 1352, 1752),
+        (CalibrationStatus::SensorBroken, 0, 0),
+    ])
+    .unwrap();
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.cal, CalibrationStatus::MagnetDetected);
+    assert_eq!((data.stats.min, data.stats.max), (1352, 1752));
+    assert_eq!(
+        sensors.get_data(1).unwrap().cal,
+        CalibrationStatus::SensorBroken
+    );
+    assert!(sensors.get_data(2).is_err());
+
+    // Ready for analysis right away
+    let analysis = sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 1552)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.distance(), 200);
+
+    // min is clamped, max follows
+    sensors
+        .restore_states(&[(CalibrationStatus::MagnetDetected, 0xFFFF, 0)])
+        .unwrap();
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!((data.stats.min, data.stats.max), (ADC_MAX, ADC_MAX));
+}
+
+#[test]
+fn distance_mm() {
+    setup_logging_lite().ok();
+
+    let mut analysis = SenseAnalysis::null();
+    // 1000 units at ~4 um per unit, in hundredths of a millimeter
+    analysis.distance = 1000;
+    assert_eq!(analysis.distance_mm(2, 5), 400);
+    // Rounding
+    analysis.distance = 3;
+    assert_eq!(analysis.distance_mm(1, 2), 2);
+    analysis.distance = -3;
+    assert_eq!(analysis.distance_mm(1, 2), -2);
+    analysis.distance = 1;
+    assert_eq!(analysis.distance_mm(1, 3), 0);
+    // Saturation and invalid scale
+    analysis.distance = i16::MAX;
+    assert_eq!(analysis.distance_mm(2, 1), i16::MAX);
+    assert_eq!(analysis.distance_mm(2, 0), 0);
+}
+
+#[test]
+fn jerk_threshold() {
+    setup_logging_lite().ok();
+
+    // Stored jerk is 3x the actual jerk
+    let mut analysis = SenseAnalysis::null();
+    analysis.jerk = 30;
+    assert!(analysis.jerk_exceeds::<9>()); // 30 > 27
+    assert!(!analysis.jerk_exceeds::<10>()); // 30 == 30
+    assert!(!analysis.jerk_exceeds::<11>());
+    analysis.jerk = -31;
+    assert!(analysis.jerk_exceeds::<10>());
+    analysis.jerk = i16::MIN;
+    assert!(analysis.jerk_exceeds::<10922>());
+    assert!(!analysis.jerk_exceeds::<10923>());
+
+    // Slow drift vs. impact
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    for offset in [2, 4, 6, 8] {
+        for _ in 0..2 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    0,
+                    rest + offset,
+                )
+                .unwrap();
+        }
+        assert!(!sensors.get_data(0).unwrap().analysis.jerk_exceeds::<10>());
+    }
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 400)
+            .unwrap();
+    }
+    assert!(sensors.get_data(0).unwrap().analysis.jerk_exceeds::<10>());
+}
+
+#[test]
+fn get_data_mut() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    assert!(sensors.get_data_mut(1).is_err());
+    // Available before calibration
+    assert!(sensors.get_data_mut(0).is_ok());
+
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Override the rest position mid-accumulation
+    assert!(sensors
+        .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 100)
+        .unwrap()
+        .is_pending());
+    sensors.get_data_mut(0).unwrap().set_rest(rest + 50);
+    let analysis = sensors
+        .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 100)
+        .unwrap()
+        .analysis()
+        .unwrap();
+    assert_eq!(analysis.distance, 0);
+
+    // Clamped to the lookup table
+    sensors.get_data_mut(0).unwrap().set_rest(0xFFFF);
+    assert_eq!(sensors.get_data(0).unwrap().stats.min, ADC_MAX);
+    assert_eq!(sensors.get_data(0).unwrap().stats.max, ADC_MAX);
+
+    // Stats are re-seeded from the current reading
+    sensors.get_data_mut(0).unwrap().reset_stats();
+    for _ in 0..2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    let stats = &sensors.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest + 50, rest + 50));
+
+    // Back to NotReady
+    sensors.get_data_mut(0).unwrap().recalibrate();
+    assert!(sensors.get_data(0).is_err());
+}
+
+#[test]
+fn calibrated_count() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<3>::new();
+    assert_eq!(sensors.len(), 3);
+    assert!(!sensors.is_empty());
+    assert_eq!(sensors.calibrated_count(), 0);
+    assert!(!sensors.all_calibrated());
+
+    // Partially calibrated
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for index in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, rest)
+            .unwrap();
+    }
+    assert_eq!(sensors.calibrated_count(), 2);
+    assert!(!sensors.all_calibrated());
+
+    // Faults are not calibrated
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(2, 0)
+        .ok();
+    assert_eq!(sensors.calibrated_count(), 2);
+    assert!(!sensors.all_calibrated());
+
+    sensors
+        .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(2, rest)
+        .unwrap();
+    assert_eq!(sensors.calibrated_count(), 3);
+    assert!(sensors.all_calibrated());
+
+    // Empty array
+    let empty = Sensors::<0>::new();
+    assert!(empty.is_empty());
+    assert_eq!(empty.calibrated_count(), 0);
+}
+
+/// Board-agnostic scan loop, returns the number of analyses produced
+fn scan_all<const S: usize>(sensors: &mut Sensors<S>, reading: u16) -> usize {
+    let mut analyses = 0;
+    for index in 0..sensors.len() {
+        if sensors
+            .add::<1>(index, reading)
+            .unwrap()
+            .analysis()
+            .is_some()
+        {
+            analyses += 1;
+        }
+    }
+    analyses
+}
+
+#[test]
+fn len() {
+    setup_logging_lite().ok();
+
+    let mut small = Sensors::<2>::new();
+    let mut large = Sensors::<115>::new();
+    assert_eq!(small.len(), 2);
+    assert_eq!(large.len(), 115);
+    assert!(!large.is_empty());
+    assert!(Sensors::<0>::new().is_empty());
+
+    // Bounded by len, every index is valid
+    assert_eq!(scan_all(&mut small, 1400), 2);
+    assert_eq!(scan_all(&mut large, 1400), 115);
+    assert!(large.add::<1>(large.len(), 1400).is_err());
+}
+
+#[test]
+fn history() {
+    setup_logging_lite().ok();
+
+    // Disabled by default, no memory used
+    let mut sensors = Sensors::<1>::new();
+    assert_eq!(
+        core::mem::size_of::<Sensors<8>>(),
+        core::mem::size_of::<Sensors<8, 0>>()
+    );
+    assert!(core::mem::size_of::<Sensors<8, 4>>() > core::mem::size_of::<Sensors<8>>());
+    sensors.add::<1>(0, 1400).unwrap();
+    assert_eq!(sensors.history(0).unwrap().count(), 0);
+    assert!(sensors.history(1).is_err());
+
+    // Partially filled
+    let mut sensors = Sensors::<2, 4>::new();
+    for val in [1400, 1500, 1600] {
+        sensors.add::<1>(0, val).unwrap();
+    }
+    assert!(sensors
+        .history(0)
+        .unwrap()
+        .map(|analysis| analysis.raw)
+        .eq([1400, 1450, 1550]));
+    assert_eq!(sensors.history(1).unwrap().count(), 0);
+
+    // Wrap-around keeps oldest to newest ordering
+    for val in [1700, 1800, 1900] {
+        sensors.add::<1>(0, val).unwrap();
+    }
+    assert!(sensors
+        .history(0)
+        .unwrap()
+        .map(|analysis| analysis.raw)
+        .eq([1550, 1650, 1750, 1850]));
+    assert_eq!(sensors.get_data(0).unwrap().stats.samples, 6);
+
+    // Non power of two depth, ordering is independent of the sample counter (which wraps)
+    let mut sensors = Sensors::<1, 3>::new();
+    sensors.get_data_mut(0).unwrap().stats.samples = u32::MAX - 1;
+    for val in [1400, 1400, 1500, 1600, 1700] {
+        sensors.add::<1>(0, val).unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().stats.samples, 3);
+    assert!(sensors
+        .history(0)
+        .unwrap()
+        .map(|analysis| analysis.raw)
+        .eq([1450, 1550, 1650]));
+
+    // Kept per sensor, cleared by recalibration
+    let data = sensors.get_data_mut(0).unwrap();
+    assert_eq!(data.history().count(), 3);
+    data.recalibrate();
+    assert_eq!(data.history().count(), 0);
+}
+
+#[test]
+fn recommended_frontend() {
+    setup_logging_lite().ok();
+
+    // Uncalibrated, unity gain
+    assert_eq!(
+        SenseData::<0>::new().recommended_frontend(),
+        FrontendConfig::default()
+    );
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // No travel observed yet
+    assert_eq!(
+        sensors.get_data(0).unwrap().recommended_frontend(),
+        FrontendConfig::default()
+    );
+
+    // Bottom out
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 1000)
+            .unwrap();
+    }
+    // 1000 counts of travel (+ 1/4 headroom) fills 4096 counts at ~3.28x gain
+    // centered at rest + 500
+    assert_eq!(
+        sensors.get_data(0).unwrap().recommended_frontend(),
+        FrontendConfig {
+            gain: 838,
+            offset: rest as i16 + 500 - 2048,
+        }
+    );
+
+    // The gain recommendation steps towards it (at least 2x, offset within GAIN_RAIL)
+    for _ in 0..GAIN_WINDOWS * 2 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest + 1000)
+            .unwrap();
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(
+        data.gain_recommendation(),
+        GainRecommendation {
+            gain: GainStep::Increase,
+            offset: OffsetStep::Hold,
+        }
+    );
+    // Only re-evaluated on calibration or min/max changes
+    assert_eq!(data.gain_pending, 0);
+    assert_eq!(
+        data.gain_inputs,
+        (
+            CalibrationStatus::MagnetDetected,
+            data.stats.min,
+            data.stats.max
+        )
+    );
+}
+
+#[test]
+fn extrema_decay() {
+    setup_logging_lite().ok();
+
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    let mut sensors = Sensors::<1>::new();
+    let mut decay = Sensors::<1>::with_config(SensorsConfig {
+        extrema_decay: Some(4),
+        ..Default::default()
+    });
+
+    // Calibrate, then inject a single outlier (mechanical shock)
+    for val in [rest, rest, rest - 40, rest, rest] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+        decay
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().stats.min, rest - 20);
+    assert_eq!(decay.get_data(0).unwrap().stats.min, rest - 20);
+
+    // Recovers by one count every 4 windows
+    for _ in 0..40 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+        decay
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().stats.min, rest - 20);
+    // 45 windows, 11 decays (the first is contradicted by the outlier)
+    assert_eq!(decay.get_data(0).unwrap().stats.min, rest - 10);
+    for _ in 0..100 {
+        decay
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    let stats = &decay.get_data(0).unwrap().stats;
+    assert_eq!((stats.min, stats.max), (rest, rest));
+}
+
+#[test]
+fn histogram() {
+    setup_logging_lite().ok();
+
+    assert_eq!(Histogram::<64>::bin_width(), 64);
+    assert_eq!(Histogram::<3>::bin_width(), 1366);
+
+    let mut histogram = Histogram::<4>::new();
+    for reading in [0, 1023, 1024, 2047, 3000, 4095, 0xFFFF] {
+        histogram.add(reading);
+    }
+    assert_eq!(histogram.bins(), &[2, 2, 1, 2]);
+    histogram.clear();
+    assert_eq!(histogram.bins(), &[0; 4]);
+}
+
+#[cfg(feature = "histogram")]
+#[test]
+fn sensor_histogram() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for val in [rest, rest, rest, rest + 1000] {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+    }
+    // Every raw reading is counted
+    let width = Histogram::<HISTOGRAM_BINS>::bin_width();
+    let bins = sensors.get_data(0).unwrap().histogram().bins();
+    assert_eq!(bins[(rest / width) as usize], 3);
+    assert_eq!(bins[((rest + 1000) / width) as usize], 1);
+    assert_eq!(bins.iter().map(|count| *count as u32).sum::<u32>(), 4);
+
+    sensors.get_data_mut(0).unwrap().clear_histogram();
+    assert!(sensors
+        .get_data(0)
+        .unwrap()
+        .histogram()
+        .bins()
+        .iter()
+        .all(|count| *count == 0));
+}
+
+#[test]
+fn samples_since_calibration() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    sensors.set_sample_count(2).unwrap();
+    magnet_calibrate::<1>(&mut sensors);
+    let calibrated = sensors.get_data(0).unwrap().samples_since_calibration();
+    assert!(calibrated > 0);
+
+    // Generic and runtime paths both count averaged samples
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    sensors.add::<2>(0, rest).unwrap();
+    assert_eq!(
+        sensors.get_data(0).unwrap().samples_since_calibration(),
+        calibrated
+    );
+    sensors.add::<2>(0, rest).unwrap();
+    sensors.add_runtime(0, rest).unwrap();
+    sensors.add_runtime(0, rest).unwrap();
+    assert_eq!(
+        sensors.get_data(0).unwrap().samples_since_calibration(),
+        calibrated + 2
+    );
+
+    // Saturates instead of wrapping
+    sensors.sensors[0].stats.samples_since_calibration = u32::MAX - 1;
+    for _ in 0..4 {
+        sensors.add::<2>(0, rest).unwrap();
+    }
+    assert_eq!(
+        sensors.get_data(0).unwrap().samples_since_calibration(),
+        u32::MAX
+    );
+
+    // Reset on the next transition into MagnetDetected, total samples keep counting
+    let total = sensors.get_data(0).unwrap().stats.samples;
+    sensors.sensors[0].recalibrate();
+    magnet_calibrate::<1>(&mut sensors);
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!(data.samples_since_calibration(), calibrated);
+    assert!(data.stats.samples > total);
+}
+
+#[test]
+fn idle_detection() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        idle: Some(IdleDetection {
+            epsilon: 2,
+            windows: 4,
+        }),
+        ..SensorsConfig::default()
+    });
+    let add = |sensors: &mut Sensors<1>, val, count| {
+        for _ in 0..count {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .unwrap();
+        }
+        sensors.get_data(0).unwrap().idle()
+    };
+    let mut mask = [0xFF; 2];
+
+    // Uncalibrated sensors are never idle
+    assert!(!SenseData::<0>::new().idle());
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Held at rest, jitter within epsilon
+    assert!(!add(&mut sensors, rest, 2));
+    assert!(add(&mut sensors, rest + 1, 8));
+    sensors.idle_mask(&mut mask);
+    assert_eq!(mask, [0x01, 0x00]);
+
+    // Movement clears the flag immediately
+    assert!(!add(&mut sensors, rest + 400, 2));
+    sensors.idle_mask(&mut mask);
+    assert_eq!(mask, [0x00, 0x00]);
+
+    // Held down but still is idle too
+    assert!(!add(&mut sensors, rest + 400, 4));
+    assert!(add(&mut sensors, rest + 400, 8));
+    assert!(sensors.get_data(0).unwrap().analysis.distance > 0);
+
+    // No idle configuration, never flagged
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    assert!(!add(&mut sensors, rest, 16));
+    assert!(sensors.get_data(0).unwrap().is_idle::<4>());
+
+    // Counted independently of is_idle, a wider epsilon doesn't make is_idle any looser
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        idle: Some(IdleDetection {
+            epsilon: 100,
+            windows: 4,
+        }),
+        ..SensorsConfig::default()
+    });
+    magnet_calibrate::<1>(&mut sensors);
+    for step in 0..8 {
+        add(&mut sensors, rest + 400 + step * 10, 2);
+    }
+    let data = sensors.get_data(0).unwrap();
+    assert!(data.analysis.velocity.unsigned_abs() > IDLE_VELOCITY);
+    assert!(data.idle());
+    assert_eq!(data.idle_samples(), 0);
+    assert!(!data.is_idle::<1>());
+}
+
+#[test]
+fn confidence() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    let add = |sensors: &mut Sensors<1>, val| {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+            .unwrap();
+        sensors.get_data(0).unwrap().confidence()
+    };
+
+    // Uncalibrated
+    assert_eq!(sensors.sensors[0].confidence(), 0);
+
+    // Fresh calibration gets the benefit of the doubt
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    let fresh = sensors.get_data(0).unwrap().confidence();
+    assert!(fresh > 120 && fresh <= 127, "{}", fresh);
+
+    // Never moving, confidence decays
+    let mut prev = fresh;
+    for _ in 0..CONFIDENCE_SAMPLES * 2 {
+        let confidence = add(&mut sensors, rest);
+        assert!(confidence <= prev);
+        prev = confidence;
+    }
+    assert_eq!(prev, 0);
+
+    // Partial press (settled past the averaging)
+    for _ in 0..3 {
+        add(&mut sensors, rest + CONFIDENCE_SPREAD / 2);
+    }
+    let half = add(&mut sensors, rest + CONFIDENCE_SPREAD / 2);
+    assert!((125..=128).contains(&half), "{}", half);
+
+    // Full press
+    for _ in 0..3 {
+        add(&mut sensors, rest + CONFIDENCE_SPREAD);
+    }
+    assert_eq!(add(&mut sensors, rest + CONFIDENCE_SPREAD), 255);
+    assert_eq!(add(&mut sensors, rest), 255);
+}
+
+#[test]
+fn poll_actuation() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    let poll = |sensors: &mut Sensors<1>, val: u16| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .ok();
+        }
+        sensors.sensors[0].poll_actuation::<200, 50>()
+    };
+
+    // Only transitions are reported
+    assert_eq!(poll(&mut sensors, rest), None);
+    assert_eq!(poll(&mut sensors, rest + 250), Some(ActuationEvent::Press));
+    assert_eq!(poll(&mut sensors, rest + 250), None);
+    assert!(sensors.sensors[0].is_pressed());
+
+    // Hysteresis
+    assert_eq!(poll(&mut sensors, rest + 175), None);
+    assert_eq!(poll(&mut sensors, rest + 200), None);
+    assert_eq!(
+        poll(&mut sensors, rest + 140),
+        Some(ActuationEvent::Release)
+    );
+    assert_eq!(poll(&mut sensors, rest + 175), None);
+    assert_eq!(poll(&mut sensors, rest + 200), Some(ActuationEvent::Press));
+
+    // Losing calibration while pressed releases the key
+    assert_eq!(
+        poll(&mut sensors, NO_SENSOR_THRESHOLD as u16 - 100),
+        Some(ActuationEvent::Release)
+    );
+    assert_eq!(poll(&mut sensors, NO_SENSOR_THRESHOLD as u16 - 100), None);
+    assert!(!sensors.sensors[0].is_pressed());
+
+    // Recalibrating while pressed releases the key
+    magnet_calibrate::<1>(&mut sensors);
+    assert_eq!(poll(&mut sensors, rest + 250), Some(ActuationEvent::Press));
+    sensors.sensors[0].recalibrate();
+    assert_eq!(
+        sensors.sensors[0].poll_actuation::<200, 50>(),
+        Some(ActuationEvent::Release)
+    );
+}
+
+/// Feed the same readings through the ISR handoff and the direct add path
+fn handoff_pair() -> (Sensors<2>, Sensors<2>, SampleProducer<2>, SampleHandoff<2>) {
+    let mut split = Sensors::<2>::new();
+    let mut direct = Sensors::<2>::new();
+    split.set_sample_count(2).unwrap();
+    direct.set_sample_count(2).unwrap();
+    split.set_inverted(1, true).unwrap();
+    direct.set_inverted(1, true).unwrap();
+    let producer = SampleProducer::<2>::new(2, split.config()).unwrap();
+    (split, direct, producer, SampleHandoff::new())
+}
+
+#[test]
+fn sample_handoff() {
+    setup_logging_lite().ok();
+
+    let (mut split, mut direct, mut producer, handoff) = handoff_pair();
+    assert!(SampleProducer::<2>::new(3, split.config()).is_err());
+    assert!(producer.add(2, 0, &handoff).is_err());
+
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    let readings = [
+        rest,
+        rest,
+        rest + 100,
+        rest + 300,
+        rest + 300,
+        rest + 50,
+        rest,
+    ];
+    for reading in readings {
+        for (index, reading) in [(0, reading), (1, ADC_MAX - reading)] {
+            let ready = producer.add(index, reading, &handoff).unwrap();
+            assert_eq!(
+                split.process_test::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    &handoff
+                ),
+                ready as usize
+            );
+            let expected = direct
+                .add_test_runtime::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    index, reading,
+                )
+                .unwrap()
+                .analysis()
+                .cloned();
+            assert_eq!(ready, expected.is_some());
+
+            let (a, b) = (&split.sensors[index], &direct.sensors[index]);
+            assert_eq!(a.cal, b.cal);
+            if let Some(expected) = expected {
+                // Mirroring the average rather than each reading is within a count
+                assert!(a.analysis.raw.abs_diff(expected.raw) <= 1);
+                assert!(a.analysis.distance.abs_diff(expected.distance) <= 1);
+            }
+        }
+    }
+
+    // Nothing new to process
+    assert_eq!(split.process(&handoff), 0);
+}
+
+#[test]
+fn sample_handoff_overrun() {
+    setup_logging_lite().ok();
+
+    let (mut split, _, mut producer, handoff) = handoff_pair();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for _ in 0..2 {
+        producer.add(0, rest, &handoff).unwrap();
+    }
+    split.process_test::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(&handoff);
+
+    // Latest window wins (averaged with the previous window, as with add)
+    for reading in [rest + 100, rest + 100, rest + 200, rest + 200] {
+        producer.add(0, reading, &handoff).unwrap();
+    }
+    assert_eq!(split.process(&handoff), 1);
+    assert_eq!(split.sensors[0].analysis.raw, rest + 150);
+    assert_eq!(split.process(&handoff), 0);
+}
+
+#[test]
+fn sample_handoff_reset() {
+    setup_logging_lite().ok();
+
+    let (mut split, _, mut producer, handoff) = handoff_pair();
+    let missing = NO_SENSOR_THRESHOLD as u16 - 100;
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for _ in 0..2 {
+        producer.add(0, missing, &handoff).unwrap();
+    }
+    assert_eq!(
+        split.process_test::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(&handoff),
+        0
+    );
+    assert_eq!(split.sensors[0].cal, CalibrationStatus::SensorMissing);
+
+    // Producer discards the in-progress accumulation on its next reading
+    producer.add(0, rest, &handoff).unwrap();
+    assert!(producer.add(0, rest, &handoff).unwrap());
+    assert_eq!(
+        split.process_test::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(&handoff),
+        1
+    );
+    assert_eq!(split.sensors[0].cal, CalibrationStatus::MagnetDetected);
+    assert_eq!(split.sensors[0].analysis.raw, rest);
+}
+
+#[test]
+fn sample_handoff_threaded() {
+    extern crate std;
+
+    let (mut split, _, mut producer, handoff) = handoff_pair();
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            // Constant pairs, any torn or mixed value would average to something else
+            for i in 0..20000u16 {
+                let reading = rest + (i / 2) % 200;
+                producer.add(0, reading, &handoff).unwrap();
+            }
+        });
+        for _ in 0..20000 {
+            if split
+                .process_test::<MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(&handoff)
+                > 0
+            {
+                let raw = split.sensors[0].analysis.raw;
+                assert!((rest..rest + 200).contains(&raw), "{}", raw);
+            }
+        }
+    });
+}
+
+#[test]
+fn rest_boundary() {
+    setup_logging_lite().ok();
+
+    let settle = |sensors: &mut Sensors<1>, val: u16| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .ok();
+        }
+        sensors.sensors[0].analysis.clone()
+    };
+
+    // Rest well above MIN_OK_THRESHOLD, so floating above it stays calibrated
+    let rest = MIN_OK_THRESHOLD as u16 + 150;
+    let calibrate = |sensors: &mut Sensors<1>| {
+        settle(sensors, rest);
+        sensors.sensors[0].set_rest(rest);
+    };
+
+    // Track (default), floating above rest moves the rest position
+    let mut sensors = Sensors::<1>::new();
+    calibrate(&mut sensors);
+    assert_eq!(settle(&mut sensors, rest - 50).distance(), 0);
+    assert!(!sensors.sensors[0].above_rest());
+    // Back at the original rest reads as a partial press
+    assert_eq!(settle(&mut sensors, rest).distance(), 50);
+
+    // Signed, rest position is kept
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        rest: RestBoundary::Signed,
+        ..SensorsConfig::default()
+    });
+    calibrate(&mut sensors);
+
+    // Crossing the rest position in both directions, velocity is continuous
+    let mut prev = settle(&mut sensors, rest + 30);
+    assert_eq!(prev.distance(), 30);
+    for val in [rest - 20, rest - 50, rest + 10, rest - 40, rest + 30] {
+        for _ in 0..4 {
+            let analysis = sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .unwrap()
+                .analysis()
+                .cloned();
+            let analysis = match analysis {
+                Some(analysis) => analysis,
+                None => continue,
+            };
+            assert_eq!(
+                analysis.velocity() as i32,
+                analysis.distance() as i32 - prev.distance() as i32
+            );
+            prev = analysis;
+        }
+        assert_eq!(prev.distance() as i32, val as i32 - rest as i32);
+        assert_eq!(sensors.sensors[0].above_rest(), val < rest);
+        assert_eq!(sensors.get_data(0).unwrap().stats.min, rest);
+    }
+
+    // Baseline drift follows the signed distance downwards
+    settle(&mut sensors, rest - 10);
+    sensors.sensors[0].decay_extrema::<4>();
+    assert_eq!(sensors.sensors[0].stats.min, rest - 4);
+    sensors.sensors[0].decay_extrema::<8>();
+    assert_eq!(sensors.sensors[0].stats.min, rest - 10);
+    assert_eq!(settle(&mut sensors, rest - 10).distance(), 0);
+
+    // Rest position is re-learned after recalibrating
+    sensors.sensors[0].recalibrate();
+    settle(&mut sensors, rest + 20);
+    assert_eq!(sensors.get_data(0).unwrap().stats.min, rest + 20);
+}
+
+#[test]
+fn table_bounds() {
+    setup_logging_lite().ok();
+
+    // MXOK larger than the lookup table
+    const MXOK: usize = ADC_MAX as usize + 1000;
+    let add = |sensors: &mut Sensors<1>, val: u16| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MXOK, NO_SENSOR_THRESHOLD>(0, val)
+                .ok();
+        }
+        sensors.sensors[0].cal.clone()
+    };
+    let mut sensors = Sensors::<1>::new();
+    assert_eq!(
+        add(&mut sensors, MIN_OK_THRESHOLD as u16 + 2),
+        CalibrationStatus::MagnetDetected
+    );
+    assert_eq!(
+        add(&mut sensors, ADC_MAX - 1),
+        CalibrationStatus::MagnetDetected
+    );
+
+    // Beyond the table (saturates at ADC_MAX) is broken rather than a lookup
+    assert_eq!(
+        add(&mut sensors, ADC_MAX + 1000),
+        CalibrationStatus::SensorBroken
+    );
+    assert_eq!(add(&mut sensors, ADC_MAX), CalibrationStatus::SensorBroken);
+
+    // Thresholds that don't fit in a u16 don't wrap (MXOK as u16 would be 1000)
+    let mut sensors = Sensors::<1>::new();
+    for _ in 0..4 {
+        sensors
+            .add_test::<2, MIN_OK_THRESHOLD, { u16::MAX as usize + 1001 }, NO_SENSOR_THRESHOLD>(
+                0,
+                MIN_OK_THRESHOLD as u16 + 2,
+            )
+            .ok();
+    }
+    assert_eq!(sensors.sensors[0].cal, CalibrationStatus::MagnetDetected);
+
+    // Readings and rest position past the end of the table (e.g. written directly) are clamped
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    sensors.sensors[0].stats.min = ADC_MAX + 1000;
+    let analysis = SenseAnalysis::new(ADC_MAX + 1000, &sensors.sensors[0], &DEFAULT_LOOKUP_TABLE);
+    assert_eq!(analysis.distance(), 0);
+    let analysis = SenseAnalysis::new(ADC_MAX - 100, &sensors.sensors[0], &DEFAULT_LOOKUP_TABLE);
+    assert_eq!(analysis.distance(), -100);
+}
+
+#[cfg(feature = "f32")]
+#[test]
+fn f32_analysis() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1>::new();
+    magnet_calibrate::<1>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+
+    // Press and release waveform, at native and oversampled resolution
+    for bits in [0, 2] {
+        let mut int = sensors.sensors[0].clone();
+        int.oversample = bits;
+        int.stats.min = rest << bits;
+        let mut float = int.clone();
+        for i in 0..200u16 {
+            let travel = if i < 100 { i * 7 } else { (200 - i) * 7 };
+            let raw = ((rest + travel) << bits) + i % 3;
+            let a = SenseAnalysis::new_int(raw, &int, &DEFAULT_LOOKUP_TABLE);
+            let b = SenseAnalysis::new_f32(raw, &float, &DEFAULT_LOOKUP_TABLE);
+            for (x, y, tolerance) in [
+                (a.distance, b.distance, 1),
+                (a.velocity, b.velocity, 1),
+                (a.acceleration, b.acceleration, 1),
+                (a.jerk, b.jerk, 2),
+            ] {
+                assert!(
+                    x.abs_diff(y) <= tolerance,
+                    "Sample {} ({} bits): {:?} {:?}",
+                    i,
+                    bits,
+                    a,
+                    b
+                );
+            }
+            int.analysis = a;
+            float.analysis = b;
+        }
+    }
+}
+
+#[test]
+fn travel_coverage() {
+    setup_logging_lite().ok();
+
+    const TRAVEL: usize = 600;
+    let mut sensors = Sensors::<3>::new();
+    let add = |sensors: &mut Sensors<3>, index, val| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .ok();
+        }
+        sensors.travel_span(index).unwrap()
+    };
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    let mut mask = [0xFF; 1];
+
+    // Sensor 2 is missing and never prompted for
+    assert_eq!(add(&mut sensors, 0, rest), 0);
+    assert_eq!(add(&mut sensors, 1, rest), 0);
+    add(&mut sensors, 2, NO_SENSOR_THRESHOLD as u16 - 100);
+    sensors.travel_pending_mask::<TRAVEL>(&mut mask);
+    assert_eq!(mask, [0b011]);
+
+    // Partial press, span is the deepest point so far
+    assert_eq!(add(&mut sensors, 0, rest + 300), 300);
+    assert_eq!(add(&mut sensors, 0, rest + 100), 300);
+    assert!(!sensors.sensors[0].travel_covered::<TRAVEL>(&DEFAULT_LOOKUP_TABLE));
+
+    // Full press
+    assert_eq!(add(&mut sensors, 0, rest + 650), 650);
+    assert_eq!(add(&mut sensors, 0, rest), 650);
+    assert!(sensors.sensors[0].travel_covered::<TRAVEL>(&DEFAULT_LOOKUP_TABLE));
+    sensors.travel_pending_mask::<TRAVEL>(&mut mask);
+    assert_eq!(mask, [0b010]);
+
+    // Recalibration starts over
+    sensors.sensors[0].recalibrate();
+    assert_eq!(sensors.travel_span(0).unwrap(), 0);
+    assert!(!sensors.sensors[0].travel_covered::<TRAVEL>(&DEFAULT_LOOKUP_TABLE));
+}
+
+#[test]
+fn differential_pair() {
+    setup_logging_lite().ok();
+
+    let pair = || {
+        let mut sensors = Sensors::<3>::new();
+        sensors.set_differential(0, 1).unwrap();
+        sensors
+    };
+    let mut clean = pair();
+    let mut drift = pair();
+    assert_eq!(clean.differential_partner(0), Some((1, true)));
+    assert_eq!(clean.differential_partner(1), Some((0, false)));
+    assert_eq!(clean.differential_partner(2), None);
+    assert!(matches!(
+        clean.set_differential(1, 2),
+        Err(SensorError::InvalidPair(1, 2))
+    ));
+    assert!(matches!(
+        clean.set_differential(2, 2),
+        Err(SensorError::InvalidPair(2, 2))
+    ));
+    assert!(matches!(
+        clean.set_differential(2, 3),
+        Err(SensorError::InvalidSensor(3))
+    ));
+
+    // Top sensor approaches the magnet while the bottom sensor moves away
+    let add = |sensors: &mut Sensors<3>, travel: u16, offset: u16| {
+        let (top, bottom) = (1600 + travel + offset, 1900 - travel + offset);
+        let mut analysis = None;
+        for _ in 0..2 {
+            let secondary = sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, bottom)
+                .unwrap();
+            assert!(!matches!(secondary, Measurement::Ready(_)));
+            analysis = sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, top)
+                .unwrap()
+                .analysis()
+                .cloned();
+            // Unpaired sensor, sees the same common-mode field as the pair
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(2, top)
+                .ok();
+        }
+        analysis
+    };
+
+    // Calibrate at rest
+    for _ in 0..4 {
+        add(&mut clean, 0, 0);
+        add(&mut drift, 0, 0);
+    }
+    assert_eq!(
+        clean.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+
+    // Common-mode offset (e.g. ambient field) ramps in during a press and release
+    let travel = [0, 0, 50, 150, 300, 300, 200, 100, 0, 0, 0];
+    for (i, travel) in travel.into_iter().enumerate() {
+        let offset = (i as u16 * 20).min(150);
+        let a = add(&mut clean, travel, 0).unwrap();
+        let b = add(&mut drift, travel, offset).unwrap();
+        assert_eq!(a.distance(), b.distance(), "Sample {}", i);
+        assert_eq!(a.velocity(), b.velocity(), "Sample {}", i);
+    }
+    assert_eq!(drift.get_data(0).unwrap().analysis.distance(), 0);
+    // Unpaired sensor follows the offset
+    assert_eq!(drift.get_data(2).unwrap().analysis.distance(), 150);
+
+    // Either sensor missing fails the pair
+    for _ in 0..4 {
+        drift
+            .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1, 0)
+            .ok();
+    }
+    assert_eq!(drift.sensors[1].cal, CalibrationStatus::SensorMissing);
+    drift
+        .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 1600)
+        .unwrap();
+    let primary = drift
+        .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, 1600)
+        .unwrap();
+    assert!(matches!(
+        primary,
+        Measurement::NotCalibrated(CalibrationStatus::SensorMissing)
+    ));
+    assert_eq!(
+        drift.get_data(2).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+}
+
+#[test]
+fn reset_stats() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<2>::new();
+    assert!(sensors.reset_stats(2).is_err());
+    magnet_calibrate::<2>(&mut sensors);
+    let rest = sensors.get_data(0).unwrap().stats.min;
+    let poll = |sensors: &mut Sensors<2>, val: u16| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .unwrap();
+        }
+        sensors.sensors[0].poll_actuation::<200, 50>()
+    };
+
+    // Measurement session
+    assert_eq!(poll(&mut sensors, rest + 300), Some(ActuationEvent::Press));
+    assert_eq!(poll(&mut sensors, rest), Some(ActuationEvent::Release));
+    let samples = sensors.sensors[0].stats.samples;
+    assert_eq!(sensors.sensors[0].stats.max, rest + 300);
+
+    // Re-seeded from the current (rest) reading, calibration and samples kept
+    sensors.reset_stats(0).unwrap();
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!((data.stats.min, data.stats.max), (rest, rest));
+    assert_eq!(data.stats.samples, samples);
+    assert_eq!(data.analysis.distance, 0);
+
+    // Actuation keeps working
+    assert_eq!(poll(&mut sensors, rest + 300), Some(ActuationEvent::Press));
+    assert_eq!(sensors.get_data(0).unwrap().stats.max, rest + 300);
+    assert_eq!(poll(&mut sensors, rest), Some(ActuationEvent::Release));
+    assert_eq!(sensors.get_data(0).unwrap().stats.samples, samples + 4);
+
+    // All sensors, uncalibrated sensors re-learn from the next sample
+    sensors.sensors[1].stats.max = rest;
+    sensors.reset_all_stats();
+    let data = sensors.get_data(0).unwrap();
+    assert_eq!((data.stats.min, data.stats.max), (rest, rest));
+    let stats = &sensors.sensors[1].stats;
+    assert_eq!((stats.min, stats.max), (0xFFFF, 0));
+}
+
+#[test]
+fn normalization() {
+    setup_logging_lite().ok();
+
+    let add = |sensors: &mut Sensors<5>, index, val| {
+        for _ in 0..4 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(index, val)
+                .ok();
+        }
+    };
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+
+    // Disabled by default
+    let mut sensors = Sensors::<5>::new();
+    add(&mut sensors, 0, rest);
+    add(&mut sensors, 0, rest + 500);
+    assert_eq!(sensors.normalize(), None);
+
+    let mut sensors = Sensors::<5>::with_config(SensorsConfig {
+        normalization: Some(Normalization { tolerance: 20 }),
+        ..SensorsConfig::default()
+    });
+    for index in 0..4 {
+        add(&mut sensors, index, rest);
+    }
+    // No key pressed yet
+    assert_eq!(sensors.normalize(), None);
+
+    // Sensor 3 has a short span (e.g. poorly placed magnet), sensor 4 is missing
+    for (index, span) in [500, 550, 450, 200].into_iter().enumerate() {
+        add(&mut sensors, index, rest + span);
+        add(&mut sensors, index, rest + 250);
+    }
+    assert_eq!(sensors.normalize(), Some(475));
+    let mut mask = [0; 1];
+    sensors.span_outlier_mask(&mut mask);
+    assert_eq!(mask, [0b1000]);
+
+    // Identical travel, shared scale
+    let distances: [i16; 4] =
+        core::array::from_fn(|index| sensors.get_data(index).unwrap().analysis.distance);
+    assert_eq!(distances, [250, 250, 250, 250]);
+    let normalized: [i16; 4] =
+        core::array::from_fn(|index| sensors.normalized_distance(index).unwrap());
+    assert_eq!(normalized, [237, 215, 263, 250]);
+    assert!(sensors.normalized_distance(4).is_err());
+
+    // Outliers barely move the scale
+    add(&mut sensors, 3, rest + 1100);
+    assert_eq!(sensors.normalize(), Some(525));
+    sensors.span_outlier_mask(&mut mask);
+    assert_eq!(mask, [0b1000]);
+}
+
+#[test]
+fn bottom_deadzone() {
+    setup_logging_lite().ok();
+
+    let mut sensors = [
+        Sensors::<1>::new(),
+        Sensors::<1>::with_config(SensorsConfig {
+            bottom_deadzone: 16,
+            ..SensorsConfig::default()
+        }),
+    ];
+    let add = |sensors: &mut [Sensors<1>; 2], val: u16| {
+        for sensors in sensors.iter_mut() {
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .unwrap();
+        }
+        // Analysis and the normalized distance (without a scale)
+        let data = |sensors: &Sensors<1>| {
+            let data = sensors.get_data(0).unwrap();
+            assert_eq!(
+                sensors.normalized_distance(0).unwrap(),
+                data.distance_bottom_clamped(
+                    sensors.lookup_table(0).unwrap(),
+                    sensors.config().bottom_deadzone
+                )
+            );
+            (
+                data.analysis.clone(),
+                sensors.normalized_distance(0).unwrap(),
+            )
+        };
+        [data(&sensors[0]), data(&sensors[1])]
+    };
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    for _ in 0..4 {
+        add(&mut sensors, rest);
+    }
+
+    // Press, velocity is kept entering the deadzone
+    for offset in (100..=600).step_by(100) {
+        for _ in 0..2 {
+            let [default, deadzone] = add(&mut sensors, rest + offset);
+            assert_eq!(default.0.velocity(), deadzone.0.velocity());
+        }
+    }
+    let span = sensors[1].travel_span(0).unwrap();
+    assert_eq!(span, sensors[0].travel_span(0).unwrap());
+
+    // Wiggle within the deadzone
+    let mut moved = false;
+    for offset in [592, 600, 588, 600, 594, 586, 600] {
+        let [default, deadzone] = add(&mut sensors, rest + offset);
+        moved |= default.0.is_moving_up();
+        assert_eq!(default.1, default.0.distance());
+        assert_eq!(deadzone.0.distance(), default.0.distance());
+        assert_eq!(deadzone.0.velocity(), 0);
+        assert_eq!(deadzone.0.smoothed_velocity(), 0);
+        assert_eq!(deadzone.1, span);
+    }
+    assert!(moved);
+
+    // Release, leaving the deadzone
+    let [default, deadzone] = add(&mut sensors, rest + 400);
+    assert_eq!(default.0.velocity(), deadzone.0.velocity());
+    assert!(deadzone.0.is_moving_up());
+    assert_eq!(deadzone.1, deadzone.0.distance());
+}
+
+#[test]
+fn calibration_quality() {
+    setup_logging_lite().ok();
+
+    let quality = |rest: u16| {
+        let mut sensors = Sensors::<2>::with_config(SensorsConfig {
+            default_travel: 600,
+            ..SensorsConfig::default()
+        });
+        for val in [rest, rest + 600, rest] {
+            for _ in 0..4 {
+                sensors
+                    .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                    .unwrap();
+            }
+        }
+        sensors
+    };
+    let get = |sensors: &Sensors<2>| {
+        sensors
+            .calibration_quality::<MIN_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0)
+            .unwrap()
+    };
+
+    // Healthy
+    let mut healthy = quality(MIN_OK_THRESHOLD as u16 + 400);
+    let score = get(&healthy);
+    assert_eq!(
+        score,
+        CalibrationQuality {
+            travel: 255,
+            noise: 255,
+            margin: 255
+        }
+    );
+    assert_eq!(score.score(), 255);
+
+    // Marginal magnet, rest just above the too-weak threshold
+    let marginal = get(&quality(MIN_OK_THRESHOLD as u16 + 20));
+    assert_eq!(marginal.travel, 255);
+    assert_eq!(marginal.margin, 14);
+    assert!(marginal.score() < score.score() / 10);
+
+    // Partial press
+    healthy.sensors[0].stats.max -= 300;
+    assert_eq!(get(&healthy).travel, 127);
+    healthy.sensors[0].stats.max += 300;
+
+    // Noise, 1/16 of the span is the limit
+    healthy.sensors[0].stats.noise = 10;
+    assert_eq!(get(&healthy).noise, 255 - 68);
+    healthy.sensors[0].stats.noise = 40;
+    assert_eq!(get(&healthy).noise, 0);
+    assert_eq!(get(&healthy).score(), 0);
+
+    // Not calibrated
+    assert_eq!(
+        healthy
+            .calibration_quality::<MIN_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(1)
+            .unwrap(),
+        CalibrationQuality::default()
+    );
+    assert!(healthy
+        .calibration_quality::<MIN_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(2)
+        .is_err());
+}
+
+#[test]
+fn channel_remap() {
+    setup_logging_lite().ok();
+
+    static DUPLICATE: [usize; 4] = [0, 0, 1, 2];
+    static OUT_OF_RANGE: [usize; 4] = [0, 1, 2, 4];
+    static REMAP: [usize; 4] = [2, 0, 3, 1];
+
+    let mut sensors = Sensors::<4>::new();
+    assert!(matches!(
+        sensors.set_remap(Some(&DUPLICATE)),
+        Err(SensorError::InvalidRemap(1))
+    ));
+    assert!(matches!(
+        sensors.set_remap(Some(&OUT_OF_RANGE)),
+        Err(SensorError::InvalidRemap(3))
+    ));
+    sensors.set_remap(Some(&REMAP)).unwrap();
+
+    // Channels are added in logical order
+    let rest = MIN_OK_THRESHOLD as u16 + 2;
+    for _ in 0..2 {
+        sensors.add::<2>(0, rest + 100).unwrap();
+    }
+    assert_eq!(sensors.get_data(2).unwrap().analysis.raw, rest + 100);
+    assert!(sensors.get_data(0).is_err());
+    assert!(sensors.add::<2>(4, rest).is_err());
+
+    // Frames too
+    for _ in 0..2 {
+        sensors
+            .add_frame(&[rest, rest + 10, rest + 20, rest + 30])
+            .unwrap();
+    }
+    let raw: [u16; 4] = core::array::from_fn(|index| sensors.get_data(index).unwrap().analysis.raw);
+    assert_eq!(raw, [rest + 10, rest + 30, rest, rest + 20]);
+
+    // Identity
+    sensors.set_remap(None).unwrap();
+    for _ in 0..4 {
+        sensors.add::<2>(0, rest).unwrap();
+    }
+    assert_eq!(sensors.get_data(0).unwrap().analysis.raw, rest);
+}
+
+#[test]
+fn full_scale_first_reading() {
+    setup_logging_lite().ok();
+
+    // 16-bit readings (4 oversampling bits), full scale is 0xFFFF
+    let config = SensorsConfig {
+        filter: Filter::Oversample { bits: 4 },
+        rest: RestBoundary::Signed,
+        ..SensorsConfig::default()
+    };
+    let mut data = SenseData::<0>::new();
+    assert!(!data.stats.min_valid);
+
+    // First averaged reading is full scale, recorded as the rest position
+    data.sample(0xFFFF, &DEFAULT_LOOKUP_TABLE, &config);
+    assert!(data.stats.min_valid);
+    assert_eq!((data.stats.min, data.stats.max), (0xFFFF, 0xFFFF));
+    assert_eq!(data.analysis.distance(), 0);
+
+    // Lower readings are below the calibrated rest position, not a new minimum
+    data.sample(0xFFFF - 1600, &DEFAULT_LOOKUP_TABLE, &config);
+    assert_eq!(data.stats.min, 0xFFFF);
+    assert!(data.analysis.distance() < 0);
+
+    // Decay still applies to a full scale rest position
+    data.decay_extrema::<16>();
+    assert_eq!(data.stats.min, 0xFFFF - 16);
+
+    // Same for a full scale rest position set after calibration
+    data.set_rest(0xFFFF);
+    data.sample(0xFFFF - 800, &DEFAULT_LOOKUP_TABLE, &config);
+    assert_eq!(data.stats.min, 0xFFFF);
+    assert!(data.analysis.distance() < 0);
+
+    // Cleared by recalibration
+    data.recalibrate();
+    assert!(!data.stats.min_valid);
+}
+
+#[test]
+fn alpha_beta() {
+    setup_logging_lite().ok();
+
+    // Synthetic jittery press, rest for 32 samples, ramp 400 counts over 40 samples then hold
+    // Returns the analyses after the sensor calibrated
+    fn press(estimator: Option<AlphaBeta>) -> [SenseAnalysis; 160] {
+        let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+            estimator,
+            ..SensorsConfig::default()
+        });
+        sensors.set_passthrough(true);
+        let rest = MIN_OK_THRESHOLD as u16 + 100;
+        core::array::from_fn(|n| {
+            let travel = (n.saturating_sub(32) * 10).min(400) as u16;
+            let jitter = [0, 12, 3, 9, 6, 1, 11, 4][n % 8];
+            sensors
+                .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                    0,
+                    rest + travel + jitter,
+                )
+                .unwrap()
+                .analysis()
+                .cloned()
+                .unwrap_or(SenseAnalysis::null())
+        })
+    }
+    // Samples to cross half travel, velocity peak-to-peak while held
+    fn lag_and_noise(analyses: &[SenseAnalysis; 160]) -> (usize, i16) {
+        let lag = analyses
+            .iter()
+            .position(|analysis| analysis.distance() >= 200)
+            .unwrap();
+        let held = analyses[128..].iter().map(|analysis| analysis.velocity());
+        let noise = held.clone().max().unwrap() - held.min().unwrap();
+        (lag, noise)
+    }
+
+    let raw = press(None);
+    let (raw_lag, raw_noise) = lag_and_noise(&raw);
+    assert!(raw_noise > 0);
+
+    // Unity alpha without beta follows the measured distance exactly
+    let unity = press(Some(AlphaBeta {
+        alpha: ALPHA_BETA_SCALE as u16,
+        beta: 0,
+    }));
+    for (raw, unity) in raw.iter().zip(unity.iter()) {
+        assert_eq!(raw.distance(), unity.distance());
+        assert_eq!(unity.velocity(), 0);
+    }
+
+    // Less noise, with at most a few samples of added lag
+    let filtered = press(Some(AlphaBeta { alpha: 64, beta: 9 }));
+    let (lag, noise) = lag_and_noise(&filtered);
+    assert!(
+        noise * 4 <= raw_noise,
+        "Raw noise: {}  Filtered noise: {}",
+        raw_noise,
+        noise
+    );
+    assert!(
+        lag >= raw_lag && lag <= raw_lag + 6,
+        "Raw lag: {}  Filtered lag: {}",
+        raw_lag,
+        lag
+    );
+
+    // Velocity tracks the ramp and the estimate settles on the held distance
+    let ramp = filtered[56..72].iter().map(|analysis| analysis.velocity());
+    assert!(ramp.clone().all(|velocity| (7..=13).contains(&velocity)));
+    let held = filtered[159].distance();
+    assert!((396..=412).contains(&held), "Held: {}", held);
+
+    // Reseeded after losing calibration
+    let mut sensors = Sensors::<1>::with_config(SensorsConfig {
+        estimator: Some(AlphaBeta { alpha: 64, beta: 9 }),
+        cal_debounce: 0,
+        ..SensorsConfig::default()
+    });
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    for _ in 0..4 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, rest)
+            .unwrap();
+    }
+    assert!(sensors.sensors[0].estimate.is_some());
+    for _ in 0..2 {
+        sensors
+            .add_test::<1, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+                0,
+                NO_SENSOR_THRESHOLD as u16 - 1,
+            )
+            .unwrap();
+    }
+    assert!(sensors.sensors[0].estimate.is_none());
+}
+
+#[test]
+fn hot_swap() {
+    setup_logging_lite().ok();
+
+    let mut sensors = Sensors::<1, 0, 4>::with_config(SensorsConfig {
+        hot_swap: Some(HotSwap {
+            grace: 8,
+            tolerance: 16,
+        }),
+        ..SensorsConfig::default()
+    });
+    fn window<const CAL_CONFIRM: usize>(
+        sensors: &mut Sensors<1, 0, CAL_CONFIRM>,
+        val: u16,
+    ) -> Option<HotSwapEvent> {
+        for _ in 0..2 {
+            sensors
+                .add_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, val)
+                .unwrap();
+        }
+        sensors.sensors[0].hot_swap_event()
+    }
+    let cal = |sensors: &Sensors<1, 0, 4>| sensors.sensors[0].cal.clone();
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    let pulled = NO_SENSOR_THRESHOLD as u16 + 100;
+
+    // Calibrate and press
+    for _ in 0..4 {
+        assert_eq!(window(&mut sensors, rest), None);
+    }
+    assert_eq!(cal(&sensors), CalibrationStatus::MagnetDetected);
+    for val in [rest + 600, rest + 600, rest, rest] {
+        window(&mut sensors, val);
+    }
+    let stats = sensors.sensors[0].stats.clone();
+
+    // Pull (after SensorsConfig::cal_debounce)
+    let pull = |sensors: &mut Sensors<1, 0, 4>| {
+        assert_eq!(window(sensors, pulled), None);
+        assert_eq!(window(sensors, pulled), None);
+        assert_eq!(window(sensors, pulled), Some(HotSwapEvent::Removed));
+        assert_eq!(cal(sensors), CalibrationStatus::MagnetWrongPoleOrMissing);
+        assert!(sensors.sensors[0].hot_swap_pending());
+        assert!(!sensors.sensors[0].stats.min_valid);
+        for _ in 0..3 {
+            assert_eq!(window(sensors, pulled), None);
+        }
+    };
+    pull(&mut sensors);
+
+    // Reinserted close to the previous rest position, which is restored immediately
+    // (max is measured again)
+    assert_eq!(
+        window(&mut sensors, rest + 4),
+        Some(HotSwapEvent::Reinserted { seeded: true })
+    );
+    assert_eq!(cal(&sensors), CalibrationStatus::MagnetDetected);
+    assert!(!sensors.sensors[0].hot_swap_pending());
+    let restored = &sensors.sensors[0].stats;
+    assert_eq!((restored.min, restored.max), (stats.min, rest + 4));
+    assert!(restored.min_valid);
+    assert_eq!(restored.samples, stats.samples + 1);
+    // Travel is measured from the restored rest position (averaged with the previous window)
+    window(&mut sensors, rest + 600);
+    assert_eq!(
+        sensors.get_data(0).unwrap().analysis.distance(),
+        crate::linearize(rest + 302) - crate::linearize(stats.min)
+    );
+    window(&mut sensors, rest);
+
+    // Reinserted elsewhere (e.g. a different switch), calibrated immediately but not seeded
+    pull(&mut sensors);
+    assert_eq!(
+        window(&mut sensors, rest + 100),
+        Some(HotSwapEvent::Reinserted { seeded: false })
+    );
+    assert_eq!(cal(&sensors), CalibrationStatus::MagnetDetected);
+    let restarted = &sensors.sensors[0].stats;
+    assert_eq!((restarted.min, restarted.max), (rest + 100, rest + 100));
+
+    // Grace period elapsed, back to CAL_CONFIRM
+    pull(&mut sensors);
+    for _ in 0..4 {
+        assert_eq!(window(&mut sensors, pulled), None);
+    }
+    assert_eq!(window(&mut sensors, pulled), Some(HotSwapEvent::Expired));
+    assert!(!sensors.sensors[0].hot_swap_pending());
+    for _ in 0..3 {
+        assert_eq!(window(&mut sensors, rest), None);
+        assert_eq!(cal(&sensors), CalibrationStatus::MagnetWrongPoleOrMissing);
+    }
+    assert_eq!(window(&mut sensors, rest), None);
+    assert_eq!(cal(&sensors), CalibrationStatus::MagnetDetected);
+
+    // Disabled by default
+    let mut sensors = Sensors::<1>::new();
+    for val in [rest, rest, pulled, pulled, pulled, rest] {
+        assert_eq!(window(&mut sensors, val), None);
+    }
+    assert!(!sensors.sensors[0].hot_swap_pending());
+}
+
+#[cfg(all(feature = "ffi", feature = "std"))]
+mod ffi {
+    use super::*;
+    use crate::ffi::*;
+
+    extern crate std;
+
+    const HEADER: &str = include_str!("../include/kiibohd_hall_effect.h");
+
+    // Declarations as generated by cbindgen (see cbindgen.toml), regenerate the header if these
+    // change
+    const DEFINES: [(&str, i32); 16] = [
+        ("HE_PENDING", HE_PENDING),
+        ("HE_READY", HE_READY),
+        ("HE_NOT_CALIBRATED", HE_NOT_CALIBRATED),
+        (
+            "HE_ERROR_INVALID_FRAME_LENGTH",
+            HE_ERROR_INVALID_FRAME_LENGTH,
+        ),
+        (
+            "HE_ERROR_INVALID_LOOKUP_TABLE",
+            HE_ERROR_INVALID_LOOKUP_TABLE,
+        ),
+        ("HE_ERROR_INVALID_PAIR", HE_ERROR_INVALID_PAIR),
+        ("HE_ERROR_INVALID_REMAP", HE_ERROR_INVALID_REMAP),
+        (
+            "HE_ERROR_INVALID_SAMPLE_COUNT",
+            HE_ERROR_INVALID_SAMPLE_COUNT,
+        ),
+        ("HE_ERROR_INVALID_SENSOR", HE_ERROR_INVALID_SENSOR),
+        ("HE_ERROR_NOT_REGISTERED", HE_ERROR_NOT_REGISTERED),
+        ("HE_STATUS_NOT_READY", HE_STATUS_NOT_READY),
+        ("HE_STATUS_SENSOR_MISSING", HE_STATUS_SENSOR_MISSING),
+        ("HE_STATUS_SENSOR_BROKEN", HE_STATUS_SENSOR_BROKEN),
+        ("HE_STATUS_MAGNET_DETECTED", HE_STATUS_MAGNET_DETECTED),
+        (
+            "HE_STATUS_MAGNET_WRONG_POLE_OR_MISSING",
+            HE_STATUS_MAGNET_WRONG_POLE_OR_MISSING,
+        ),
+        ("HE_STATUS_INVALID_INDEX", HE_STATUS_INVALID_INDEX),
+    ];
+    const FUNCTIONS: [&str; 3] = [
+        "int32_t he_sensor_add(uint16_t index, uint16_t reading);",
+        "int16_t he_sensor_distance(uint16_t index);",
+        "int32_t he_sensor_status(uint16_t index);",
+    ];
+
+    #[test]
+    fn codes() {
+        let errors = [
+            (
+                SensorError::InvalidFrameLength(0),
+                HE_ERROR_INVALID_FRAME_LENGTH,
+            ),
+            (
+                SensorError::InvalidLookupTable(0),
+                HE_ERROR_INVALID_LOOKUP_TABLE,
+            ),
+            (SensorError::InvalidPair(0, 1), HE_ERROR_INVALID_PAIR),
+            (SensorError::InvalidRemap(0), HE_ERROR_INVALID_REMAP),
+            (
+                SensorError::InvalidSampleCount(0),
+                HE_ERROR_INVALID_SAMPLE_COUNT,
+            ),
+            (SensorError::InvalidSensor(0), HE_ERROR_INVALID_SENSOR),
+        ];
+        for (err, code) in errors {
+            assert_eq!(err.code(), code, "{:?}", err);
+            assert!(code < 0);
+        }
+
+        // Every measurement has its own code
+        let analysis = SenseAnalysis::null();
+        let measurements = [
+            (Measurement::Pending, HE_PENDING),
+            (Measurement::Ready(&analysis), HE_READY),
+            (
+                Measurement::NotCalibrated(CalibrationStatus::NotReady),
+                HE_NOT_CALIBRATED,
+            ),
+        ];
+        for (measurement, code) in measurements {
+            assert_eq!(measurement.code(), code, "{:?}", measurement);
+        }
+    }
+
+    #[test]
+    fn header() {
+        // Signatures (fails to build if the Rust side drifts)
+        let _: unsafe extern "C" fn(u16, u16) -> i32 = he_sensor_add;
+        let _: unsafe extern "C" fn(u16) -> i16 = he_sensor_distance;
+        let _: unsafe extern "C" fn(u16) -> i32 = he_sensor_status;
+
+        let lines: std::vec::Vec<&str> = HEADER.lines().map(str::trim).collect();
+        let defines: std::vec::Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.starts_with("#define HE_"))
+            .collect();
+        assert_eq!(defines.len(), DEFINES.len(), "{:?}", defines);
+        for (name, value) in DEFINES {
+            let define = std::format!("#define {} {}", name, value);
+            assert!(defines.contains(&define.as_str()), "Missing: {}", define);
+        }
+
+        let functions: std::vec::Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| !line.starts_with('*') && line.contains(" he_"))
+            .collect();
+        assert_eq!(functions, FUNCTIONS);
+    }
+
+    #[test]
+    fn accessors() {
+        unsafe {
+            assert_eq!(he_sensor_add(0, 1500), HE_ERROR_NOT_REGISTERED);
+            assert_eq!(he_sensor_status(0), HE_ERROR_NOT_REGISTERED);
+            assert_eq!(he_sensor_distance(0), 0);
+
+            let sensors = std::boxed::Box::leak(std::boxed::Box::new(Sensors::<2>::new()));
+            sensors.set_sample_count(2).unwrap();
+            register(sensors);
+
+            assert_eq!(he_sensor_status(0), HE_STATUS_NOT_READY);
+            assert_eq!(he_sensor_add(0, 1500), HE_PENDING);
+            assert_eq!(he_sensor_add(0, 1500), HE_READY);
+            assert_eq!(he_sensor_status(0), HE_STATUS_MAGNET_DETECTED);
+            assert_eq!(he_sensor_distance(0), 0);
+
+            // Press
+            for _ in 0..4 {
+                he_sensor_add(0, 2000);
+            }
+            assert!(he_sensor_distance(0) > 0);
+
+            // Uncalibrated sensor
+            assert_eq!(he_sensor_status(1), HE_STATUS_NOT_READY);
+            assert_eq!(he_sensor_distance(1), 0);
+
+            // Invalid index
+            assert_eq!(he_sensor_add(2, 1500), HE_ERROR_INVALID_SENSOR);
+            assert_eq!(he_sensor_status(2), HE_ERROR_INVALID_SENSOR);
+            assert_eq!(he_sensor_distance(2), 0);
+        }
+    }
+}
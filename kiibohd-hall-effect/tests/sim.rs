@@ -0,0 +1,166 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Simulation harness integration tests (public sim API only)
+
+// ----- Crates -----
+
+use flexi_logger::Logger;
+use kiibohd_hall_effect::sim::{Profile, Simulation};
+use kiibohd_hall_effect::{CalibrationStatus, Sensors};
+
+// ----- Types -----
+
+// Calibration Mode Thresholds (see src/test.rs)
+const MIN_OK_THRESHOLD: usize = 1350;
+const MAX_OK_THRESHOLD: usize = 2500;
+const NO_SENSOR_THRESHOLD: usize = 1000;
+
+// ----- Functions -----
+
+/// Lite logging setup
+fn setup_logging_lite() {
+    Logger::with_env_or_str("")
+        .format(flexi_logger::colored_default_format)
+        .format_for_files(flexi_logger::colored_detailed_format)
+        .duplicate_to_stderr(flexi_logger::Duplicate::All)
+        .start()
+        .ok();
+}
+
+fn calibrated() -> (Simulation<1>, u16) {
+    let mut sim = Simulation::new(Sensors::<1>::new());
+    let rest = MIN_OK_THRESHOLD as u16 + 100;
+    sim.run_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+        0,
+        &Profile::Hold {
+            raw: rest,
+            samples: 8,
+        },
+    )
+    .unwrap();
+    (sim, rest)
+}
+
+fn run(sim: &mut Simulation<1>, profile: Profile) {
+    sim.run_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(0, &profile)
+        .unwrap();
+}
+
+// ----- Tests -----
+
+#[test]
+fn calibration_cycle() {
+    setup_logging_lite();
+
+    let (mut sim, rest) = calibrated();
+    assert_eq!(
+        sim.transitions.last().unwrap().value.to,
+        CalibrationStatus::MagnetDetected
+    );
+    assert_eq!(sim.sensors.get_data(0).unwrap().stats.min, rest);
+
+    // Steady at rest, no further transitions
+    sim.clear();
+    run(
+        &mut sim,
+        Profile::Sine {
+            center: rest + 20,
+            amplitude: 10,
+            period: 16,
+            samples: 64,
+        },
+    );
+    assert!(sim.transitions.is_empty());
+    assert_eq!(sim.analyses.len(), 32);
+
+    // Invalid index
+    assert!(sim
+        .run_test::<2, MIN_OK_THRESHOLD, MAX_OK_THRESHOLD, NO_SENSOR_THRESHOLD>(
+            1,
+            &Profile::Hold {
+                raw: rest,
+                samples: 1
+            },
+        )
+        .is_err());
+}
+
+#[test]
+fn press_release() {
+    setup_logging_lite();
+
+    let (mut sim, rest) = calibrated();
+    sim.clear();
+    let bottom = rest + 800;
+    run(
+        &mut sim,
+        Profile::Ramp {
+            from: rest,
+            to: bottom,
+            samples: 40,
+        },
+    );
+    run(
+        &mut sim,
+        Profile::BottomBounce {
+            rest: bottom,
+            bottom,
+            amplitude: 100,
+            samples: 16,
+        },
+    );
+    run(
+        &mut sim,
+        Profile::Ramp {
+            from: bottom,
+            to: rest,
+            samples: 40,
+        },
+    );
+    run(
+        &mut sim,
+        Profile::Hold {
+            raw: rest,
+            samples: 8,
+        },
+    );
+    assert!(sim.transitions.is_empty());
+
+    let peak = sim.analyses_of(0).map(|a| a.distance()).max().unwrap();
+    assert!(peak <= 800 && peak > 750, "{}", peak);
+    assert_eq!(sim.analyses_of(0).last().unwrap().distance(), 0);
+
+    // Pressing moves forward, releasing moves back
+    assert!(sim.analyses[2..18].iter().all(|a| a.value.velocity() > 0));
+    assert!(sim.analyses[30..48].iter().all(|a| a.value.velocity() < 0));
+}
+
+#[test]
+fn magnet_yank() {
+    setup_logging_lite();
+
+    let (mut sim, rest) = calibrated();
+    sim.clear();
+    run(
+        &mut sim,
+        Profile::MagnetRemoval {
+            rest,
+            baseline: NO_SENSOR_THRESHOLD as u16 + 100,
+            samples: 32,
+        },
+    );
+    assert_eq!(sim.transitions.len(), 1);
+    let transition = &sim.transitions[0].value;
+    assert_eq!(transition.from, CalibrationStatus::MagnetDetected);
+    assert_eq!(transition.to, CalibrationStatus::MagnetWrongPoleOrMissing);
+    assert!(sim.sensors.get_data(0).is_ok());
+    assert_ne!(
+        sim.sensors.get_data(0).unwrap().cal,
+        CalibrationStatus::MagnetDetected
+    );
+}
@@ -0,0 +1,129 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+// Hybrid boards
+// Merges the digital (Matrix) and analog (kiibohd-hall-effect Sensors) keys of a board into a
+// single event stream keyed by a global key index.
+
+// ----- Crates -----
+
+use crate::{KeyEvent, State};
+use kiibohd_hall_effect::{ActuationEvent, Sensors};
+
+// ----- Types -----
+
+/// Origin (and event) of a hybrid board key event
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub enum Source {
+    /// Matrix-scanned digital key
+    Matrix(KeyEvent),
+    /// Hall-effect analog key
+    Analog(ActuationEvent),
+}
+
+/// Key event of a hybrid board
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct HybridEvent {
+    /// Global key index
+    pub index: usize,
+    pub source: Source,
+}
+
+/// Global key index mapping of a hybrid board
+///
+/// Matrix keys are global indices matrix_offset.. (in matrix index order), analog sensor n is
+/// global index analog[n]. The global indices should not overlap.
+///
+/// ```rust,ignore
+/// // 18x6 matrix at 0..108, 4 analog keys after it
+/// let hybrid = Hybrid::new(0, [108, 109, 110, 111]);
+///
+/// let states = matrix.scan_all(&mut NoDelay).unwrap();
+/// for reading in readings {
+///     sensors.add::<SC>(reading.index, reading.value).unwrap();
+/// }
+/// for event in hybrid.events::<_, _, AP, HYST>(&states, matrix.changes(), &mut sensors) {
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, defmt::Format)]
+pub struct Hybrid<const S: usize> {
+    matrix_offset: usize,
+    analog: [usize; S],
+}
+
+impl<const S: usize> Hybrid<S> {
+    pub fn new(matrix_offset: usize, analog: [usize; S]) -> Hybrid<S> {
+        Hybrid {
+            matrix_offset,
+            analog,
+        }
+    }
+
+    /// Global index of a matrix index
+    pub fn matrix_index(&self, index: usize) -> usize {
+        self.matrix_offset + index
+    }
+
+    /// Global index of an analog sensor index, None if the sensor isn't mapped
+    pub fn analog_index(&self, sensor: usize) -> Option<usize> {
+        self.analog.get(sensor).copied()
+    }
+
+    /// Merged state changes of a full matrix scan (see Matrix::scan_all) and the analog sensors
+    ///
+    /// changes are the state changes of the same scan (see Matrix::changes), bounces while the
+    /// key is debouncing are not state changes. Matrix events are only reported on a state change
+    /// (press or release) and for auto-repeats, held and idle keys are skipped.
+    /// Matrix state changes come first, then auto-repeats (both in matrix index order), then
+    /// analog actuation events (see SenseData::poll_actuation, in sensor order).
+    /// Sensors are polled as the iterator advances, so consume it fully every scan.
+    pub fn events<
+        'a,
+        const CSIZE: usize,
+        const RSIZE: usize,
+        const AP: usize,
+        const HYST: usize,
+        const H: usize,
+        const CAL_CONFIRM: usize,
+    >(
+        &'a self,
+        scan: &'a [[KeyEvent; RSIZE]; CSIZE],
+        changes: impl Iterator<Item = (usize, State)> + 'a,
+        sensors: &'a mut Sensors<S, H, CAL_CONFIRM>,
+    ) -> impl Iterator<Item = HybridEvent> + 'a {
+        let changed = changes.filter_map(|(index, _)| {
+            let event = scan.get(index / RSIZE)?[index % RSIZE];
+            Some((index, event))
+        });
+        let repeats = scan
+            .iter()
+            .flatten()
+            .copied()
+            .enumerate()
+            .filter(|(_, event)| matches!(event, KeyEvent::Repeat { .. }));
+        let matrix = changed.chain(repeats).map(|(index, event)| HybridEvent {
+            index: self.matrix_index(index),
+            source: Source::Matrix(event),
+        });
+        let analog = self
+            .analog
+            .iter()
+            .enumerate()
+            .filter_map(move |(sensor, &index)| {
+                let event = sensors
+                    .get_data_mut(sensor)
+                    .ok()?
+                    .poll_actuation::<AP, HYST>()?;
+                Some(HybridEvent {
+                    index,
+                    source: Source::Analog(event),
+                })
+            });
+        matrix.chain(analog)
+    }
+}
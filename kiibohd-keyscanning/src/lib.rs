@@ -8,14 +8,22 @@
 
 #![no_std]
 
+#[cfg(feature = "hall-effect")]
+pub mod hybrid;
+pub mod selftest;
 pub mod state;
+pub mod tap;
+mod test;
 
-pub use self::state::{KeyState, State};
+pub use self::selftest::{MatrixPin, SelfTestReport, SELF_TEST_SHORTS};
+pub use self::state::{DebounceMode, KeyState, State};
+pub use self::tap::{TapCounter, TapHold, TapHoldDecision};
+use embedded_hal::blocking::delay::DelayUs;
 use embedded_hal::digital::v2::{InputPin, IoPin, OutputPin, PinState};
 
 /// Records momentary push button events
 ///
-/// Cycles can be converted to time by multiplying by the scan period (Matrix::period())
+/// Cycles are full matrix scans (Matrix::period() * CSIZE), see Matrix::cycles_to_us
 #[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
 pub enum KeyEvent {
     On {
@@ -28,6 +36,81 @@ pub enum KeyEvent {
         /// Cycles since the last state change
         cycles_since_state_change: u32,
     },
+    /// Key is still held and an auto-repeat is due (replaces On for that scan)
+    Repeat {
+        /// Repeats since the key was pressed (starting at 1)
+        count: u32,
+        /// Cycles since the last state change
+        cycles_since_state_change: u32,
+    },
+}
+
+/// GPIO error raised while scanning, with the position of the failing pin
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct ScanError<E> {
+    /// Strobe (column) being scanned
+    pub strobe: usize,
+    /// Row (sense) that failed, None if the strobe itself failed
+    pub row: Option<usize>,
+    /// Underlying embedded-hal error
+    pub source: E,
+}
+
+/// Drains stray potential from the sense lines between strobes (see Matrix DRAIN)
+///
+/// Implemented for rows of IoPins when DRAIN is true, and for any rows when DRAIN is false (no
+/// draining, for HALs that don't implement IoPin). Draining is only needed for high capacitance
+/// matrices (STROBE_SETTLE_US may be enough on its own).
+pub trait Drain<C, const DRAIN: bool, E> {
+    /// Temporarily drive each sense line to the released level (inactive)
+    /// strobe is the column that was just released, for errors.
+    fn drain(&mut self, strobe: usize, inactive: PinState) -> Result<(), ScanError<E>>;
+}
+
+impl<R, C, E, const RSIZE: usize> Drain<C, true, E> for [R; RSIZE]
+where
+    R: InputPin + IoPin<R, C>,
+    C: OutputPin + IoPin<R, C>,
+    E: core::convert::From<<R as IoPin<R, C>>::Error>
+        + core::convert::From<<C as IoPin<R, C>>::Error>,
+{
+    fn drain(&mut self, strobe: usize, inactive: PinState) -> Result<(), ScanError<E>> {
+        // NOTE: This is unsafe because the gpio are stored in an array and (likely) do not implement
+        //       copy or clone. Since they are in an array, we can't move them either.
+        //       Since we're just temporarily sinking the pin and putting it back, this is safe to
+        //       do.
+        for (i, s) in self.iter_mut().enumerate() {
+            let ptr = s as *const R;
+            let err = |source| ScanError {
+                strobe,
+                row: Some(i),
+                source,
+            };
+            unsafe {
+                let row = core::ptr::read(ptr);
+                // Temporarily drive sense gpios to the released level and reset to sense/read gpio
+                row.into_output_pin(inactive)
+                    .map_err(|e| err(E::from(e)))?
+                    .into_input_pin()
+                    .map_err(|e| err(E::from(e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<R, C, E, const RSIZE: usize> Drain<C, false, E> for [R; RSIZE] {
+    fn drain(&mut self, _strobe: usize, _inactive: PinState) -> Result<(), ScanError<E>> {
+        Ok(())
+    }
+}
+
+/// No-op delay, for Matrix::next_strobe and Matrix::scan_all without settling
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoDelay;
+
+impl DelayUs<u32> for NoDelay {
+    fn delay_us(&mut self, _us: u32) {}
 }
 
 /// This struct handles scanning and strobing of the key matrix.
@@ -36,6 +119,8 @@ pub enum KeyEvent {
 /// OutputPin's are passed as columns (cols) which are strobed.
 /// IoPins are functionally InputPins (rows) which are read. Rows are IoPins in order to drain the
 /// row/sense between strobes to prevent stray capacitance.
+/// For HALs without IoPin, plain InputPin rows can be used with DRAIN = false which skips the
+/// drain (see Drain).
 ///
 /// ```rust,ignore
 /// const CSIZE: usize = 18; // Number of columns
@@ -47,7 +132,19 @@ pub enum KeyEvent {
 /// // Per-key timer is reset if the raw gpio reading changes for any reason.
 /// const DEBOUNCE_US = 5000; // 5 ms
 /// // Idle timer in ms. Only valid if the switch is in the off state.
-/// const IDLE_MS = 600_0000; // 600 seconds or 10 minutes
+/// const IDLE_MS = 600_000; // 600 seconds or 10 minutes
+/// // (Optional) Settling time after strobing a column in us, for high capacitance matrices.
+/// // Only used when next_strobe and scan_all are given a delay. Counts towards SCAN_PERIOD_US.
+/// const STROBE_SETTLE_US = 5;
+/// // (Optional) Strobes are driven low and a pressed key senses low (e.g. pull-ups).
+/// const ACTIVE_LOW = false;
+/// // (Optional) Auto-repeat, KeyEvent::Repeat after a key is held for REPEAT_DELAY_MS then every
+/// // REPEAT_RATE_MS. Only as precise as a multiple of SCAN_PERIOD_US * CSIZE, 0 disables.
+/// const REPEAT_DELAY_MS = 500;
+/// const REPEAT_RATE_MS = 33;
+/// // (Optional) Drain the sense lines between strobes, false for HALs without IoPin (rows only
+/// // need to be InputPins, see Drain).
+/// const DRAIN = true;
 ///
 /// let cols = [
 ///     pins.strobe1.downgrade(),
@@ -80,14 +177,24 @@ pub enum KeyEvent {
 /// ];
 ///
 /// let mut matrix = Matrix::<OutputPin, InputPin, CSIZE, RSIZE, MSIZE, SCAN_PERIOD_US, DEBOUNCE_US,
-/// IDLE_MS>::new(cols, rows);
+/// IDLE_MS, STROBE_SETTLE_US, ACTIVE_LOW, REPEAT_DELAY_MS, REPEAT_RATE_MS, DRAIN>::new(cols, rows);
 ///
-/// // Prepare first strobe
-/// matrix.next_strobe().unwrap();
+/// // Prepare first strobe (NoDelay skips settling)
+/// matrix.next_strobe(&mut NoDelay).unwrap();
 ///
 /// // --> This next part must be done in constant time (SCAN_PERIOD_US) <--
 /// let state = matrix.sense().unwrap();
-/// matrix.next_strobe().unwrap();
+/// matrix.next_strobe(&mut NoDelay).unwrap();
+///
+/// // Or scan every column at once (primes the strobe automatically)
+/// // --> Must be done in constant time (SCAN_PERIOD_US * CSIZE) <--
+/// let states = matrix.scan_all(&mut NoDelay).unwrap();
+///
+/// // Or settle for STROBE_SETTLE_US after each strobe (using a DelayUs<u32> implementation)
+/// matrix.next_strobe(&mut delay).unwrap();
+/// let state = matrix.sense().unwrap();
+/// matrix.next_strobe(&mut delay).unwrap();
+/// let states = matrix.scan_all(&mut delay).unwrap();
 /// ```
 pub struct Matrix<
     C: OutputPin,
@@ -98,6 +205,11 @@ pub struct Matrix<
     const SCAN_PERIOD_US: u32,
     const DEBOUNCE_US: u32,
     const IDLE_MS: u32,
+    const STROBE_SETTLE_US: u32 = 0,
+    const ACTIVE_LOW: bool = false,
+    const REPEAT_DELAY_MS: u32 = 0,
+    const REPEAT_RATE_MS: u32 = 0,
+    const DRAIN: bool = true,
 > {
     /// Strobe GPIOs (columns)
     cols: [C; CSIZE],
@@ -105,8 +217,20 @@ pub struct Matrix<
     rows: [R; RSIZE],
     /// Current GPIO column being strobed
     cur_strobe: usize,
+    /// Position of cur_strobe in strobe_order
+    position: usize,
+    /// Sequence of GPIO columns walked by next_strobe (see set_strobe_order)
+    strobe_order: [usize; CSIZE],
+    /// A column is currently strobed (cleared by clear())
+    strobed: bool,
+    /// Report suspected phantom keys as off in sense results (see set_ghost_mask)
+    mask_ghosts: bool,
+    /// Debounce algorithm (see set_debounce_mode)
+    debounce: DebounceMode,
     /// Recorded state of the entire matrix
     state_matrix: [KeyState<CSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS>; MSIZE],
+    /// Debounced state changed during the most recent sense of the key's column
+    changed: [bool; MSIZE],
 }
 
 impl<
@@ -118,19 +242,58 @@ impl<
         const SCAN_PERIOD_US: u32,
         const DEBOUNCE_US: u32,
         const IDLE_MS: u32,
-    > Matrix<C, R, CSIZE, RSIZE, MSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS>
+        const STROBE_SETTLE_US: u32,
+        const ACTIVE_LOW: bool,
+        const REPEAT_DELAY_MS: u32,
+        const REPEAT_RATE_MS: u32,
+        const DRAIN: bool,
+    >
+    Matrix<
+        C,
+        R,
+        CSIZE,
+        RSIZE,
+        MSIZE,
+        SCAN_PERIOD_US,
+        DEBOUNCE_US,
+        IDLE_MS,
+        STROBE_SETTLE_US,
+        ACTIVE_LOW,
+        REPEAT_DELAY_MS,
+        REPEAT_RATE_MS,
+        DRAIN,
+    >
 {
-    pub fn new<'a, E: 'a>(cols: [C; CSIZE], rows: [R; RSIZE]) -> Result<Self, E>
+    /// Electrical level of a strobed column (and a pressed key)
+    const ACTIVE: PinState = if ACTIVE_LOW {
+        PinState::Low
+    } else {
+        PinState::High
+    };
+
+    /// Electrical level of an idle column (and a released key)
+    const INACTIVE: PinState = if ACTIVE_LOW {
+        PinState::High
+    } else {
+        PinState::Low
+    };
+
+    pub fn new<'a, E: 'a>(cols: [C; CSIZE], rows: [R; RSIZE]) -> Result<Self, ScanError<E>>
     where
         C: OutputPin<Error = E>,
-        E: core::convert::From<<C as OutputPin>::Error>,
     {
         let state_matrix = [KeyState::<CSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS>::new(); MSIZE];
         let mut res = Self {
             cols,
             rows,
             cur_strobe: CSIZE - 1,
+            position: CSIZE - 1,
+            strobe_order: core::array::from_fn(|col| col),
+            strobed: false,
+            mask_ghosts: false,
+            debounce: DebounceMode::Deferred,
             state_matrix,
+            changed: [false; MSIZE],
         };
 
         // Reset strobe position and make sure all strobes are off
@@ -139,68 +302,204 @@ impl<
     }
 
     /// Clears strobes
-    /// Resets strobe counter to the last element (so next_strobe starts at 0)
-    pub fn clear<'a, E: 'a>(&'a mut self) -> Result<(), E>
+    /// Resets strobe counter to the last element (so next_strobe starts at the first column of
+    /// the strobe order)
+    /// GPIO errors carry the strobe that failed.
+    pub fn clear<'a, E: 'a>(&'a mut self) -> Result<(), ScanError<E>>
     where
         C: OutputPin<Error = E>,
     {
         // Clear all strobes
-        for c in self.cols.iter_mut() {
-            c.set_low()?;
+        for (strobe, c) in self.cols.iter_mut().enumerate() {
+            c.set_state(Self::INACTIVE).map_err(|source| ScanError {
+                strobe,
+                row: None,
+                source,
+            })?;
         }
 
         // Reset strobe position
-        self.cur_strobe = CSIZE - 1;
+        self.position = CSIZE - 1;
+        self.cur_strobe = self.strobe_order[self.position];
+        self.strobed = false;
         Ok(())
     }
 
-    /// Next strobe
-    pub fn next_strobe<'a, E: 'a>(&'a mut self) -> Result<usize, E>
+    /// Next strobe, then wait STROBE_SETTLE_US for the sense lines to settle
+    ///
+    /// The sense lines are drained between strobes unless DRAIN is false (rows must be IoPins
+    /// otherwise, see Drain).
+    /// The delay is always the full STROBE_SETTLE_US (no polling), so the scan stays constant
+    /// time. It must fit within SCAN_PERIOD_US along with the rest of the scan loop. Pass NoDelay
+    /// to skip settling.
+    /// GPIO errors carry the strobe (and row, while draining the sense lines) that failed.
+    pub fn next_strobe<'a, E: 'a, D: DelayUs<u32>>(
+        &'a mut self,
+        delay: &mut D,
+    ) -> Result<usize, ScanError<E>>
     where
-        C: OutputPin<Error = E> + IoPin<R, C>,
-        R: InputPin<Error = E> + IoPin<R, C>,
-        E: core::convert::From<<R as IoPin<R, C>>::Error>
-            + core::convert::From<<C as IoPin<R, C>>::Error>,
+        C: OutputPin<Error = E>,
+        [R; RSIZE]: Drain<C, DRAIN, E>,
+    {
+        let strobe = self.advance_strobe()?;
+        if STROBE_SETTLE_US > 0 {
+            delay.delay_us(STROBE_SETTLE_US);
+        }
+        Ok(strobe)
+    }
+
+    /// Unset the current strobe, drain the sense lines and set the next strobe
+    fn advance_strobe<'a, E: 'a>(&'a mut self) -> Result<usize, ScanError<E>>
+    where
+        C: OutputPin<Error = E>,
+        [R; RSIZE]: Drain<C, DRAIN, E>,
     {
         // Unset current strobe
-        self.cols[self.cur_strobe].set_low()?;
+        let strobe = self.cur_strobe;
+        self.cols[strobe]
+            .set_state(Self::INACTIVE)
+            .map_err(|source| ScanError {
+                strobe,
+                row: None,
+                source,
+            })?;
 
-        // Drain stray potential from sense lines
-        // NOTE: This is unsafe because the gpio are stored in an array and (likely) do not implement
-        //       copy or clone. Since they are in an array, we can't move them either.
-        //       Since we're just temporarily sinking the pin and putting it back, this is safe to
-        //       do.
-        for s in self.rows.iter_mut() {
-            let ptr = s as *const R;
-            unsafe {
-                let row = core::ptr::read(ptr);
-                // Temporarily sink sense gpios and reset to sense/read gpio
-                row.into_output_pin(PinState::Low)?.into_input_pin()?;
-            }
-        }
+        self.rows.drain(strobe, Self::INACTIVE)?;
 
         // Check for roll-over condition
-        if self.cur_strobe >= CSIZE - 1 {
-            self.cur_strobe = 0;
+        if self.position >= CSIZE - 1 {
+            self.position = 0;
         } else {
-            self.cur_strobe += 1;
+            self.position += 1;
         }
+        self.cur_strobe = self.strobe_order[self.position];
 
         // Set new strobe
-        self.cols[self.cur_strobe].set_high()?;
+        let strobe = self.cur_strobe;
+        self.cols[strobe]
+            .set_state(Self::ACTIVE)
+            .map_err(|source| ScanError {
+                strobe,
+                row: None,
+                source,
+            })?;
+        self.strobed = true;
 
         Ok(self.cur_strobe)
     }
 
-    /// Current strobe
+    /// Scan the entire matrix
+    ///
+    /// Senses every column once, starting from the currently strobed column, using the same
+    /// sense then next_strobe ordering as a manual scan loop. Results are indexed by column.
+    /// The priming strobe is done automatically if no column is strobed yet (e.g. after new() or
+    /// clear()).
+    ///
+    /// Every key is recorded exactly once per call, so a call counts as a single debounce cycle.
+    /// The next column is left strobed on return so it can settle until the next call.
+    /// Like the manual loop, calls must be made in constant time (SCAN_PERIOD_US * CSIZE,
+    /// including CSIZE settling delays, see next_strobe).
+    pub fn scan_all<'a, E: 'a, D: DelayUs<u32>>(
+        &'a mut self,
+        delay: &mut D,
+    ) -> Result<[[KeyEvent; RSIZE]; CSIZE], ScanError<E>>
+    where
+        C: OutputPin<Error = E>,
+        R: InputPin<Error = E>,
+        [R; RSIZE]: Drain<C, DRAIN, E>,
+    {
+        let mut res = [[KeyEvent::Off {
+            idle: false,
+            cycles_since_state_change: 0,
+        }; RSIZE]; CSIZE];
+
+        // Prepare first strobe
+        if !self.strobed {
+            self.next_strobe(delay)?;
+        }
+
+        for _ in 0..CSIZE {
+            let (events, sensed) = self.sense::<E>()?;
+            res[sensed] = events;
+            self.next_strobe(delay)?;
+        }
+
+        Ok(res)
+    }
+
+    /// Current strobe (GPIO column)
     pub fn strobe(&self) -> usize {
         self.cur_strobe
     }
 
+    /// Scan period (SCAN_PERIOD_US), time to sense a single column
+    pub fn period(&self) -> u32 {
+        SCAN_PERIOD_US
+    }
+
+    /// Convert KeyEvent/KeyState cycles to us
+    /// A key is sensed once per full matrix scan, so a cycle is SCAN_PERIOD_US * CSIZE.
+    /// Saturates at u32::MAX (a little over an hour).
+    pub fn cycles_to_us(cycles: u32) -> u32 {
+        (cycles as u64 * SCAN_PERIOD_US as u64 * CSIZE as u64).min(u32::MAX as u64) as u32
+    }
+
+    /// Strobe columns in a custom sequence (e.g. to follow the physical column order)
+    ///
+    /// order lists GPIO columns (indices of cols) in the order next_strobe walks them, the
+    /// default is 0..CSIZE. Matrix indices (and scan_all results) are still by GPIO column.
+    /// Returns false (without changing the order) unless order is a permutation of 0..CSIZE.
+    /// The currently strobed column is kept, next_strobe continues from its position in the
+    /// new order. Before the first strobe (see clear) next_strobe starts at the first column of
+    /// the new order.
+    pub fn set_strobe_order(&mut self, order: [usize; CSIZE]) -> bool {
+        let mut seen = [false; CSIZE];
+        for &col in order.iter() {
+            if col >= CSIZE || seen[col] {
+                return false;
+            }
+            seen[col] = true;
+        }
+
+        self.strobe_order = order;
+        if self.strobed {
+            self.position = order
+                .iter()
+                .position(|&col| col == self.cur_strobe)
+                .unwrap_or(CSIZE - 1);
+        } else {
+            self.position = CSIZE - 1;
+            self.cur_strobe = order[self.position];
+        }
+        true
+    }
+
+    /// Sequence of GPIO columns walked by next_strobe (see set_strobe_order)
+    pub fn strobe_order(&self) -> &[usize; CSIZE] {
+        &self.strobe_order
+    }
+
     /// Sense a column of switches
     ///
     /// Returns the results of each row for the currently strobed column and the measured strobe
-    pub fn sense<'a, E: 'a>(&'a mut self) -> Result<([KeyEvent; RSIZE], usize), E>
+    ///
+    /// A failed GPIO read returns the strobe and row of the pin and stops the column early.
+    pub fn sense<'a, E: 'a>(&'a mut self) -> Result<([KeyEvent; RSIZE], usize), ScanError<E>>
+    where
+        E: core::convert::From<<R as InputPin>::Error>,
+    {
+        self.sense_dt(SCAN_PERIOD_US * CSIZE as u32)
+    }
+
+    /// Sense a column of switches, elapsed_us since the column was last sensed
+    ///
+    /// Debounce, idle and auto-repeat timing use the measured time rather than
+    /// SCAN_PERIOD_US * CSIZE, e.g. when the scan loop isn't constant time (see
+    /// KeyState::record_dt). Cycle counts still count scans. Otherwise the same as sense.
+    pub fn sense_dt<'a, E: 'a>(
+        &'a mut self,
+        elapsed_us: u32,
+    ) -> Result<([KeyEvent; RSIZE], usize), ScanError<E>>
     where
         E: core::convert::From<<R as InputPin>::Error>,
     {
@@ -210,12 +509,20 @@ impl<
         }; RSIZE];
 
         for (i, r) in self.rows.iter().enumerate() {
-            // Read GPIO
-            let on = r.is_high()?;
+            // Read GPIO (logical pressed state, debouncing is polarity independent)
+            let on =
+                if ACTIVE_LOW { r.is_low() } else { r.is_high() }.map_err(|err| ScanError {
+                    strobe: self.cur_strobe,
+                    row: Some(i),
+                    source: E::from(err),
+                })?;
             // Determine matrix index
             let index = self.cur_strobe * RSIZE + i;
             // Record GPIO event and determine current status after debouncing algorithm
-            let (keystate, idle, cycles_since_state_change) = self.state_matrix[index].record(on);
+            let previous = self.state_matrix[index].state().0;
+            let (keystate, idle, cycles_since_state_change) =
+                self.state_matrix[index].record_dt_with(on, elapsed_us, self.debounce);
+            self.changed[index] = keystate != previous;
 
             // Assign KeyEvent using the output keystate
             res[i] = if keystate == State::On {
@@ -230,9 +537,276 @@ impl<
             };
         }
 
+        // Mask phantom keys (after recording the whole column)
+        if self.mask_ghosts {
+            for (i, event) in res.iter_mut().enumerate() {
+                if let KeyEvent::On {
+                    cycles_since_state_change,
+                } = *event
+                {
+                    if self.ghost(self.cur_strobe, i) {
+                        *event = KeyEvent::Off {
+                            idle: false,
+                            cycles_since_state_change,
+                        };
+                    }
+                }
+            }
+        }
+
+        // Auto-repeat (after masking so phantom keys never repeat)
+        // Derived from the hold time, releasing the key cancels any pending repeats.
+        if REPEAT_DELAY_MS > 0 && REPEAT_RATE_MS > 0 {
+            for (i, event) in res.iter_mut().enumerate() {
+                if let KeyEvent::On {
+                    cycles_since_state_change,
+                } = *event
+                {
+                    let state = &mut self.state_matrix[self.cur_strobe * RSIZE + i];
+                    let count = Self::repeats(state.us_since_state_change());
+                    if state.repeat(count) {
+                        *event = KeyEvent::Repeat {
+                            count,
+                            cycles_since_state_change,
+                        };
+                    }
+                }
+            }
+        }
+
         Ok((res, self.cur_strobe))
     }
 
+    /// Number of repeats of a key held for held_us
+    fn repeats(held_us: u64) -> u32 {
+        let delay_us = REPEAT_DELAY_MS as u64 * 1000;
+        if held_us < delay_us {
+            0
+        } else {
+            ((held_us - delay_us) / (REPEAT_RATE_MS as u64 * 1000) + 1) as u32
+        }
+    }
+
+    /// Manufacturing self-test for shorted strobes and senses (e.g. solder bridges)
+    ///
+    /// No key may be pressed while testing (a pressed key looks like a strobe/sense short).
+    /// Not meant for the scan loop, the key states are not updated.
+    /// 1. Each strobe is set individually, any sense responding is shorted to the strobe and any
+    ///    other strobe (temporarily an input) responding is shorted to the strobe.
+    /// 2. With every strobe released, each sense is driven individually (temporarily an output),
+    ///    any other sense responding is shorted to it.
+    ///
+    /// Every strobe is released afterwards (see clear), the rows (and columns) must be IoPins.
+    /// GPIO errors carry the strobe and row of the failing pin (like next_strobe), errors of the
+    /// sense to sense tests carry the released strobe position (see clear).
+    pub fn self_test<'a, E: 'a>(&'a mut self) -> Result<SelfTestReport, ScanError<E>>
+    where
+        C: OutputPin<Error = E> + IoPin<R, C>,
+        R: InputPin<Error = E> + IoPin<R, C>,
+        E: core::convert::From<<R as IoPin<R, C>>::Error>
+            + core::convert::From<<C as IoPin<R, C>>::Error>,
+    {
+        let err = |strobe, row| {
+            move |source| ScanError {
+                strobe,
+                row,
+                source,
+            }
+        };
+        let mut report = SelfTestReport::new();
+        self.clear()?;
+
+        // NOTE: Pins are temporarily converted in place, see Drain
+        for strobe in 0..CSIZE {
+            self.cols[strobe]
+                .set_state(Self::ACTIVE)
+                .map_err(err(strobe, None))?;
+            for (row, sense) in self.rows.iter().enumerate() {
+                if Self::active(sense).map_err(err(strobe, Some(row)))? {
+                    report.add(MatrixPin::Strobe(strobe), MatrixPin::Sense(row));
+                }
+            }
+            for other in (0..CSIZE).filter(|other| *other != strobe) {
+                let ptr = &self.cols[other] as *const C;
+                let active = unsafe {
+                    let input = core::ptr::read(ptr)
+                        .into_input_pin()
+                        .map_err(E::from)
+                        .map_err(err(other, None))?;
+                    let active = Self::active(&input).map_err(err(other, None))?;
+                    input
+                        .into_output_pin(Self::INACTIVE)
+                        .map_err(E::from)
+                        .map_err(err(other, None))?;
+                    active
+                };
+                if active {
+                    report.add(MatrixPin::Strobe(strobe), MatrixPin::Strobe(other));
+                }
+            }
+            self.cols[strobe]
+                .set_state(Self::INACTIVE)
+                .map_err(err(strobe, None))?;
+        }
+
+        // No strobe is set while testing the senses, errors carry the cleared strobe position
+        let strobe = self.cur_strobe;
+        for sense in 0..RSIZE {
+            let ptr = &self.rows[sense] as *const R;
+            let output = unsafe {
+                core::ptr::read(ptr)
+                    .into_output_pin(Self::ACTIVE)
+                    .map_err(E::from)
+                    .map_err(err(strobe, Some(sense)))?
+            };
+            for (other, row) in self.rows.iter().enumerate() {
+                if other != sense && Self::active(row).map_err(err(strobe, Some(other)))? {
+                    report.add(MatrixPin::Sense(sense), MatrixPin::Sense(other));
+                }
+            }
+            output
+                .into_input_pin()
+                .map_err(E::from)
+                .map_err(err(strobe, Some(sense)))?;
+        }
+
+        self.clear()?;
+        Ok(report)
+    }
+
+    /// Input is at the active level (see ACTIVE_LOW)
+    fn active<P: InputPin>(pin: &P) -> Result<bool, P::Error> {
+        if ACTIVE_LOW {
+            pin.is_low()
+        } else {
+            pin.is_high()
+        }
+    }
+
+    /// Override the debounce period (in us) of a single key, None uses DEBOUNCE_US
+    /// Returns false if the index is out of range.
+    pub fn set_debounce(&mut self, index: usize, us: Option<u32>) -> bool {
+        match self.state_matrix.get_mut(index) {
+            Some(state) => {
+                state.set_debounce(us);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Number of unsettled raw reading flips of a key (see KeyState::bounce_count)
+    pub fn bounce_count(&self, index: usize) -> Option<u32> {
+        self.state_matrix
+            .get(index)
+            .map(|state| state.bounce_count())
+    }
+
+    /// Reset the bounce counter of a key
+    /// Returns false if the index is out of range.
+    pub fn reset_bounce_count(&mut self, index: usize) -> bool {
+        match self.state_matrix.get_mut(index) {
+            Some(state) => {
+                state.reset_bounce_count();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Select the debounce algorithm (DebounceMode::Deferred by default)
+    /// See DebounceMode for the latency vs. robustness tradeoff. Set before scanning.
+    pub fn set_debounce_mode(&mut self, mode: DebounceMode) {
+        self.debounce = mode;
+    }
+
+    /// Report suspected phantom keys as off in sense (and scan_all) results
+    ///
+    /// On a matrix without per-key diodes, pressing three corners of a rectangle (two rows
+    /// sharing two pressed columns) also reads the fourth corner as pressed. The most recently
+    /// pressed corner is assumed to be the phantom (corners pressed in the same cycle are all
+    /// masked). The recorded KeyState (see state and states) is not changed.
+    /// Masking checks every other key, so sense takes longer (but still constant time).
+    pub fn set_ghost_mask(&mut self, mask: bool) {
+        self.mask_ghosts = mask;
+    }
+
+    /// Detect a ghosting condition in the debounced state
+    ///
+    /// Returns the (column, row) of the four corners of the first rectangle of pressed keys
+    /// found (any one of them may be a phantom), or None if there isn't one.
+    pub fn detect_ghosting(&self) -> Option<[(usize, usize); 4]> {
+        for c1 in 0..CSIZE {
+            for c2 in c1 + 1..CSIZE {
+                for r1 in 0..RSIZE {
+                    if !self.is_on(c1, r1) || !self.is_on(c2, r1) {
+                        continue;
+                    }
+                    for r2 in r1 + 1..RSIZE {
+                        if self.is_on(c1, r2) && self.is_on(c2, r2) {
+                            return Some([(c1, r1), (c1, r2), (c2, r1), (c2, r2)]);
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Number of pressed keys (debounced)
+    pub fn pressed_count(&self) -> usize {
+        self.states()
+            .filter(|(_, state)| *state == State::On)
+            .count()
+    }
+
+    /// Additional key presses can still be reported reliably
+    ///
+    /// False once three corners of a rectangle are pressed (a pressed key sharing a column and
+    /// a row with other pressed keys), as a further key or a phantom at the fourth corner can
+    /// no longer be told apart. Also false while ghosting (see detect_ghosting).
+    pub fn rollover_safe(&self) -> bool {
+        for col in 0..CSIZE {
+            for row in 0..RSIZE {
+                if !self.is_on(col, row) {
+                    continue;
+                }
+                let shares_col = (0..RSIZE).any(|r| r != row && self.is_on(col, r));
+                let shares_row = (0..CSIZE).any(|c| c != col && self.is_on(c, row));
+                if shares_col && shares_row {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Key is pressed (debounced)
+    fn is_on(&self, col: usize, row: usize) -> bool {
+        self.state_matrix[col * RSIZE + row].state().0 == State::On
+    }
+
+    /// Key completes a rectangle of pressed keys and was the last of them to be pressed
+    fn ghost(&self, col: usize, row: usize) -> bool {
+        let age = |col: usize, row: usize| self.state_matrix[col * RSIZE + row].state().2;
+        let key = age(col, row);
+        let mut ghost = false;
+        for c in (0..CSIZE).filter(|&c| c != col) {
+            if !self.is_on(c, row) {
+                continue;
+            }
+            for r in (0..RSIZE).filter(|&r| r != row) {
+                if self.is_on(col, r)
+                    && self.is_on(c, r)
+                    && key <= age(c, row).min(age(col, r)).min(age(c, r))
+                {
+                    ghost = true;
+                }
+            }
+        }
+        ghost
+    }
+
     /// Return the KeyState for a given index
     pub fn state(
         &self,
@@ -245,6 +819,47 @@ impl<
         }
     }
 
+    /// Return the debounced State for a given index
+    pub fn state_at(&self, index: usize) -> Option<State> {
+        self.state_matrix.get(index).map(|state| state.state().0)
+    }
+
+    /// Iterate over the debounced State of every key, with its matrix index
+    /// Reflects the last recorded state (no GPIO is read), e.g. to build a full report after a
+    /// complete scan.
+    pub fn states(&self) -> impl Iterator<Item = (usize, State)> + '_ {
+        self.state_matrix
+            .iter()
+            .enumerate()
+            .map(|(index, state)| (index, state.state().0))
+    }
+
+    /// Every key is off and idle (e.g. to decide when to enter deep sleep)
+    pub fn all_idle(&self) -> bool {
+        self.state_matrix.iter().all(|state| state.idle())
+    }
+
+    /// Cycles until the last active key becomes idle (see KeyState::cycles_until_idle)
+    /// 0 if all keys are idle, u32::MAX while any key is on.
+    pub fn cycles_until_idle(&self) -> u32 {
+        self.state_matrix
+            .iter()
+            .map(|state| state.cycles_until_idle())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Keys whose debounced State changed during the most recent scan pass, with their new State
+    /// Each key reflects the last sense of its column (flags are refreshed by every sense), so
+    /// after a full scan_all this is every change of that scan.
+    pub fn changes(&self) -> impl Iterator<Item = (usize, State)> + '_ {
+        self.changed
+            .iter()
+            .enumerate()
+            .filter(|(_, changed)| **changed)
+            .map(|(index, _)| (index, self.state_matrix[index].state().0))
+    }
+
     /// Generate event from KeyState
     /// Useful when trying to determine if a key has not been pressed
     pub fn generate_event(&self, index: usize) -> Option<KeyEvent> {
@@ -289,6 +904,14 @@ mod converters {
                         }
                     }
                 }
+                KeyEvent::Repeat {
+                    cycles_since_state_change,
+                    ..
+                } => kll_core::TriggerEvent::Switch {
+                    state: kll_core::trigger::Phro::Hold,
+                    index: index as u16,
+                    last_state: *cycles_since_state_change,
+                },
                 KeyEvent::Off {
                     idle: _,
                     cycles_since_state_change,
@@ -0,0 +1,58 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+/// Maximum number of shorts recorded by a SelfTestReport
+pub const SELF_TEST_SHORTS: usize = 16;
+
+/// Matrix GPIO (see Matrix::self_test)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum MatrixPin {
+    /// Strobe (column)
+    Strobe(usize),
+    /// Sense (row)
+    Sense(usize),
+}
+
+/// Result of Matrix::self_test
+///
+/// Lists the suspected shorted pin pairs (e.g. solder bridges), each pair once.
+#[derive(Copy, Clone, Debug, PartialEq, defmt::Format)]
+pub struct SelfTestReport {
+    shorts: [Option<(MatrixPin, MatrixPin)>; SELF_TEST_SHORTS],
+    /// More than SELF_TEST_SHORTS shorts were found, the rest are not listed
+    pub truncated: bool,
+}
+
+impl SelfTestReport {
+    pub(crate) fn new() -> Self {
+        Self {
+            shorts: [None; SELF_TEST_SHORTS],
+            truncated: false,
+        }
+    }
+
+    /// Record a short between a and b (ignored if the pair is already listed)
+    pub(crate) fn add(&mut self, a: MatrixPin, b: MatrixPin) {
+        if self.shorts().any(|pair| pair == (a, b) || pair == (b, a)) {
+            return;
+        }
+        match self.shorts.iter_mut().find(|short| short.is_none()) {
+            Some(short) => *short = Some((a, b)),
+            None => self.truncated = true,
+        }
+    }
+
+    /// Suspected shorted pin pairs, in the order they were found
+    pub fn shorts(&self) -> impl Iterator<Item = (MatrixPin, MatrixPin)> + '_ {
+        self.shorts.iter().map_while(|short| *short)
+    }
+
+    /// No shorts were found
+    pub fn passed(&self) -> bool {
+        self.shorts[0].is_none()
+    }
+}
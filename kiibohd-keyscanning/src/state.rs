@@ -25,6 +25,22 @@ impl Not for State {
     }
 }
 
+/// Debounce algorithm (see KeyState::record_with)
+#[derive(PartialEq, Copy, Clone, Debug, Default, defmt::Format)]
+pub enum DebounceMode {
+    /// Wait for the reading to be stable for the debounce period before reporting the change
+    ///
+    /// Latency is the debounce period (plus up to a scan period). Robust for noisy switches, short
+    /// glitches (e.g. EMI) and contact bounce never reach the debounced state.
+    #[default]
+    Deferred,
+    /// Report the first changed reading immediately, then ignore the key for the debounce period
+    ///
+    /// Latency is a single scan (e.g. for gaming), but there is no noise rejection: a single
+    /// glitched reading is reported as a full press (or release) lasting the debounce period.
+    Eager,
+}
+
 /// The KeyState handles all of the decision making and state changes based on a high or low signal from a GPIO pin
 #[derive(Copy, Clone)]
 pub struct KeyState<
@@ -42,7 +58,7 @@ pub struct KeyState<
     /// Used to determine if the key is idle (in Off state for IDLE_MS)
     idle: bool,
 
-    /// Tracking bounce
+    /// Tracking bounce (Deferred), or locked out after a state change (Eager)
     debounce_tracking: bool,
 
     /// Used to determine the state after debounce
@@ -56,10 +72,23 @@ pub struct KeyState<
     /// Used to track the number of cycles since state has changed.
     cycles_since_state_change: u32,
 
+    /// Time (us) since state has changed (saturates)
+    /// u64 so IDLE_MS isn't capped at u32::MAX us (a little over an hour).
+    us_since_state_change: u64,
+
     /// This is used to track the list GPIO read bounce
     ///
-    /// If cycles * scan_period > DEBOUNCE_US then raw_state is assigned to state.
-    cycles_since_last_bounce: u32,
+    /// If us_since_last_bounce >= DEBOUNCE_US then raw_state is assigned to state.
+    us_since_last_bounce: u32,
+
+    /// Per-key debounce period override, None uses DEBOUNCE_US
+    debounce_us: Option<u32>,
+
+    /// Raw reading flips while the key was still unsettled (saturates)
+    bounce_count: u32,
+
+    /// Last reported auto-repeat count (see Matrix REPEAT_DELAY_MS)
+    repeats: u32,
 }
 
 impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, const IDLE_MS: u32>
@@ -73,15 +102,99 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
             debounce_tracking: false,
             raw_state_average: 0,
             cycles_since_state_change: 0,
-            cycles_since_last_bounce: 0,
+            us_since_state_change: 0,
+            us_since_last_bounce: 0,
+            debounce_us: None,
+            bounce_count: 0,
+            repeats: 0,
         }
     }
 
+    /// Override the debounce period (in us) of this key, None uses DEBOUNCE_US
+    /// e.g. for a switch that chatters worse than the others.
+    pub fn set_debounce(&mut self, us: Option<u32>) {
+        self.debounce_us = us;
+    }
+
+    /// Debounce period (in us) of this key
+    pub fn debounce_us(&self) -> u32 {
+        self.debounce_us.unwrap_or(DEBOUNCE_US)
+    }
+
     /// Record the GPIO read event and adjust debounce state machine accordingly
+    /// Uses DebounceMode::Deferred
     ///
     /// Returns:
     /// (State, idle, cycles_since_state_change)
     pub fn record(&mut self, on: bool) -> (State, bool, u32) {
+        self.record_with(on, DebounceMode::Deferred)
+    }
+
+    /// Record the GPIO read event using the given debounce algorithm
+    /// The mode should not be changed while a key is bouncing.
+    ///
+    /// Returns:
+    /// (State, idle, cycles_since_state_change)
+    pub fn record_with(&mut self, on: bool, mode: DebounceMode) -> (State, bool, u32) {
+        self.record_dt_with(on, SCAN_PERIOD_US * CSIZE as u32, mode)
+    }
+
+    /// Record the GPIO read event, elapsed_us since the previous record
+    /// Debounce and idle timing use the measured time rather than SCAN_PERIOD_US, e.g. when the
+    /// scan rate varies with the number of active keys. Uses DebounceMode::Deferred
+    ///
+    /// Returns:
+    /// (State, idle, cycles_since_state_change)
+    pub fn record_dt(&mut self, on: bool, elapsed_us: u32) -> (State, bool, u32) {
+        self.record_dt_with(on, elapsed_us, DebounceMode::Deferred)
+    }
+
+    /// Record the GPIO read event using the given debounce algorithm, elapsed_us since the
+    /// previous record (see record_dt and record_with)
+    ///
+    /// Returns:
+    /// (State, idle, cycles_since_state_change)
+    pub fn record_dt_with(
+        &mut self,
+        on: bool,
+        elapsed_us: u32,
+        mode: DebounceMode,
+    ) -> (State, bool, u32) {
+        match mode {
+            DebounceMode::Deferred => self.record_deferred(on, elapsed_us),
+            DebounceMode::Eager => self.record_eager(on, elapsed_us),
+        }
+    }
+
+    fn record_eager(&mut self, on: bool, elapsed_us: u32) -> (State, bool, u32) {
+        let raw_state = if on { State::On } else { State::Off };
+        let flipped = raw_state != self.raw_state;
+        self.raw_state = raw_state;
+
+        // Lockout expired
+        if self.debounce_tracking && self.us_since_state_change >= self.debounce_us() as u64 {
+            self.debounce_tracking = false;
+        }
+
+        // Flipped during the lockout
+        if flipped && self.debounce_tracking {
+            self.bounce_count = self.bounce_count.saturating_add(1);
+        }
+
+        // Report the change immediately and start the lockout
+        if !self.debounce_tracking && self.raw_state != self.state {
+            self.state = self.raw_state;
+            self.idle = false;
+            self.debounce_tracking = true;
+            self.cycles_since_state_change = 0;
+            self.us_since_state_change = 0;
+            return self.state();
+        }
+
+        self.tick(elapsed_us)
+    }
+
+    fn record_deferred(&mut self, on: bool, elapsed_us: u32) -> (State, bool, u32) {
         // Track raw state average
         // This is used to set the new state
         if self.debounce_tracking {
@@ -98,8 +211,14 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
             // Update raw state
             self.raw_state = if on { State::On } else { State::Off };
 
-            // Reset bounce cycle counter
-            self.cycles_since_last_bounce = 0;
+            // Flipped again before the previous flip settled
+            let elapsed = self.us_since_last_bounce.saturating_add(elapsed_us);
+            if self.debounce_tracking && elapsed < self.debounce_us() {
+                self.bounce_count = self.bounce_count.saturating_add(1);
+            }
+
+            // Reset bounce timer
+            self.us_since_last_bounce = 0;
 
             // Start debounce tracking (if we haven't already started)
             self.debounce_tracking = true;
@@ -109,14 +228,14 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
             return self.state();
         }
 
-        // Increment debounce cycle counter
-        self.cycles_since_last_bounce += 1;
+        // Increment debounce timer
+        self.us_since_last_bounce = self.us_since_last_bounce.saturating_add(elapsed_us);
 
         // Update the debounced state if it has changed and exceeded the debounce timer
         // (debounce timer resets if there is any bouncing during the debounce interval).
-        if self.cycles_since_last_bounce * SCAN_PERIOD_US * CSIZE as u32 >= DEBOUNCE_US {
-            // Since we have hit the cycles_since_last_bounce threshold, we can keep it here
-            self.cycles_since_last_bounce -= 1;
+        if self.us_since_last_bounce >= self.debounce_us() {
+            // Since we have hit the us_since_last_bounce threshold, we can keep it here
+            self.us_since_last_bounce = self.us_since_last_bounce.saturating_sub(elapsed_us);
 
             if self.raw_state != self.state && self.raw_state_average != 0 {
                 // Update state
@@ -139,6 +258,7 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
                 if new_state != self.state {
                     self.state = new_state;
                     self.cycles_since_state_change = 0;
+                    self.us_since_state_change = 0;
                 }
 
                 // Return current state
@@ -146,13 +266,18 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
             }
         }
 
-        // Increment state cycle counter
-        self.cycles_since_state_change += 1;
+        self.tick(elapsed_us)
+    }
+
+    /// A cycle without a state change
+    fn tick(&mut self, elapsed_us: u32) -> (State, bool, u32) {
+        // Increment state cycle counter and timer
+        self.cycles_since_state_change = self.cycles_since_state_change.saturating_add(1);
+        self.us_since_state_change = self.us_since_state_change.saturating_add(elapsed_us as u64);
 
         // Determine if key is idle
         // Must be both in the off state and have been off >= IDLE_MS
-        self.idle = self.state == State::Off
-            && self.cycles_since_state_change * SCAN_PERIOD_US * CSIZE as u32 / 1000 >= IDLE_MS;
+        self.idle = self.state == State::Off && self.us_since_state_change / 1000 >= IDLE_MS as u64;
 
         // Return current state
         self.state()
@@ -171,6 +296,45 @@ impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const DEBOUNCE_US: u32, cons
         self.cycles_since_state_change
     }
 
+    /// Time (us) since the last state change
+    /// Measured (see record_dt) rather than derived from the cycle count.
+    pub fn us_since_state_change(&self) -> u64 {
+        self.us_since_state_change
+    }
+
+    /// Number of raw reading flips while the key was still unsettled (i.e. within the debounce
+    /// period of the previous flip, or during the Eager lockout)
+    /// Near zero for a healthy switch, a failing switch chatters a lot.
+    pub fn bounce_count(&self) -> u32 {
+        self.bounce_count
+    }
+
+    /// Reset the bounce counter
+    pub fn reset_bounce_count(&mut self) {
+        self.bounce_count = 0;
+    }
+
+    /// Cycles until the key becomes idle (IDLE_MS in the off state)
+    /// 0 if already idle, u32::MAX while the key is on (it must be released first).
+    /// The remaining time is converted using SCAN_PERIOD_US (see record_dt).
+    pub fn cycles_until_idle(&self) -> u32 {
+        if self.state == State::On {
+            return u32::MAX;
+        }
+        let period = SCAN_PERIOD_US as u64 * CSIZE as u64;
+        let remaining = (IDLE_MS as u64 * 1000).saturating_sub(self.us_since_state_change);
+        remaining.div_ceil(period.max(1)).min(u32::MAX as u64) as u32
+    }
+
+    /// Track the auto-repeat count of a held key
+    /// Returns true if count is a new repeat (each count is only reported once, even if the
+    /// cycle counter stalls while bouncing). A new press starts over from 0.
+    pub(crate) fn repeat(&mut self, count: u32) -> bool {
+        let new = count > self.repeats;
+        self.repeats = count;
+        new
+    }
+
     /// True if the switch is idle
     /// idle indicates the switch is off and there have been no events
     pub fn idle(&self) -> bool {
@@ -0,0 +1,157 @@
+// Copyright 2021-2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use crate::KeyEvent;
+
+/// At least ms have elapsed after cycles full matrix scans
+fn elapsed<const CSIZE: usize, const SCAN_PERIOD_US: u32>(cycles: u32, ms: u32) -> bool {
+    cycles as u64 * SCAN_PERIOD_US as u64 * CSIZE as u64 >= ms as u64 * 1000
+}
+
+/// Counts consecutive taps of a single key (e.g. for tap-dance style bindings)
+///
+/// Feed every KeyEvent of the key (one per scan) to add. Each press is a tap, the sequence ends
+/// once the key has been released for TAP_WINDOW_MS and the final tap count is returned.
+/// Holding the key keeps the sequence open until it is released.
+/// The timing uses cycles_since_state_change, like the idle/debounce timers (CSIZE and
+/// SCAN_PERIOD_US should match the Matrix).
+/// Presses are detected from the previous event, cycles_since_state_change may stay 0 for a few
+/// scans while the key bounces after the press.
+#[derive(Copy, Clone, Debug, Default, PartialEq, defmt::Format)]
+pub struct TapCounter<const CSIZE: usize, const SCAN_PERIOD_US: u32, const TAP_WINDOW_MS: u32> {
+    taps: u8,
+    /// Previous event was On/Repeat
+    pressed: bool,
+}
+
+impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const TAP_WINDOW_MS: u32>
+    TapCounter<CSIZE, SCAN_PERIOD_US, TAP_WINDOW_MS>
+{
+    pub fn new() -> Self {
+        Self {
+            taps: 0,
+            pressed: false,
+        }
+    }
+
+    /// Add the next event of the key
+    /// Returns the final tap count once the inter-tap gap exceeds TAP_WINDOW_MS.
+    pub fn add(&mut self, event: KeyEvent) -> Option<u8> {
+        match event {
+            KeyEvent::On { .. } | KeyEvent::Repeat { .. } => {
+                if !core::mem::replace(&mut self.pressed, true) {
+                    self.taps = self.taps.saturating_add(1);
+                }
+                None
+            }
+            KeyEvent::Off {
+                cycles_since_state_change,
+                ..
+            } => {
+                self.pressed = false;
+                if self.taps > 0
+                    && elapsed::<CSIZE, SCAN_PERIOD_US>(cycles_since_state_change, TAP_WINDOW_MS)
+                {
+                    Some(core::mem::take(&mut self.taps))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Taps in the current (unfinished) sequence
+    pub fn taps(&self) -> u8 {
+        self.taps
+    }
+
+    /// Drop the current sequence without reporting it
+    pub fn reset(&mut self) {
+        self.taps = 0;
+    }
+}
+
+/// Tap or hold decision of a key press (see TapHold)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, defmt::Format)]
+pub enum TapHoldDecision {
+    /// Released before HOLD_THRESHOLD_MS
+    Tap,
+    /// Held for HOLD_THRESHOLD_MS (the key may still be pressed)
+    Hold,
+}
+
+/// Classifies each press of a single key as a tap or a hold (e.g. for home-row mods)
+///
+/// Feed every KeyEvent of the key (one per scan) to add. A tap is reported when the key is
+/// released before HOLD_THRESHOLD_MS, a hold as soon as the key has been held for
+/// HOLD_THRESHOLD_MS (the release of a hold is not reported again).
+/// The timing uses cycles_since_state_change and presses are detected from the previous event,
+/// like TapCounter (CSIZE and SCAN_PERIOD_US should match the Matrix).
+#[derive(Copy, Clone, Debug, Default, PartialEq, defmt::Format)]
+pub struct TapHold<const CSIZE: usize, const SCAN_PERIOD_US: u32, const HOLD_THRESHOLD_MS: u32> {
+    /// Pressed and not decided yet
+    pending: bool,
+    /// Previous event was On/Repeat
+    pressed: bool,
+}
+
+impl<const CSIZE: usize, const SCAN_PERIOD_US: u32, const HOLD_THRESHOLD_MS: u32>
+    TapHold<CSIZE, SCAN_PERIOD_US, HOLD_THRESHOLD_MS>
+{
+    pub fn new() -> Self {
+        Self {
+            pending: false,
+            pressed: false,
+        }
+    }
+
+    /// Add the next event of the key
+    /// Returns the decision once it's known (at most once per press).
+    pub fn add(&mut self, event: KeyEvent) -> Option<TapHoldDecision> {
+        match event {
+            KeyEvent::On {
+                cycles_since_state_change,
+            }
+            | KeyEvent::Repeat {
+                cycles_since_state_change,
+                ..
+            } => {
+                if !core::mem::replace(&mut self.pressed, true) {
+                    self.pending = true;
+                }
+                if self.pending
+                    && elapsed::<CSIZE, SCAN_PERIOD_US>(
+                        cycles_since_state_change,
+                        HOLD_THRESHOLD_MS,
+                    )
+                {
+                    self.pending = false;
+                    return Some(TapHoldDecision::Hold);
+                }
+                None
+            }
+            KeyEvent::Off { .. } => {
+                self.pressed = false;
+                if core::mem::take(&mut self.pending) {
+                    Some(TapHoldDecision::Tap)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Key is pressed and not decided yet (e.g. to buffer other keys until it is)
+    pub fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Drop the current press without reporting it
+    pub fn reset(&mut self) {
+        self.pending = false;
+    }
+}
@@ -0,0 +1,1280 @@
+// Copyright 2022 Jacob Alexander
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+#![cfg(test)]
+
+// ----- Crates -----
+
+extern crate std;
+
+use super::*;
+use core::cell::Cell;
+use std::boxed::Box;
+
+// ----- Types -----
+
+const CSIZE: usize = 3;
+const RSIZE: usize = 2;
+const MSIZE: usize = RSIZE * CSIZE;
+const SCAN_PERIOD_US: u32 = 40;
+// 4 full matrix scans
+const DEBOUNCE_US: u32 = 4 * SCAN_PERIOD_US * CSIZE as u32;
+const IDLE_MS: u32 = 1000;
+const STROBE_SETTLE_US: u32 = 5;
+
+type TestMatrix<const SETTLE: u32 = 0, const ACTIVE_LOW: bool = false> =
+    Matrix<Pin, Pin, CSIZE, RSIZE, MSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS, SETTLE, ACTIVE_LOW>;
+
+/// Simulated switch matrix
+#[derive(Default)]
+struct Board {
+    /// Electrical level of each strobe
+    strobes: [Cell<bool>; CSIZE],
+    pressed: [[Cell<bool>; RSIZE]; CSIZE],
+    /// Strobes are driven low, sense lines are pulled up
+    active_low: bool,
+    /// Strobe that fails to be driven
+    faulty_strobe: Cell<Option<usize>>,
+    /// Sense line that fails to be read
+    faulty_row: Cell<Option<usize>>,
+    /// Solder bridge between two lines
+    short: Cell<Option<(MatrixPin, MatrixPin)>>,
+    /// Strobe switched to an input
+    floating: [Cell<bool>; CSIZE],
+    /// Sense line switched to an output, driven active (true) or inactive (false)
+    driven_rows: [Cell<Option<bool>>; RSIZE],
+}
+
+impl Board {
+    fn new() -> &'static Board {
+        Box::leak(Box::default())
+    }
+
+    fn new_active_low() -> &'static Board {
+        Box::leak(Box::new(Board {
+            active_low: true,
+            ..Board::default()
+        }))
+    }
+
+    /// Currently strobed column, panics if more than one column is strobed
+    fn strobed(&self) -> Option<usize> {
+        let active = !self.active_low;
+        let mut strobed = self
+            .strobes
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.get() == active);
+        let col = strobed.next().map(|(col, _)| col);
+        assert!(strobed.next().is_none(), "Multiple columns strobed");
+        col
+    }
+
+    /// Line is driven active
+    fn driven(&self, line: MatrixPin) -> bool {
+        match line {
+            MatrixPin::Strobe(col) => {
+                !self.floating[col].get() && self.strobes[col].get() != self.active_low
+            }
+            MatrixPin::Sense(row) => self.driven_rows[row].get() == Some(true),
+        }
+    }
+
+    /// Line reads active, driven lines read their own level
+    fn active(&self, line: MatrixPin) -> bool {
+        match line {
+            MatrixPin::Strobe(col) if !self.floating[col].get() => self.driven(line),
+            MatrixPin::Sense(row) if self.driven_rows[row].get().is_some() => self.driven(line),
+            _ => {
+                let pressed = match line {
+                    MatrixPin::Sense(row) => self
+                        .strobed()
+                        .is_some_and(|col| self.pressed[col][row].get()),
+                    MatrixPin::Strobe(_) => false,
+                };
+                let bridged = match self.short.get() {
+                    Some((a, b)) if a == line => self.driven(b),
+                    Some((a, b)) if b == line => self.driven(a),
+                    _ => false,
+                };
+                pressed || bridged
+            }
+        }
+    }
+
+    fn matrix<const SETTLE: u32>(&'static self) -> TestMatrix<SETTLE> {
+        self.matrix_with::<SETTLE, false>()
+    }
+
+    fn matrix_with<const SETTLE: u32, const ACTIVE_LOW: bool>(
+        &'static self,
+    ) -> TestMatrix<SETTLE, ACTIVE_LOW> {
+        let cols = core::array::from_fn(|col| Pin::Col(self, col));
+        let rows = core::array::from_fn(|row| Pin::Row(self, row));
+        TestMatrix::<SETTLE, ACTIVE_LOW>::new::<Fault>(cols, rows).unwrap()
+    }
+}
+
+/// Records each delay along with the column strobed at the time
+struct Delay {
+    board: &'static Board,
+    delays: std::vec::Vec<(Option<usize>, u32)>,
+}
+
+impl DelayUs<u32> for Delay {
+    fn delay_us(&mut self, us: u32) {
+        self.delays.push((self.board.strobed(), us));
+    }
+}
+
+/// GPIO failure of a simulated matrix
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Fault;
+
+/// GPIO pin of a simulated matrix
+/// Copy, since next_strobe temporarily duplicates the sense pins
+#[derive(Clone, Copy)]
+enum Pin {
+    Col(&'static Board, usize),
+    Row(&'static Board, usize),
+}
+
+impl OutputPin for Pin {
+    type Error = Fault;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        if let Pin::Col(board, col) = self {
+            if board.faulty_strobe.get() == Some(*col) {
+                return Err(Fault);
+            }
+            board.strobes[*col].set(false);
+        }
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if let Pin::Col(board, col) = self {
+            if board.faulty_strobe.get() == Some(*col) {
+                return Err(Fault);
+            }
+            board.strobes[*col].set(true);
+        }
+        Ok(())
+    }
+}
+
+impl InputPin for Pin {
+    type Error = Fault;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(match self {
+            Pin::Row(board, row) if board.faulty_row.get() == Some(*row) => return Err(Fault),
+            Pin::Row(board, row) => board.active(MatrixPin::Sense(*row)) != board.active_low,
+            Pin::Col(board, col) => board.active(MatrixPin::Strobe(*col)) != board.active_low,
+        })
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_high()?)
+    }
+}
+
+impl IoPin<Pin, Pin> for Pin {
+    type Error = Fault;
+
+    fn into_input_pin(self) -> Result<Pin, Self::Error> {
+        match self {
+            Pin::Col(board, col) => board.floating[col].set(true),
+            Pin::Row(board, row) => board.driven_rows[row].set(None),
+        }
+        Ok(self)
+    }
+
+    fn into_output_pin(self, state: PinState) -> Result<Pin, Self::Error> {
+        let high = state == PinState::High;
+        match self {
+            Pin::Col(board, col) => {
+                board.floating[col].set(false);
+                board.strobes[col].set(high);
+            }
+            Pin::Row(board, row) => board.driven_rows[row].set(Some(high != board.active_low)),
+        }
+        Ok(self)
+    }
+}
+
+/// GPIO pin without IoPin (e.g. a HAL that doesn't support switching directions)
+struct PlainPin(Pin);
+
+impl OutputPin for PlainPin {
+    type Error = Fault;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.0.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.0.set_high()
+    }
+}
+
+impl InputPin for PlainPin {
+    type Error = Fault;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.0.is_high()
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.0.is_low()
+    }
+}
+
+fn is_on(event: &KeyEvent) -> bool {
+    matches!(event, KeyEvent::On { .. } | KeyEvent::Repeat { .. })
+}
+
+// ----- Tests -----
+
+#[test]
+fn scan_all() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert_eq!(board.strobed(), None);
+
+    // Priming strobe is handled internally
+    board.pressed[1][0].set(true);
+    let states = matrix.scan_all(&mut NoDelay).unwrap();
+    assert!(states.iter().flatten().all(|event| !is_on(event)));
+
+    // Next scan's first column is left strobed (settling)
+    assert_eq!(board.strobed(), Some(0));
+    assert_eq!(matrix.strobe(), 0);
+
+    // Debounced after DEBOUNCE_US worth of full scans
+    for _ in 0..3 {
+        let states = matrix.scan_all(&mut NoDelay).unwrap();
+        assert!(!is_on(&states[1][0]));
+    }
+    let states = matrix.scan_all(&mut NoDelay).unwrap();
+    assert!(is_on(&states[1][0]));
+    for (col, events) in states.iter().enumerate() {
+        for (row, event) in events.iter().enumerate() {
+            assert_eq!(is_on(event), (col, row) == (1, 0));
+        }
+    }
+    assert!(is_on(&matrix.generate_event(RSIZE).unwrap()));
+}
+
+#[test]
+fn scan_all_matches_manual() {
+    let board = Board::new();
+    let manual_board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let mut manual = manual_board.matrix::<0>();
+    manual.next_strobe(&mut NoDelay).unwrap();
+
+    // Bouncy press then release
+    let pattern = [
+        true, false, true, true, true, true, true, false, false, false, false, false,
+    ];
+    for pressed in pattern {
+        for b in [board, manual_board] {
+            b.pressed[2][1].set(pressed);
+        }
+
+        let states = matrix.scan_all(&mut NoDelay).unwrap();
+        for (col, expected) in states.iter().enumerate() {
+            let (events, strobe) = manual.sense::<Fault>().unwrap();
+            assert_eq!(strobe, col);
+            assert_eq!(&events, expected);
+            manual.next_strobe(&mut NoDelay).unwrap();
+        }
+    }
+
+    // Interleaves with a manual scan (resumes from the current strobe)
+    manual.clear().unwrap();
+    manual.next_strobe(&mut NoDelay).unwrap();
+    manual.sense::<Fault>().unwrap();
+    manual.next_strobe(&mut NoDelay).unwrap();
+    assert_eq!(manual.strobe(), 1);
+    manual.scan_all(&mut NoDelay).unwrap();
+    assert_eq!(manual.strobe(), 1);
+}
+
+#[test]
+fn strobe_settle() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<STROBE_SETTLE_US>();
+    let mut delay = Delay {
+        board,
+        delays: std::vec::Vec::new(),
+    };
+
+    // Settles after the new column is strobed
+    assert_eq!(matrix.next_strobe(&mut delay).unwrap(), 0);
+    assert_eq!(delay.delays, [(Some(0), STROBE_SETTLE_US)]);
+
+    // Exactly one fixed delay per strobe, so a full scan is constant time
+    delay.delays.clear();
+    board.pressed[2][1].set(true);
+    matrix.scan_all(&mut delay).unwrap();
+    assert_eq!(
+        delay.delays,
+        [
+            (Some(1), STROBE_SETTLE_US),
+            (Some(2), STROBE_SETTLE_US),
+            (Some(0), STROBE_SETTLE_US),
+        ]
+    );
+
+    // Same debounce results as scanning without settling
+    let manual_board = Board::new();
+    let mut manual = manual_board.matrix::<0>();
+    manual_board.pressed[2][1].set(true);
+    manual.scan_all(&mut NoDelay).unwrap();
+    for _ in 0..4 {
+        assert_eq!(
+            matrix.scan_all(&mut delay).unwrap(),
+            manual.scan_all(&mut NoDelay).unwrap()
+        );
+    }
+    assert!(is_on(&matrix.generate_event(2 * RSIZE + 1).unwrap()));
+
+    // No settling configured, no delay
+    let mut unsettled = Board::new().matrix::<0>();
+    delay.delays.clear();
+    unsettled.scan_all(&mut delay).unwrap();
+    assert!(delay.delays.is_empty());
+}
+
+#[test]
+fn states() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert!(matrix.states().all(|(_, state)| state == State::Off));
+    assert_eq!(matrix.states().count(), MSIZE);
+
+    board.pressed[1][1].set(true);
+    board.pressed[2][0].set(true);
+    for _ in 0..5 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+
+    // Doesn't read the GPIOs
+    board.pressed[1][1].set(false);
+    for (index, state) in matrix.states() {
+        let on = index == RSIZE + 1 || index == 2 * RSIZE;
+        assert_eq!(state, if on { State::On } else { State::Off });
+        assert_eq!(matrix.state_at(index), Some(state));
+    }
+    assert_eq!(matrix.state_at(MSIZE), None);
+}
+
+#[test]
+fn ghosting() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let scan = |matrix: &mut TestMatrix| {
+        let mut states = matrix.scan_all(&mut NoDelay).unwrap();
+        for _ in 0..4 {
+            states = matrix.scan_all(&mut NoDelay).unwrap();
+        }
+        states
+    };
+
+    // Three corners of a rectangle are not a ghosting condition
+    for (col, row) in [(0, 0), (0, 1), (2, 0)] {
+        board.pressed[col][row].set(true);
+    }
+    scan(&mut matrix);
+    assert_eq!(matrix.detect_ghosting(), None);
+
+    // Phantom fourth corner (a diodeless matrix reads it as pressed)
+    board.pressed[2][1].set(true);
+    let states = scan(&mut matrix);
+    assert!(is_on(&states[2][1]));
+    assert_eq!(
+        matrix.detect_ghosting(),
+        Some([(0, 0), (0, 1), (2, 0), (2, 1)])
+    );
+
+    // Masked, only the most recent corner is suppressed
+    matrix.set_ghost_mask(true);
+    let states = matrix.scan_all(&mut NoDelay).unwrap();
+    for (col, row) in [(0, 0), (0, 1), (2, 0)] {
+        assert!(is_on(&states[col][row]));
+    }
+    assert!(!is_on(&states[2][1]));
+    assert!(!is_on(&states[1][0]));
+    assert_eq!(matrix.state_at(2 * RSIZE + 1), Some(State::On));
+
+    // Releasing one of the real corners unmasks it
+    board.pressed[0][0].set(false);
+    let states = scan(&mut matrix);
+    assert!(is_on(&states[2][1]));
+    assert_eq!(matrix.detect_ghosting(), None);
+}
+
+#[test]
+fn rollover() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let mut press = |col: usize, row: usize, pressed: bool| {
+        board.pressed[col][row].set(pressed);
+        for _ in 0..5 {
+            matrix.scan_all(&mut NoDelay).unwrap();
+        }
+        (matrix.pressed_count(), matrix.rollover_safe())
+    };
+
+    // Keys sharing only a column, or only a row, are safe
+    assert_eq!(press(0, 0, true), (1, true));
+    assert_eq!(press(0, 1, true), (2, true));
+    assert_eq!(press(2, 0, false), (2, true));
+    assert_eq!(press(0, 1, false), (1, true));
+    assert_eq!(press(2, 0, true), (2, true));
+
+    // Three corners of a rectangle block further keys
+    assert_eq!(press(0, 1, true), (3, false));
+    assert_eq!(press(2, 1, true), (4, false));
+
+    // Diagonal keys don't share a row or column
+    assert_eq!(press(0, 0, false), (3, false));
+    assert_eq!(press(2, 0, false), (2, true));
+}
+
+#[test]
+fn active_low() {
+    let high = Board::new();
+    let low = Board::new_active_low();
+    let mut matrix = high.matrix_with::<0, false>();
+    let mut inverted = low.matrix_with::<0, true>();
+
+    // Idle strobes are driven high
+    assert!(low.strobes.iter().all(|strobe| strobe.get()));
+    assert_eq!(low.strobed(), None);
+
+    // Same logical results for both polarities
+    let pattern = [
+        true, true, false, true, true, true, true, true, false, false,
+    ];
+    for pressed in pattern {
+        for board in [high, low] {
+            board.pressed[1][1].set(pressed);
+            board.pressed[2][0].set(!pressed);
+        }
+        assert_eq!(
+            matrix.scan_all(&mut NoDelay).unwrap(),
+            inverted.scan_all(&mut NoDelay).unwrap()
+        );
+        assert_eq!(low.strobed(), Some(0));
+        assert!(!low.strobes[0].get());
+    }
+    assert!(inverted.states().eq(matrix.states()));
+    assert_eq!(inverted.pressed_count(), 1);
+
+    inverted.clear().unwrap();
+    assert_eq!(low.strobed(), None);
+}
+
+#[test]
+fn debounce_modes() {
+    let deferred_board = Board::new();
+    let eager_board = Board::new();
+    let mut deferred = deferred_board.matrix::<0>();
+    let mut eager = eager_board.matrix::<0>();
+    eager.set_debounce_mode(DebounceMode::Eager);
+
+    // Bouncy press, then a single scan glitch while released
+    let pattern = [
+        true, false, true, false, true, true, true, true, true, false, false, false, false, false,
+        false, false, false, true, false, false, false, false, false, false,
+    ];
+    let mut history = [[false; 2]; 24];
+    for (pressed, history) in pattern.into_iter().zip(history.iter_mut()) {
+        for board in [deferred_board, eager_board] {
+            board.pressed[0][1].set(pressed);
+        }
+        *history = [
+            is_on(&deferred.scan_all(&mut NoDelay).unwrap()[0][1]),
+            is_on(&eager.scan_all(&mut NoDelay).unwrap()[0][1]),
+        ];
+    }
+    let deferred: std::vec::Vec<bool> = history.iter().map(|h| h[0]).collect();
+    let eager: std::vec::Vec<bool> = history.iter().map(|h| h[1]).collect();
+
+    // Reports the press after DEBOUNCE_US of stable readings, ignores the glitch
+    assert_eq!(deferred.iter().position(|on| *on), Some(8));
+    assert!(deferred[17..].iter().all(|on| !on));
+
+    // Reports the press on the first reading, holds through the bounce (DEBOUNCE_US lockout)
+    assert_eq!(eager.iter().position(|on| *on), Some(0));
+    assert!(eager[..9].iter().all(|on| *on));
+    assert!(!eager[9]);
+    // The glitch is reported
+    assert!(eager[17]);
+}
+
+#[test]
+fn per_key_debounce() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert!(!matrix.set_debounce(MSIZE, Some(0)));
+    assert!(matrix.set_debounce(1, Some(2 * DEBOUNCE_US)));
+    assert_eq!(matrix.state(0).unwrap().debounce_us(), DEBOUNCE_US);
+    assert_eq!(matrix.state(1).unwrap().debounce_us(), 2 * DEBOUNCE_US);
+
+    // Press both keys, the chattery key takes twice as long to settle
+    let press = |matrix: &mut TestMatrix, pressed: bool| {
+        board.pressed[0][0].set(pressed);
+        board.pressed[0][1].set(pressed);
+        let mut first = [None; 2];
+        for scan in 0..20 {
+            let events = matrix.scan_all(&mut NoDelay).unwrap();
+            for (key, first) in first.iter_mut().enumerate() {
+                if first.is_none() && is_on(&events[0][key]) == pressed {
+                    *first = Some(scan);
+                }
+            }
+        }
+        first.map(Option::unwrap)
+    };
+    let [default, chattery] = press(&mut matrix, true);
+    assert_eq!(chattery - default, 4);
+    let [default, chattery] = press(&mut matrix, false);
+    assert_eq!(chattery - default, 4);
+
+    // Back to DEBOUNCE_US
+    matrix.set_debounce(1, None);
+    let [default, chattery] = press(&mut matrix, true);
+    assert_eq!(default, chattery);
+}
+
+#[test]
+fn record_dt() {
+    type Key = KeyState<CSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS>;
+    let period = SCAN_PERIOD_US * CSIZE as u32;
+
+    // Matches record at the nominal scan period
+    let pattern = [
+        true, false, true, true, true, true, true, true, false, false, false, false, false, false,
+    ];
+    for mode in [DebounceMode::Deferred, DebounceMode::Eager] {
+        let (mut cycles, mut timed) = (Key::new(), Key::new());
+        for on in pattern {
+            assert_eq!(
+                cycles.record_with(on, mode),
+                timed.record_dt_with(on, period, mode)
+            );
+        }
+        assert_eq!(cycles.bounce_count(), timed.bounce_count());
+    }
+
+    // Debounced after DEBOUNCE_US of stable readings, regardless of the scan rate
+    let settled = |elapsed: &[u32]| {
+        let mut key = Key::new();
+        elapsed
+            .iter()
+            .position(|elapsed_us| key.record_dt(true, *elapsed_us).0 == State::On)
+    };
+    assert_eq!(settled(&[period; 8]), Some(4));
+    assert_eq!(settled(&[2 * period; 8]), Some(2));
+    assert_eq!(settled(&[period / 2; 16]), Some(8));
+    // Jitter, DEBOUNCE_US is reached at index 4 (60 + 300 + 30 + 90)
+    assert_eq!(settled(&[500, 60, 300, 30, 90, 500]), Some(4));
+
+    // Bounce within DEBOUNCE_US, even though more than 4 (fast) scans apart
+    let mut key = Key::new();
+    assert_eq!(key.record_dt(true, DEBOUNCE_US / 8).0, State::Off);
+    for _ in 0..6 {
+        assert_eq!(key.record_dt(true, DEBOUNCE_US / 8).0, State::Off);
+    }
+    key.record_dt(false, DEBOUNCE_US / 8);
+    assert_eq!(key.bounce_count(), 1);
+
+    // Idle after IDLE_MS in the off state
+    let mut key = Key::new();
+    key.record_dt(false, IDLE_MS * 500);
+    assert!(!key.idle());
+    key.record_dt(false, IDLE_MS * 500);
+    assert!(key.idle());
+    assert_eq!(key.cycles_since_state_change(), 2);
+
+    // IDLE_MS past u32::MAX us (about 71.6 minutes)
+    let mut key = KeyState::<CSIZE, SCAN_PERIOD_US, DEBOUNCE_US, 5_000_000>::new();
+    key.record_dt(false, u32::MAX);
+    assert!(!key.idle());
+    assert_eq!(key.us_since_state_change(), u32::MAX as u64);
+    key.record_dt(false, u32::MAX);
+    assert!(key.idle());
+    assert_eq!(key.cycles_until_idle(), 0);
+}
+
+#[test]
+fn sense_dt() {
+    let board = Board::new();
+    let timed_board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let mut timed = timed_board.matrix::<0>();
+    let period = SCAN_PERIOD_US * CSIZE as u32;
+
+    // Scan the column of the key, returning its event
+    let scan = |matrix: &mut TestMatrix, elapsed_us: Option<u32>| {
+        matrix.clear().unwrap();
+        matrix.next_strobe(&mut NoDelay).unwrap();
+        let (events, _) = match elapsed_us {
+            Some(elapsed_us) => matrix.sense_dt::<Fault>(elapsed_us).unwrap(),
+            None => matrix.sense::<Fault>().unwrap(),
+        };
+        events[1]
+    };
+
+    // Matches sense at the nominal scan period
+    let pattern = [
+        true, false, true, true, true, true, true, false, false, false, false, false, false,
+    ];
+    for pressed in pattern {
+        for b in [board, timed_board] {
+            b.pressed[0][1].set(pressed);
+        }
+        assert_eq!(scan(&mut matrix, None), scan(&mut timed, Some(period)));
+    }
+
+    // Debounced after DEBOUNCE_US of measured time (2 slow scans rather than 4)
+    timed_board.pressed[0][1].set(true);
+    for _ in 0..2 {
+        assert!(!is_on(&scan(&mut timed, Some(2 * period))));
+    }
+    assert!(is_on(&scan(&mut timed, Some(2 * period))));
+}
+
+#[test]
+fn bounce_count() {
+    for mode in [DebounceMode::Deferred, DebounceMode::Eager] {
+        let board = Board::new();
+        let mut matrix = board.matrix::<0>();
+        matrix.set_debounce_mode(mode);
+        let scan = |matrix: &mut TestMatrix, pattern: &[bool]| {
+            for pressed in pattern {
+                board.pressed[0][1].set(*pressed);
+                matrix.scan_all(&mut NoDelay).unwrap();
+            }
+        };
+
+        // Clean press and release
+        scan(&mut matrix, &[true; 10]);
+        scan(&mut matrix, &[false; 10]);
+        assert_eq!(matrix.bounce_count(1), Some(0));
+
+        // Bouncy press
+        scan(&mut matrix, &[true, false, true, false, true]);
+        scan(&mut matrix, &[true; 10]);
+        scan(&mut matrix, &[false; 10]);
+        assert_eq!(matrix.bounce_count(1), Some(4), "{:?}", mode);
+        assert_eq!(matrix.bounce_count(0), Some(0));
+        assert_eq!(matrix.bounce_count(MSIZE), None);
+
+        assert!(matrix.reset_bounce_count(1));
+        assert_eq!(matrix.bounce_count(1), Some(0));
+        assert!(!matrix.reset_bounce_count(MSIZE));
+    }
+}
+
+#[test]
+fn repeat() {
+    // 1 ms delay and rate, the scan cycle is 120 us
+    type RepeatMatrix =
+        Matrix<Pin, Pin, CSIZE, RSIZE, MSIZE, SCAN_PERIOD_US, DEBOUNCE_US, IDLE_MS, 0, false, 1, 1>;
+    let board = Board::new();
+    let cols = core::array::from_fn(|col| Pin::Col(board, col));
+    let rows = core::array::from_fn(|row| Pin::Row(board, row));
+    let mut matrix = RepeatMatrix::new::<Fault>(cols, rows).unwrap();
+
+    let hold = |matrix: &mut RepeatMatrix, pressed: bool, scans: usize| {
+        board.pressed[1][0].set(pressed);
+        let mut repeats = std::vec::Vec::new();
+        for _ in 0..scans {
+            if let KeyEvent::Repeat {
+                count,
+                cycles_since_state_change,
+            } = matrix.scan_all(&mut NoDelay).unwrap()[1][0]
+            {
+                repeats.push((count, cycles_since_state_change));
+            }
+        }
+        repeats
+    };
+
+    // Repeats after the delay, then at the rate (relative to the debounced press)
+    assert_eq!(hold(&mut matrix, true, 30), [(1, 9), (2, 17), (3, 25)]);
+
+    // Releasing cancels, pressing again starts over
+    assert!(hold(&mut matrix, false, 30).is_empty());
+    assert_eq!(hold(&mut matrix, true, 14), [(1, 9)]);
+
+    // Released before the delay
+    assert!(hold(&mut matrix, false, 30).is_empty());
+    assert!(hold(&mut matrix, true, 8).is_empty());
+    assert!(hold(&mut matrix, false, 30).is_empty());
+}
+
+#[test]
+fn tap_counter() {
+    // 1 ms window, the scan cycle is 120 us (9 cycles)
+    let mut taps = TapCounter::<CSIZE, SCAN_PERIOD_US, 1>::new();
+    let on = |cycles_since_state_change| KeyEvent::On {
+        cycles_since_state_change,
+    };
+    let off = |cycles_since_state_change| KeyEvent::Off {
+        idle: false,
+        cycles_since_state_change,
+    };
+    let tap = |taps: &mut TapCounter<CSIZE, SCAN_PERIOD_US, 1>, gap: u32| {
+        let mut done = None;
+        for cycles in 0..3 {
+            assert_eq!(taps.add(on(cycles)), None);
+        }
+        for cycles in 0..gap {
+            done = done.or(taps.add(off(cycles)));
+        }
+        done
+    };
+
+    // Idle
+    assert_eq!(taps.add(off(100)), None);
+
+    // Single, double and triple taps
+    assert_eq!(tap(&mut taps, 20), Some(1));
+    assert_eq!(tap(&mut taps, 5), None);
+    assert_eq!(tap(&mut taps, 20), Some(2));
+    assert_eq!(tap(&mut taps, 8), None);
+    assert_eq!(tap(&mut taps, 8), None);
+    assert_eq!(taps.taps(), 2);
+    assert_eq!(tap(&mut taps, 9), None);
+    assert_eq!(taps.taps(), 3);
+    // Reported once the gap exceeds the window
+    assert_eq!(taps.add(off(9)), Some(3));
+    assert_eq!(taps.add(off(10)), None);
+
+    // Holding keeps the sequence open
+    for cycles in 0..100 {
+        assert_eq!(taps.add(on(cycles)), None);
+    }
+    for cycles in 0..5 {
+        assert_eq!(taps.add(off(cycles)), None);
+    }
+    assert_eq!(tap(&mut taps, 20), Some(2));
+
+    // Dropped sequence
+    assert_eq!(tap(&mut taps, 2), None);
+    taps.reset();
+    assert_eq!(taps.add(off(20)), None);
+
+    // Bounce right after the debounced press (repeats On with 0 cycles), still a single tap
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let mut taps = TapCounter::<CSIZE, SCAN_PERIOD_US, 1>::new();
+    let mut edges = 0;
+    for pressed in [[true; 5].as_slice(), &[false], &[true; 4]].concat() {
+        board.pressed[0][1].set(pressed);
+        let event = matrix.scan_all(&mut NoDelay).unwrap()[0][1];
+        if event == on(0) {
+            edges += 1;
+        }
+        assert_eq!(taps.add(event), None);
+    }
+    assert_eq!(edges, 3);
+    assert_eq!(taps.taps(), 1);
+}
+
+#[test]
+fn tap_hold() {
+    // 1 ms threshold, the scan cycle is 120 us (9 cycles)
+    let mut tap_hold = TapHold::<CSIZE, SCAN_PERIOD_US, 1>::new();
+    let on = |cycles_since_state_change| KeyEvent::On {
+        cycles_since_state_change,
+    };
+    let off = |cycles_since_state_change| KeyEvent::Off {
+        idle: false,
+        cycles_since_state_change,
+    };
+    let press = |tap_hold: &mut TapHold<CSIZE, SCAN_PERIOD_US, 1>, held: u32| {
+        let mut decisions = std::vec::Vec::new();
+        for cycles in 0..held {
+            decisions.extend(tap_hold.add(on(cycles)).map(|decision| (cycles, decision)));
+        }
+        for cycles in 0..3 {
+            decisions.extend(tap_hold.add(off(cycles)).map(|decision| (cycles, decision)));
+        }
+        decisions
+    };
+
+    // Idle
+    assert_eq!(tap_hold.add(off(100)), None);
+    assert!(!tap_hold.pending());
+
+    // Tap, decided on release
+    assert_eq!(press(&mut tap_hold, 3), [(0, TapHoldDecision::Tap)]);
+    assert_eq!(press(&mut tap_hold, 9), [(0, TapHoldDecision::Tap)]);
+
+    // Hold, decided when the threshold is crossed (not again on release)
+    assert_eq!(press(&mut tap_hold, 10), [(9, TapHoldDecision::Hold)]);
+    assert_eq!(press(&mut tap_hold, 100), [(9, TapHoldDecision::Hold)]);
+
+    // Auto-repeat counts as held
+    for cycles in 0..5 {
+        assert_eq!(tap_hold.add(on(cycles)), None);
+    }
+    assert!(tap_hold.pending());
+    assert_eq!(
+        tap_hold.add(KeyEvent::Repeat {
+            count: 1,
+            cycles_since_state_change: 9,
+        }),
+        Some(TapHoldDecision::Hold)
+    );
+    assert!(!tap_hold.pending());
+    assert_eq!(tap_hold.add(off(0)), None);
+
+    // Already held when first seen, decided right away
+    let mut tap_hold = TapHold::<CSIZE, SCAN_PERIOD_US, 1>::new();
+    assert_eq!(tap_hold.add(on(50)), Some(TapHoldDecision::Hold));
+    assert_eq!(tap_hold.add(off(0)), None);
+
+    // Dropped press
+    assert_eq!(tap_hold.add(on(0)), None);
+    tap_hold.reset();
+    assert_eq!(tap_hold.add(off(0)), None);
+
+    // Bounce right after the debounced press (repeats On with 0 cycles), still a single press
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let mut tap_hold = TapHold::<CSIZE, SCAN_PERIOD_US, 1>::new();
+    let mut decisions = std::vec::Vec::new();
+    for pressed in [[true; 5].as_slice(), &[false], &[true; 20]].concat() {
+        board.pressed[0][1].set(pressed);
+        decisions.extend(tap_hold.add(matrix.scan_all(&mut NoDelay).unwrap()[0][1]));
+    }
+    assert_eq!(decisions, [TapHoldDecision::Hold]);
+}
+
+#[test]
+fn changes() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    let scan = |matrix: &mut TestMatrix, scans: usize| {
+        let mut changes = std::vec::Vec::new();
+        for _ in 0..scans {
+            matrix.scan_all(&mut NoDelay).unwrap();
+            changes.extend(matrix.changes());
+        }
+        changes
+    };
+    assert!(scan(&mut matrix, 10).is_empty());
+
+    // Reported once, on the scan that debounced the change
+    board.pressed[0][1].set(true);
+    board.pressed[2][0].set(true);
+    assert_eq!(scan(&mut matrix, 10), [(1, State::On), (4, State::On)]);
+    assert_eq!(matrix.changes().count(), 0);
+
+    // Bouncing doesn't report
+    board.pressed[0][1].set(false);
+    matrix.scan_all(&mut NoDelay).unwrap();
+    board.pressed[0][1].set(true);
+    assert!(scan(&mut matrix, 10).is_empty());
+
+    board.pressed[2][0].set(false);
+    assert_eq!(scan(&mut matrix, 10), [(4, State::Off)]);
+}
+
+#[test]
+fn all_idle() {
+    // IDLE_MS in scan cycles (120 us each)
+    const IDLE_CYCLES: u32 = (IDLE_MS * 1000).div_ceil(SCAN_PERIOD_US * CSIZE as u32);
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert!(!matrix.all_idle());
+    assert_eq!(matrix.cycles_until_idle(), IDLE_CYCLES);
+
+    for _ in 0..IDLE_CYCLES - 1 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    assert!(!matrix.all_idle());
+    assert_eq!(matrix.cycles_until_idle(), 1);
+    matrix.scan_all(&mut NoDelay).unwrap();
+    assert!(matrix.all_idle());
+    assert_eq!(matrix.cycles_until_idle(), 0);
+
+    // A pressed key is never idle
+    board.pressed[1][1].set(true);
+    for _ in 0..10 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    assert!(!matrix.all_idle());
+    assert_eq!(matrix.cycles_until_idle(), u32::MAX);
+
+    // The last released key decides
+    board.pressed[1][1].set(false);
+    for _ in 0..10 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    let remaining = matrix.cycles_until_idle();
+    assert!(remaining < IDLE_CYCLES && remaining > IDLE_CYCLES - 10);
+    for _ in 0..remaining {
+        assert!(!matrix.all_idle());
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    assert!(matrix.all_idle());
+}
+
+#[test]
+fn scan_error() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    matrix.next_strobe(&mut NoDelay).unwrap();
+    matrix.next_strobe(&mut NoDelay).unwrap();
+
+    // Failing sense line, reports the strobed column and the row
+    board.faulty_row.set(Some(1));
+    assert_eq!(
+        matrix.sense::<Fault>(),
+        Err(ScanError {
+            strobe: 1,
+            row: Some(1),
+            source: Fault,
+        })
+    );
+    board.faulty_row.set(None);
+
+    // Failing strobe, moving onto it
+    board.faulty_strobe.set(Some(2));
+    assert_eq!(
+        matrix.next_strobe(&mut NoDelay),
+        Err(ScanError {
+            strobe: 2,
+            row: None,
+            source: Fault,
+        })
+    );
+
+    // Failing strobe, moving off of it
+    assert_eq!(
+        matrix.next_strobe(&mut NoDelay),
+        Err(ScanError {
+            strobe: 2,
+            row: None,
+            source: Fault,
+        })
+    );
+
+    // Failing strobe, clearing
+    assert_eq!(
+        matrix.clear(),
+        Err(ScanError {
+            strobe: 2,
+            row: None,
+            source: Fault,
+        })
+    );
+    board.faulty_strobe.set(None);
+
+    // Full scan stops at the failing pin
+    matrix.clear::<Fault>().unwrap();
+    board.faulty_row.set(Some(0));
+    assert_eq!(
+        matrix.scan_all(&mut NoDelay),
+        Err(ScanError {
+            strobe: 0,
+            row: Some(0),
+            source: Fault,
+        })
+    );
+}
+
+#[test]
+fn no_drain() {
+    let board = Board::new();
+    let plain_board = Board::new();
+    let mut matrix = board.matrix::<STROBE_SETTLE_US>();
+    let cols = core::array::from_fn(|col| PlainPin(Pin::Col(plain_board, col)));
+    let rows = core::array::from_fn(|row| PlainPin(Pin::Row(plain_board, row)));
+    let mut plain = Matrix::<
+        PlainPin,
+        PlainPin,
+        CSIZE,
+        RSIZE,
+        MSIZE,
+        SCAN_PERIOD_US,
+        DEBOUNCE_US,
+        IDLE_MS,
+        STROBE_SETTLE_US,
+        false,
+        0,
+        0,
+        false,
+    >::new::<Fault>(cols, rows)
+    .unwrap();
+
+    // Same results as a drained matrix
+    board.pressed[1][0].set(true);
+    plain_board.pressed[1][0].set(true);
+    for _ in 0..6 {
+        assert_eq!(
+            matrix.scan_all(&mut NoDelay).unwrap(),
+            plain.scan_all(&mut NoDelay).unwrap()
+        );
+    }
+    assert_eq!(plain.state(RSIZE).unwrap().state().0, State::On);
+    assert_eq!(plain_board.strobed(), Some(0));
+
+    // Manual scan loop, settling after each strobe
+    let mut delay = Delay {
+        board: plain_board,
+        delays: std::vec::Vec::new(),
+    };
+    assert_eq!(plain.next_strobe(&mut delay).unwrap(), 1);
+    assert_eq!(delay.delays, [(Some(1), STROBE_SETTLE_US)]);
+    let (events, strobe) = plain.sense::<Fault>().unwrap();
+    assert_eq!(strobe, 1);
+    assert!(is_on(&events[0]));
+    plain.scan_all(&mut delay).unwrap();
+    assert_eq!(delay.delays.len(), 1 + CSIZE);
+    assert_eq!(plain.next_strobe(&mut NoDelay).unwrap(), 2);
+}
+
+#[test]
+fn strobe_order() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert_eq!(matrix.strobe_order(), &[0, 1, 2]);
+
+    // Not a permutation
+    assert!(!matrix.set_strobe_order([0, 1, 3]));
+    assert!(!matrix.set_strobe_order([0, 2, 2]));
+    assert_eq!(matrix.strobe_order(), &[0, 1, 2]);
+
+    // Set before the first strobe, starts at the first column of the order
+    assert!(matrix.set_strobe_order([2, 0, 1]));
+    assert_eq!(matrix.strobe(), 1);
+
+    // Columns are strobed in order, each sense reports its GPIO column
+    board.pressed[0][1].set(true);
+    for col in [2, 0, 1, 2] {
+        assert_eq!(matrix.next_strobe(&mut NoDelay).unwrap(), col);
+        assert_eq!(board.strobed(), Some(col));
+        assert_eq!(matrix.sense::<Fault>().unwrap().1, col);
+    }
+
+    // Keys are still indexed by GPIO column
+    for _ in 0..4 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    let events = matrix.scan_all(&mut NoDelay).unwrap();
+    for (col, column) in events.iter().enumerate() {
+        for (row, event) in column.iter().enumerate() {
+            let pressed = (col, row) == (0, 1);
+            assert_eq!(is_on(event), pressed);
+            assert_eq!(
+                matrix.state(col * RSIZE + row).unwrap().state().0 == State::On,
+                pressed
+            );
+        }
+    }
+
+    // Changing the order keeps the current strobe
+    let strobe = matrix.strobe();
+    assert!(matrix.set_strobe_order([0, 1, 2]));
+    assert_eq!(matrix.strobe(), strobe);
+    assert_eq!(
+        matrix.next_strobe(&mut NoDelay).unwrap(),
+        (strobe + 1) % CSIZE
+    );
+}
+
+#[cfg(feature = "hall-effect")]
+mod hybrid {
+    use super::*;
+    use crate::hybrid::{Hybrid, HybridEvent, Source};
+    use kiibohd_hall_effect::{ActuationEvent, Sensors};
+    use std::vec::Vec;
+
+    const REST: u16 = 1500;
+
+    #[test]
+    fn events() {
+        let board = Board::new();
+        let mut matrix = board.matrix::<0>();
+        let mut sensors = Sensors::<2>::new();
+        // Matrix keys at 0..6, analog keys at 10 and 6
+        let hybrid = Hybrid::new(0, [10, MSIZE]);
+        assert_eq!(hybrid.matrix_index(3), 3);
+        assert_eq!(hybrid.analog_index(0), Some(10));
+        assert_eq!(hybrid.analog_index(2), None);
+
+        let mut scan = |sensors: &mut Sensors<2>, readings: [u16; 2]| -> Vec<HybridEvent> {
+            let states = matrix.scan_all(&mut NoDelay).unwrap();
+            for _ in 0..4 {
+                for (index, reading) in readings.iter().enumerate() {
+                    sensors.add::<1>(index, *reading).unwrap();
+                }
+            }
+            hybrid
+                .events::<CSIZE, RSIZE, 200, 50, 0, 1>(&states, matrix.changes(), sensors)
+                .collect()
+        };
+
+        // Nothing changes at rest
+        for _ in 0..6 {
+            assert_eq!(scan(&mut sensors, [REST, REST]), []);
+        }
+
+        // Analog and digital presses
+        board.pressed[1][0].set(true);
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            events.extend(scan(&mut sensors, [REST, REST + 300]));
+        }
+        assert_eq!(
+            events,
+            [
+                HybridEvent {
+                    index: MSIZE,
+                    source: Source::Analog(ActuationEvent::Press),
+                },
+                HybridEvent {
+                    index: RSIZE,
+                    source: Source::Matrix(KeyEvent::On {
+                        cycles_since_state_change: 0,
+                    }),
+                },
+            ]
+        );
+
+        // Releases
+        board.pressed[1][0].set(false);
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            events.extend(scan(&mut sensors, [REST, REST]));
+        }
+        assert_eq!(
+            events,
+            [
+                HybridEvent {
+                    index: MSIZE,
+                    source: Source::Analog(ActuationEvent::Release),
+                },
+                HybridEvent {
+                    index: RSIZE,
+                    source: Source::Matrix(KeyEvent::Off {
+                        idle: false,
+                        cycles_since_state_change: 0,
+                    }),
+                },
+            ]
+        );
+
+        // Bounce right after the debounced press (repeats On with 0 cycles), a single press
+        let mut events = Vec::new();
+        for pressed in [[true; 5].as_slice(), &[false], &[true; 4]].concat() {
+            board.pressed[1][0].set(pressed);
+            events.extend(scan(&mut sensors, [REST, REST]));
+        }
+        assert_eq!(
+            events,
+            [HybridEvent {
+                index: RSIZE,
+                source: Source::Matrix(KeyEvent::On {
+                    cycles_since_state_change: 0,
+                }),
+            }]
+        );
+    }
+}
+
+#[test]
+fn period() {
+    let board = Board::new();
+    let mut matrix = board.matrix::<0>();
+    assert_eq!(matrix.period(), SCAN_PERIOD_US);
+    assert_eq!(TestMatrix::<0>::cycles_to_us(0), 0);
+    assert_eq!(
+        TestMatrix::<0>::cycles_to_us(10),
+        10 * SCAN_PERIOD_US * CSIZE as u32
+    );
+    assert_eq!(TestMatrix::<0>::cycles_to_us(u32::MAX), u32::MAX);
+
+    // Time since a press
+    board.pressed[0][0].set(true);
+    for _ in 0..16 {
+        matrix.scan_all(&mut NoDelay).unwrap();
+    }
+    let cycles = matrix.state(0).unwrap().cycles_since_state_change();
+    assert!(cycles > 0);
+    assert_eq!(
+        TestMatrix::<0>::cycles_to_us(cycles),
+        cycles * matrix.period() * CSIZE as u32
+    );
+}
+
+#[test]
+fn self_test() {
+    let shorts = |board: &'static Board| -> std::vec::Vec<(MatrixPin, MatrixPin)> {
+        let report = board.matrix::<0>().self_test().unwrap();
+        assert!(!report.truncated);
+        assert_eq!(board.strobed(), None);
+        report.shorts().collect()
+    };
+
+    // Clean board
+    let board = Board::new();
+    assert!(board.matrix::<0>().self_test().unwrap().passed());
+
+    // Each bridge is reported once, whichever line is driven
+    for short in [
+        (MatrixPin::Strobe(1), MatrixPin::Sense(0)),
+        (MatrixPin::Strobe(0), MatrixPin::Strobe(2)),
+        (MatrixPin::Sense(0), MatrixPin::Sense(1)),
+    ] {
+        let board = Board::new();
+        board.short.set(Some(short));
+        assert_eq!(shorts(board), [short]);
+        assert!(board.floating.iter().all(|floating| !floating.get()));
+        assert!(board
+            .driven_rows
+            .iter()
+            .all(|driven| driven.get().is_none()));
+    }
+
+    // Active low
+    let board = Board::new_active_low();
+    board
+        .short
+        .set(Some((MatrixPin::Strobe(2), MatrixPin::Sense(1))));
+    let mut matrix = board.matrix_with::<0, true>();
+    let report = matrix.self_test().unwrap();
+    assert_eq!(
+        report.shorts().collect::<std::vec::Vec<_>>(),
+        [(MatrixPin::Strobe(2), MatrixPin::Sense(1))]
+    );
+    assert_eq!(board.strobed(), None);
+    let board = Board::new_active_low();
+    assert!(board.matrix_with::<0, true>().self_test().unwrap().passed());
+
+    // GPIO failures carry the failing pin
+    let board = Board::new();
+    board.faulty_row.set(Some(1));
+    assert_eq!(
+        board.matrix::<0>().self_test(),
+        Err(ScanError {
+            strobe: 0,
+            row: Some(1),
+            source: Fault,
+        })
+    );
+}